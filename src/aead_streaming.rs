@@ -0,0 +1,279 @@
+use std::io::{Read, Result, Write};
+
+use crate::error::SplitSecretError;
+use crate::utils::read_full;
+
+use aead::consts::U12;
+use aead::generic_array::GenericArray;
+use aead::stream::{DecryptorBE32, EncryptorBE32};
+use aead::{AeadCore, AeadInPlace, KeyInit};
+use cipher::Unsigned;
+
+/// Number of plaintext bytes sealed together with one authentication tag.
+pub const CHUNK_SIZE: usize = 4096;
+/// Length of the random nonce prefix; the remaining 5 bytes of the nonce are the STREAM counter
+/// and last-chunk flag.
+pub const NONCE_PREFIX_SIZE: usize = 7;
+
+fn authentication_error() -> std::io::Error {
+    SplitSecretError::Authentication.into()
+}
+
+/// Encrypts everything read from the wrapped reader with the STREAM construction, sealing every
+/// [`CHUNK_SIZE`] bytes of plaintext separately so that the ciphertext can be produced and
+/// verified incrementally.
+pub struct AeadEncryptReadStream<A, R>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    R: Read,
+{
+    // None once the last chunk has been sealed.
+    encryptor: Option<EncryptorBE32<A>>,
+    reader: R,
+    // One byte more than a chunk, so that we can tell whether a full chunk is the last one.
+    plaintext: Vec<u8>,
+    plaintext_bytes: usize,
+    ciphertext: Vec<u8>,
+    ciphertext_read: usize,
+}
+
+impl<A, R> AeadEncryptReadStream<A, R>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    R: Read,
+{
+    /// `nonce_prefix` must be [`NONCE_PREFIX_SIZE`] bytes long and must never be reused with the
+    /// same key.
+    pub fn new(aead: A, nonce_prefix: &[u8], reader: R) -> Self {
+        Self {
+            encryptor: Some(EncryptorBE32::from_aead(
+                aead,
+                GenericArray::from_slice(nonce_prefix),
+            )),
+            reader,
+            plaintext: vec![0u8; CHUNK_SIZE + 1],
+            plaintext_bytes: 0,
+            ciphertext: Vec::new(),
+            ciphertext_read: 0,
+        }
+    }
+
+    fn fill_buf(&mut self) -> Result<usize> {
+        if self.ciphertext_read < self.ciphertext.len() || self.encryptor.is_none() {
+            return Ok(self.ciphertext.len() - self.ciphertext_read);
+        }
+        self.plaintext_bytes += read_full(
+            &mut self.reader,
+            &mut self.plaintext[self.plaintext_bytes..],
+        )?;
+        self.ciphertext = if self.plaintext_bytes > CHUNK_SIZE {
+            let ciphertext = self
+                .encryptor
+                .as_mut()
+                .unwrap()
+                .encrypt_next(&self.plaintext[..CHUNK_SIZE])
+                .map_err(|_| authentication_error())?;
+            self.plaintext[0] = self.plaintext[CHUNK_SIZE];
+            self.plaintext_bytes = 1;
+            ciphertext
+        } else {
+            self.encryptor
+                .take()
+                .unwrap()
+                .encrypt_last(&self.plaintext[..self.plaintext_bytes])
+                .map_err(|_| authentication_error())?
+        };
+        self.ciphertext_read = 0;
+        Ok(self.ciphertext.len())
+    }
+}
+
+impl<A, R> Read for AeadEncryptReadStream<A, R>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let available = self.fill_buf()?;
+        let read_size = available.min(buf.len());
+        buf[..read_size].copy_from_slice(
+            &self.ciphertext[self.ciphertext_read..self.ciphertext_read + read_size],
+        );
+        self.ciphertext_read += read_size;
+        Ok(read_size)
+    }
+}
+
+/// Decrypts and authenticates a stream produced by [`AeadEncryptReadStream`] before passing it
+/// to the wrapped writer.
+///
+/// Every chunk is authenticated before any of its plaintext is written. The last chunk is only
+/// written once the stream is flushed, which fails if the ciphertext was truncated.
+pub struct AeadDecryptWriteStream<A, W>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    W: Write,
+{
+    // None once the last chunk has been opened.
+    decryptor: Option<DecryptorBE32<A>>,
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<A, W> AeadDecryptWriteStream<A, W>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    W: Write,
+{
+    const CIPHERTEXT_CHUNK_SIZE: usize = CHUNK_SIZE + <A as AeadCore>::TagSize::USIZE;
+
+    /// `nonce_prefix` must be the prefix the stream was encrypted with.
+    pub fn new(aead: A, nonce_prefix: &[u8], writer: W) -> Self {
+        Self {
+            decryptor: Some(DecryptorBE32::from_aead(
+                aead,
+                GenericArray::from_slice(nonce_prefix),
+            )),
+            writer,
+            buf: Vec::with_capacity(Self::CIPHERTEXT_CHUNK_SIZE + 1),
+        }
+    }
+}
+
+impl<A, W> Write for AeadDecryptWriteStream<A, W>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let decryptor = match self.decryptor.as_mut() {
+            Some(decryptor) => decryptor,
+            None => return Err(authentication_error()),
+        };
+        self.buf.extend_from_slice(buf);
+        // Only open a chunk once we have seen a byte past it, since the last chunk is sealed
+        // differently.
+        while self.buf.len() > Self::CIPHERTEXT_CHUNK_SIZE {
+            let plaintext = decryptor
+                .decrypt_next(&self.buf[..Self::CIPHERTEXT_CHUNK_SIZE])
+                .map_err(|_| authentication_error())?;
+            self.writer.write_all(&plaintext)?;
+            self.buf.drain(..Self::CIPHERTEXT_CHUNK_SIZE);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(decryptor) = self.decryptor.take() {
+            let plaintext = decryptor
+                .decrypt_last(&self.buf[..])
+                .map_err(|_| authentication_error())?;
+            self.writer.write_all(&plaintext)?;
+            self.buf.clear();
+        }
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use aes_gcm::Aes256Gcm;
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    const NONCE_PREFIX: [u8; NONCE_PREFIX_SIZE] = [7u8; NONCE_PREFIX_SIZE];
+
+    fn encrypt<A>(plaintext: &[u8]) -> Vec<u8>
+    where
+        A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    {
+        let aead = A::new(&GenericArray::default());
+        let mut ciphertext = Vec::new();
+        AeadEncryptReadStream::new(aead, &NONCE_PREFIX, plaintext)
+            .read_to_end(&mut ciphertext)
+            .unwrap();
+        ciphertext
+    }
+
+    fn decrypt<A>(ciphertext: &[u8]) -> std::result::Result<Vec<u8>, SplitSecretError>
+    where
+        A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    {
+        let aead = A::new(&GenericArray::default());
+        let mut plaintext = Vec::new();
+        let mut stream = AeadDecryptWriteStream::new(aead, &NONCE_PREFIX, &mut plaintext);
+        // Write in odd-sized pieces so that chunks straddle writes.
+        for piece in ciphertext.chunks(1000) {
+            stream.write_all(piece)?;
+        }
+        stream.flush()?;
+        Ok(plaintext)
+    }
+
+    fn base_round_trip<A>()
+    where
+        A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    {
+        for size in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE + 5,
+        ] {
+            let plaintext: Vec<u8> = (0..size).map(|i| i as u8).collect();
+            let ciphertext = encrypt::<A>(&plaintext);
+            assert_eq!(decrypt::<A>(&ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn round_trip_aes_gcm() {
+        base_round_trip::<Aes256Gcm>();
+    }
+
+    #[test]
+    fn round_trip_chacha20_poly1305() {
+        base_round_trip::<ChaCha20Poly1305>();
+    }
+
+    #[test]
+    fn modified() {
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE + 10];
+        let mut ciphertext = encrypt::<Aes256Gcm>(&plaintext);
+        ciphertext[CHUNK_SIZE + 100] ^= 1;
+        assert!(matches!(
+            decrypt::<Aes256Gcm>(&ciphertext),
+            Err(SplitSecretError::Authentication)
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE + 10];
+        let ciphertext = encrypt::<Aes256Gcm>(&plaintext);
+        let chunk = AeadDecryptWriteStream::<Aes256Gcm, Vec<u8>>::CIPHERTEXT_CHUNK_SIZE;
+        for length in [0, chunk, 2 * chunk, ciphertext.len() - 1] {
+            assert!(matches!(
+                decrypt::<Aes256Gcm>(&ciphertext[..length]),
+                Err(SplitSecretError::Authentication)
+            ));
+        }
+    }
+
+    #[test]
+    fn reordered() {
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE + 10];
+        let ciphertext = encrypt::<ChaCha20Poly1305>(&plaintext);
+        let chunk = AeadDecryptWriteStream::<ChaCha20Poly1305, Vec<u8>>::CIPHERTEXT_CHUNK_SIZE;
+        let mut reordered = ciphertext[chunk..2 * chunk].to_vec();
+        reordered.extend_from_slice(&ciphertext[..chunk]);
+        reordered.extend_from_slice(&ciphertext[2 * chunk..]);
+        assert!(matches!(
+            decrypt::<ChaCha20Poly1305>(&reordered),
+            Err(SplitSecretError::Authentication)
+        ));
+    }
+}
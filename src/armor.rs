@@ -0,0 +1,411 @@
+use std::io::{self, BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Result, SplitSecretError};
+use crate::header::{ShareHeader, HEADER_SIZE};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// First line of an armored share.
+pub const BEGIN: &str = "-----BEGIN SPLIT-SECRET SHARE-----";
+/// Last line of an armored share.
+pub const END: &str = "-----END SPLIT-SECRET SHARE-----";
+
+// Bytes of the share per line of base64, which makes lines of 64 characters.
+const LINE_BYTES: usize = 48;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The fields shown before the body of an armored share, for whoever handles it.
+fn fields(header: &ShareHeader) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("Scheme", header.scheme.to_string()),
+        ("Index", header.x.to_string()),
+    ];
+    if header.weight > 1 {
+        fields.push(("Weight", header.weight.to_string()));
+    }
+    fields.push(("Threshold", header.k.to_string()));
+    fields.push(("Set-ID", hex(&header.set_id)));
+    fields
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Writes a share file as text that survives being pasted into email, chat or a ticket: a
+/// [`BEGIN`] line, the header fields of the share as `Key: value` lines, a blank line, the whole
+/// share file in base64, and an [`END`] line.
+///
+/// The share header is taken from the first bytes written, which must be a share file such as
+/// the ones written by [`crate::split_shares`].
+pub struct ArmorWriter<W: Write> {
+    writer: W,
+    // Bytes not yet encoded: the share header until all of it has been written, then less than a
+    // line.
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl<W: Write> ArmorWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let header = ShareHeader::read(&mut &self.pending[..]).map_err(|e| match e {
+            SplitSecretError::Io(_) => {
+                SplitSecretError::InvalidHeader("the share is too short to armor".into())
+            }
+            e => e,
+        })?;
+        let mut text = format!("{}\n", BEGIN);
+        for (key, value) in fields(&header) {
+            text.push_str(&format!("{}: {}\n", key, value));
+        }
+        text.push('\n');
+        self.writer.write_all(text.as_bytes())?;
+        self.started = true;
+        Ok(())
+    }
+
+    // Encodes the whole lines pending, and with all, the partial line at the end too.
+    fn write_lines(&mut self, all: bool) -> io::Result<()> {
+        let whole = match all {
+            true => self.pending.len(),
+            false => self.pending.len() - self.pending.len() % LINE_BYTES,
+        };
+        let mut text = String::new();
+        for line in self.pending[..whole].chunks(LINE_BYTES) {
+            text.push_str(&STANDARD.encode(line));
+            text.push('\n');
+        }
+        self.writer.write_all(text.as_bytes())?;
+        self.pending.drain(..whole);
+        Ok(())
+    }
+
+    /// Writes the rest of the body and the [`END`] line, and returns the wrapped writer.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.started {
+            self.start()?;
+        }
+        self.write_lines(true)?;
+        writeln!(self.writer, "{}", END)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ArmorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if !self.started && self.pending.len() >= HEADER_SIZE {
+            self.start()?;
+        }
+        if self.started {
+            self.write_lines(false)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads the share file out of a share armored by [`ArmorWriter`].
+///
+/// Blank lines before the [`BEGIN`] line, whitespace around and within lines, and lines of base64
+/// wrapped at any width are all accepted, since text passed around by hand tends to pick them up.
+/// The header fields are checked against the share header in the body, which is what a join goes
+/// by; fields that are missing or unknown are ignored.
+pub struct ArmorReader<R: BufRead> {
+    reader: R,
+    fields: Vec<(String, String)>,
+    // Base64 read but not yet decoded, less than a group of four characters.
+    text: String,
+    decoded: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: BufRead> ArmorReader<R> {
+    /// Reads the armor up to the start of the body.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || !line.trim().is_empty() {
+                break;
+            }
+        }
+        if line.trim() != BEGIN {
+            return Err(SplitSecretError::InvalidHeader(
+                "not an armored share".into(),
+            ));
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(SplitSecretError::InvalidHeader(
+                    "the armored share ends before its body".into(),
+                ));
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+            match line.split_once(':') {
+                Some((key, value)) => fields.push((key.trim().into(), value.trim().into())),
+                None => {
+                    return Err(SplitSecretError::InvalidHeader(format!(
+                        "malformed armor header line '{}'",
+                        line.trim()
+                    )))
+                }
+            }
+        }
+
+        let mut armor = Self {
+            reader,
+            fields,
+            text: String::new(),
+            decoded: Vec::new(),
+            position: 0,
+            finished: false,
+        };
+        while armor.decoded.len() < HEADER_SIZE && !armor.finished {
+            armor.fill()?;
+        }
+        armor.check_fields()?;
+        Ok(armor)
+    }
+
+    /// The header fields of the armor, in order.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    fn check_fields(&self) -> Result<()> {
+        // A body too short for a header is left for the reader of the share to reject.
+        let header = match ShareHeader::read(&mut &self.decoded[..]) {
+            Ok(header) => header,
+            Err(_) => return Ok(()),
+        };
+        for (key, expected) in fields(&header) {
+            let value = self
+                .fields
+                .iter()
+                .find(|(field, _)| field.eq_ignore_ascii_case(key));
+            if let Some((_, value)) = value {
+                if !value.eq_ignore_ascii_case(&expected) {
+                    return Err(SplitSecretError::InvalidHeader(format!(
+                        "the armor says {} is {}, but the share says {}",
+                        key, value, expected
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Decodes the next line of the body.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "the armored share has no END line",
+            ));
+        }
+        let line = line.trim();
+        if line == END {
+            if !self.text.is_empty() {
+                return Err(invalid_data("the armored share is not valid base64"));
+            }
+            self.finished = true;
+            return Ok(());
+        }
+        if line.starts_with("-----") {
+            return Err(invalid_data("unexpected line in the armored share"));
+        }
+        self.text
+            .extend(line.chars().filter(|c| !c.is_ascii_whitespace()));
+        let whole = self.text.len() - self.text.len() % 4;
+        let decoded = STANDARD
+            .decode(&self.text[..whole])
+            .map_err(|_| invalid_data("the armored share is not valid base64"))?;
+        self.decoded.extend(decoded);
+        self.text.drain(..whole);
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ArmorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.decoded.clear();
+            self.position = 0;
+            self.fill()?;
+        }
+        let read_size = buf.len().min(self.decoded.len() - self.position);
+        buf[..read_size].copy_from_slice(&self.decoded[self.position..self.position + read_size]);
+        self.position += read_size;
+        Ok(read_size)
+    }
+}
+
+/// A share file that may or may not be armored, read as the binary share it holds, so that
+/// armored and binary shares can be used interchangeably.
+pub enum ShareReader<R: BufRead> {
+    Binary(R),
+    Armored(ArmorReader<R>),
+}
+
+impl<R: BufRead> ShareReader<R> {
+    /// Tells an armored share from a binary one by how it starts.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let start = reader.fill_buf()?;
+        let armored = start
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .is_some_and(|i| start[i..].starts_with(b"-----BEGIN"));
+        Ok(match armored {
+            true => ShareReader::Armored(ArmorReader::new(reader)?),
+            false => ShareReader::Binary(reader),
+        })
+    }
+}
+
+impl<R: BufRead> Read for ShareReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ShareReader::Binary(reader) => reader.read(buf),
+            ShareReader::Armored(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Only binary shares can be seeked, as [`crate::resume_join_shares`] needs.
+impl<R: BufRead + Seek> Seek for ShareReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            ShareReader::Binary(reader) => reader.seek(pos),
+            ShareReader::Armored(_) => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "armored shares cannot be seeked",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{Cipher, Padding, Scheme};
+    use crate::partitioner::OutputPartition;
+    use crate::shares::{join_shares, split_shares};
+
+    fn shares(n: u16) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let plaintext: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); n.into()];
+        let mut outputs: Vec<_> = shares
+            .iter_mut()
+            .zip(1u16..)
+            .map(|(share, x)| OutputPartition { x, writer: share })
+            .collect();
+        split_shares(&header, &plaintext[..], &mut outputs).unwrap();
+        (plaintext, shares)
+    }
+
+    fn armor(share: &[u8]) -> String {
+        let mut writer = ArmorWriter::new(Vec::new());
+        for piece in share.chunks(100) {
+            writer.write_all(piece).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    fn dearmor(text: &str) -> Result<Vec<u8>> {
+        let mut share = Vec::new();
+        ShareReader::new(text.as_bytes())?.read_to_end(&mut share)?;
+        Ok(share)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_, shares) = shares(2);
+        let text = armor(&shares[1]);
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(BEGIN));
+        assert_eq!(lines.next(), Some("Scheme: shamir-ida"));
+        assert_eq!(lines.next(), Some("Index: 2"));
+        assert_eq!(lines.next(), Some("Threshold: 2"));
+        assert!(text.lines().all(|line| line.len() <= 64));
+        assert_eq!(text.lines().last(), Some(END));
+        assert_eq!(dearmor(&text).unwrap(), shares[1]);
+
+        // Rewrapped, indented and with Windows line endings.
+        let body: String = text.lines().skip(6).collect::<Vec<_>>().join("");
+        let body = body.trim_end_matches(END);
+        let rewrapped: Vec<String> = body
+            .as_bytes()
+            .chunks(70)
+            .map(|line| format!("  {}\r\n", std::str::from_utf8(line).unwrap()))
+            .collect();
+        let header: String = text.lines().take(6).map(|l| format!("{}\r\n", l)).collect();
+        let text = format!("\n{}{}{}\n", header, rewrapped.concat(), END);
+        assert_eq!(dearmor(&text).unwrap(), shares[1]);
+    }
+
+    #[test]
+    fn join_mixed() {
+        let (plaintext, shares) = shares(2);
+        let armored = armor(&shares[0]);
+        let mut inputs = vec![
+            ShareReader::new(armored.as_bytes()).unwrap(),
+            ShareReader::new(&shares[1][..]).unwrap(),
+        ];
+        let mut output = Vec::new();
+        join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(output, plaintext);
+    }
+
+    #[test]
+    fn mismatched_fields() {
+        let (_, shares) = shares(2);
+        let text = armor(&shares[0]).replace("Index: 1", "Index: 4");
+        assert!(matches!(
+            dearmor(&text),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+
+        let text = armor(&shares[0]).replace("Threshold: 2\n", "");
+        assert!(dearmor(&text).is_ok());
+    }
+
+    #[test]
+    fn damaged() {
+        let (_, shares) = shares(2);
+        let text = armor(&shares[0]);
+        let truncated = &text[..text.len() - END.len() - 1];
+        assert!(dearmor(truncated).is_err());
+
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines[8] = "not base64!";
+        assert!(dearmor(&lines.join("\n")).is_err());
+    }
+}
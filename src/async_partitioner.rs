@@ -0,0 +1,348 @@
+use std::future::Future;
+use std::marker::PhantomData;
+
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{InputPartition, JoinReport, OutputPartition};
+use crate::pipeline::chunk_size;
+
+use block_padding::RawPadding;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// A split that is given its input a piece at a time, as it arrives.
+pub trait SplitEncoder {
+    /// Encodes the next piece of the input, appending to the shares in `outputs`.
+    fn update(&mut self, input: &[u8], outputs: &mut [Vec<u8>]) -> Result<()>;
+
+    /// Encodes the end of the input.
+    fn finish(&mut self, outputs: &mut [Vec<u8>]) -> Result<()>;
+}
+
+/// A join that is given its inputs a piece at a time, as they arrive.
+pub trait JoinDecoder {
+    /// Decodes the next piece of every input, which must all be the same length, appending to
+    /// `output`.
+    fn update(&mut self, inputs: &[&[u8]], output: &mut Vec<u8>) -> Result<()>;
+
+    /// Decodes the end of the inputs, and reports which of them had errors corrected.
+    fn finish(&mut self, output: &mut Vec<u8>) -> Result<JoinReport>;
+}
+
+/// A partitioner that splits into and joins from tokio's async streams, such as sockets, without
+/// blocking a thread on them.
+///
+/// The shares are the same as those of the scheme's [`Partitioner`](crate::Partitioner), so
+/// either can join what the other split. The encoding runs on the task that awaits it, a chunk at
+/// a time.
+pub trait AsyncPartitioner: Sync {
+    /// An encoder for a split into shares with indices `xs`, in that order.
+    fn split_encoder(&self, xs: &[u16]) -> Result<Box<dyn SplitEncoder + Send + '_>>;
+
+    /// A decoder for a join of shares with indices `xs`, in that order.
+    fn join_decoder(&self, xs: &[u16]) -> Result<Box<dyn JoinDecoder + Send + '_>>;
+
+    /// Like [`Partitioner::split`](crate::Partitioner::split). The outputs are flushed but not
+    /// shut down.
+    fn split_async<R, W>(
+        &self,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> impl Future<Output = Result<()>> + Send
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        async move {
+            let xs: Vec<u16> = outputs.iter().map(|output| output.x).collect();
+            let mut encoder = self.split_encoder(&xs)?;
+            let mut buf = vec![0u8; chunk_size(outputs.len(), 1)];
+            let mut shares = vec![Vec::new(); outputs.len()];
+            loop {
+                let read_size = input.read(&mut buf).await?;
+                if read_size == 0 {
+                    break;
+                }
+                encoder.update(&buf[..read_size], &mut shares)?;
+                write_shares(outputs, &mut shares).await?;
+            }
+            encoder.finish(&mut shares)?;
+            write_shares(outputs, &mut shares).await?;
+            for output in outputs.iter_mut() {
+                output.writer.flush().await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Like [`Partitioner::join`](crate::Partitioner::join). The output is flushed but not shut
+    /// down.
+    fn join_async<R, W>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> impl Future<Output = Result<JoinReport>> + Send
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        async move {
+            let xs: Vec<u16> = inputs.iter().map(|input| input.x).collect();
+            let mut decoder = self.join_decoder(&xs)?;
+            let mut bufs = vec![vec![0u8; chunk_size(inputs.len(), 1)]; inputs.len()];
+            let mut decoded = Vec::new();
+            loop {
+                let read_size = read_shares(inputs, &mut bufs).await?;
+                if read_size == 0 {
+                    break;
+                }
+                let pieces: Vec<&[u8]> = bufs.iter().map(|buf| &buf[..read_size]).collect();
+                decoder.update(&pieces, &mut decoded)?;
+                output.write_all(&decoded).await?;
+                decoded.clear();
+            }
+            let report = decoder.finish(&mut decoded)?;
+            output.write_all(&decoded).await?;
+            output.flush().await?;
+            Ok(report)
+        }
+    }
+}
+
+// Writes out the shares encoded so far, leaving them empty.
+async fn write_shares<W: AsyncWrite + Unpin>(
+    outputs: &mut [OutputPartition<W>],
+    shares: &mut [Vec<u8>],
+) -> Result<()> {
+    for (share, output) in shares.iter_mut().zip(outputs.iter_mut()) {
+        output.writer.write_all(share).await?;
+        share.clear();
+    }
+    Ok(())
+}
+
+// Like partitioner::read_shares: fills every buffer unless its input ends first, and fails if
+// the inputs end at different places.
+async fn read_shares<R: AsyncRead + Unpin>(
+    inputs: &mut [InputPartition<R>],
+    bufs: &mut [Vec<u8>],
+) -> Result<usize> {
+    let mut read_sizes = Vec::with_capacity(inputs.len());
+    for (input, buf) in inputs.iter_mut().zip(bufs.iter_mut()) {
+        let mut read_size = 0;
+        while read_size < buf.len() {
+            match input.reader.read(&mut buf[read_size..]).await? {
+                0 => break,
+                n => read_size += n,
+            }
+        }
+        read_sizes.push(read_size);
+    }
+    let read_size = read_sizes.iter().copied().max().unwrap_or(0);
+    match inputs
+        .iter()
+        .zip(read_sizes.iter())
+        .find(|(_, size)| **size != read_size)
+    {
+        Some((input, _)) => Err(SplitSecretError::TruncatedShare { x: input.x }),
+        None => Ok(read_size),
+    }
+}
+
+// Cuts a stream given a piece at a time into whole blocks, and pads the end with P the way
+// PaddedReader does if pad is set.
+pub(crate) struct Blocks<P: RawPadding> {
+    block_size: usize,
+    pad: bool,
+    pending: Vec<u8>,
+    empty: bool,
+    _p: PhantomData<P>,
+}
+
+impl<P: RawPadding> Blocks<P> {
+    pub(crate) fn new(block_size: usize, pad: bool) -> Self {
+        Blocks {
+            block_size,
+            pad,
+            pending: Vec::new(),
+            empty: true,
+            _p: PhantomData,
+        }
+    }
+
+    // The whole blocks available after adding input.
+    pub(crate) fn update(&mut self, input: &[u8]) -> Vec<u8> {
+        self.empty &= input.is_empty();
+        self.pending.extend_from_slice(input);
+        let whole = self.pending.len() - self.pending.len() % self.block_size;
+        self.pending.drain(..whole).collect()
+    }
+
+    // The rest of the stream: a final padded block, or without padding, whatever is left over.
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        let mut last = std::mem::take(&mut self.pending);
+        if self.pad && !self.empty {
+            let last_block_size = last.len();
+            last.resize(self.block_size, 0u8);
+            P::raw_pad(&mut last, last_block_size);
+        }
+        last
+    }
+}
+
+// Collects pieces of the inputs of a join until every input has a whole number of units, and
+// returns that many bytes of each.
+pub(crate) struct Units {
+    unit: usize,
+    pending: Vec<Vec<u8>>,
+}
+
+impl Units {
+    pub(crate) fn new(unit: usize, inputs: usize) -> Self {
+        Units {
+            unit,
+            pending: vec![Vec::new(); inputs],
+        }
+    }
+
+    pub(crate) fn update(&mut self, inputs: &[&[u8]]) -> Vec<Vec<u8>> {
+        for (pending, input) in self.pending.iter_mut().zip(inputs.iter()) {
+            pending.extend_from_slice(input);
+        }
+        let len = self.pending[0].len();
+        let whole = len - len % self.unit;
+        self.pending
+            .iter_mut()
+            .map(|pending| pending.drain(..whole).collect())
+            .collect()
+    }
+
+    // Whether the inputs ended partway through a unit.
+    pub(crate) fn truncated(&self) -> bool {
+        !self.pending[0].is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Gf65536;
+    use crate::ida::Ida;
+    use crate::partitioner::{InMemoryPartition, Partitioner};
+    use crate::shamir::Shamir;
+    use crate::shamir_ida::ShamirIda;
+
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use aes::Aes256;
+    use block_padding::{Iso7816, Pkcs7};
+    use tokio::io::ReadBuf;
+
+    // A reader that gives out a few bytes at a time, as a socket might.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let len = self.0.len().min(buf.remaining()).min(7);
+            buf.put_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn split(partitioner: &impl AsyncPartitioner, input: &[u8], n: u16) -> Vec<Vec<u8>> {
+        let mut shares = vec![Vec::new(); usize::from(n)];
+        let mut outputs: Vec<_> = (1..=n)
+            .zip(shares.iter_mut())
+            .map(|(x, share)| OutputPartition { x, writer: share })
+            .collect();
+        block_on(partitioner.split_async(Trickle(input), &mut outputs)).unwrap();
+        shares
+    }
+
+    fn join(
+        partitioner: &impl AsyncPartitioner,
+        shares: &[(u16, &Vec<u8>)],
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        let mut inputs: Vec<_> = shares
+            .iter()
+            .map(|(x, share)| InputPartition {
+                x: *x,
+                reader: Trickle(share),
+            })
+            .collect();
+        let mut output = Vec::new();
+        let report = block_on(partitioner.join_async(&mut inputs, &mut output))?;
+        Ok((output, report))
+    }
+
+    fn all_of(shares: &[InMemoryPartition]) -> Vec<(u16, &Vec<u8>)> {
+        shares.iter().map(|share| (share.x, &share.value)).collect()
+    }
+
+    // Splits and joins between the blocking and async methods in both directions.
+    fn round_trip(partitioner: &(impl AsyncPartitioner + Partitioner)) {
+        let plaintext: Vec<u8> = (0..1000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+
+        let shares = split(partitioner, &plaintext, 5);
+        let mut inputs: Vec<_> = [4u16, 1, 5]
+            .iter()
+            .map(|x| InputPartition {
+                x: *x,
+                reader: &shares[usize::from(*x) - 1][..],
+            })
+            .collect();
+        let mut output = Vec::new();
+        partitioner.join(&mut inputs, &mut output).unwrap();
+        assert_eq!(output, plaintext);
+
+        let mut shares = partitioner.split_in_memory(&plaintext, 5).unwrap();
+        // Past any key share, which Feldman refuses outright.
+        let last = shares[1].value.len() - 1;
+        shares[1].value[last] ^= 1;
+        let (output, report) = join(partitioner, &all_of(&shares)).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [2]);
+
+        shares[2].value.pop();
+        assert!(matches!(
+            join(partitioner, &all_of(&shares)),
+            Err(SplitSecretError::TruncatedShare { x: 3 })
+        ));
+    }
+
+    #[test]
+    fn shamir() {
+        round_trip(&Shamir::new(3).unwrap());
+        round_trip(&Shamir::<Gf65536>::with_field(3).unwrap());
+    }
+
+    #[test]
+    fn ida() {
+        round_trip(&Ida::<Iso7816>::new(3).unwrap());
+    }
+
+    #[test]
+    fn shamir_ida() {
+        round_trip(
+            &ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Pkcs7>::new(3).unwrap(),
+        );
+        round_trip(
+            &ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new_verifiable(
+                3,
+            )
+            .unwrap(),
+        );
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{Error, ErrorKind, Read, Result, Write};
 use std::marker::PhantomData;
 use std::ops::Range;
 
+use crate::error::SplitSecretError;
 use crate::padding_streaming::{Op, PaddedReader, PaddedWriter};
 use crate::utils::read_full;
 
@@ -13,6 +14,7 @@ use core::slice;
 
 const BUF_SIZE: usize = 1024;
 
+/// Pads and encrypts everything read from the wrapped reader.
 pub struct EncryptReadStream<C, P, R>
 where
     C: BlockEncryptMut,
@@ -26,7 +28,7 @@ where
     _p: PhantomData<P>,
 }
 
-fn to_blocks<N>(data: &mut [u8]) -> &mut [GenericArray<u8, N>]
+pub(crate) fn to_blocks<N>(data: &mut [u8]) -> &mut [GenericArray<u8, N>]
 where
     N: ArrayLength<u8>,
 {
@@ -108,6 +110,9 @@ where
     }
 }
 
+/// Decrypts and unpads everything written to it before passing it to the wrapped writer.
+///
+/// The final block is only written once the stream is flushed.
 pub struct DecryptWriteStream<C, P, W>
 where
     C: BlockDecryptMut,
@@ -139,6 +144,12 @@ where
             _p: PhantomData,
         }
     }
+
+    /// Returns the wrapped writer, which has been given the plaintext of every block written so
+    /// far but the last.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
 }
 
 impl<C, P, W> Write for DecryptWriteStream<C, P, W>
@@ -174,10 +185,7 @@ where
 
     fn flush(&mut self) -> Result<()> {
         if self.buf_bytes != 0 {
-            Err(Error::new(
-                ErrorKind::InvalidData,
-                "the number of bytes written was not a multiple of block size",
-            ))
+            Err(SplitSecretError::Padding.into())
         } else {
             self.writer.flush()
         }
@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Cursor, Read, Write};
+use std::rc::Rc;
+
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{InputPartition, OutputPartition};
+use crate::utils::read_full;
+
+// A share file of weight w bundles w shares, called lanes here, with consecutive indices. Lanes
+// are interleaved bytewise: byte i of lane l is at position i * w + l of the body. Since all
+// shares of a split have the same length and partitioners write and read them in lockstep, only a
+// little of each lane is ever buffered.
+
+const CHUNK_SIZE: usize = 4096;
+
+// Checks that a bundle of `weight` shares starting at index `x` fits in the index space.
+fn check_bundle(x: u16, weight: u8) -> Result<()> {
+    if weight == 0 || usize::from(x) + usize::from(weight) > 1 << 16 {
+        return Err(SplitSecretError::InvalidIndex(x));
+    }
+    Ok(())
+}
+
+pub(crate) struct Interleaver<W: Write> {
+    writer: W,
+    x: u16,
+    lanes: Vec<VecDeque<u8>>,
+}
+
+impl<W: Write> Interleaver<W> {
+    // Writes out as many whole rows of one byte per lane as are buffered.
+    fn write_rows(&mut self) -> Result<()> {
+        let rows = self.lanes.iter().map(|lane| lane.len()).min().unwrap_or(0);
+        if rows == 0 {
+            return Ok(());
+        }
+        let mut buf = Vec::with_capacity(rows * self.lanes.len());
+        for _ in 0..rows {
+            for lane in self.lanes.iter_mut() {
+                buf.push(lane.pop_front().unwrap());
+            }
+        }
+        self.writer.write_all(&buf)?;
+        Ok(())
+    }
+}
+
+/// One share of a bundle being written.
+pub(crate) struct LaneWriter<W: Write> {
+    interleaver: Rc<RefCell<Interleaver<W>>>,
+    lane: usize,
+}
+
+impl<W: Write> Write for LaneWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut interleaver = self.interleaver.borrow_mut();
+        interleaver.lanes[self.lane].extend(buf);
+        interleaver.write_rows()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// The shares of several share files being written, for handing to a partitioner.
+pub(crate) struct OutputLanes<W: Write> {
+    interleavers: Vec<Rc<RefCell<Interleaver<W>>>>,
+    pub(crate) outputs: Vec<OutputPartition<LaneWriter<W>>>,
+}
+
+impl<W: Write> OutputLanes<W> {
+    pub(crate) fn new() -> Self {
+        OutputLanes {
+            interleavers: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    // Adds a share file holding the shares with indices x..x + weight.
+    pub(crate) fn add(&mut self, x: u16, weight: u8, writer: W) -> Result<()> {
+        check_bundle(x, weight)?;
+        let interleaver = Rc::new(RefCell::new(Interleaver {
+            writer,
+            x,
+            lanes: vec![VecDeque::new(); weight.into()],
+        }));
+        for lane in 0..weight {
+            self.outputs.push(OutputPartition {
+                x: x + u16::from(lane),
+                writer: LaneWriter {
+                    interleaver: Rc::clone(&interleaver),
+                    lane: lane.into(),
+                },
+            });
+        }
+        self.interleavers.push(interleaver);
+        Ok(())
+    }
+
+    // Checks that every lane was written to the same length.
+    pub(crate) fn finish(self) -> Result<()> {
+        for interleaver in self.interleavers.iter() {
+            let interleaver = interleaver.borrow();
+            if interleaver.lanes.iter().any(|lane| !lane.is_empty()) {
+                return Err(SplitSecretError::TruncatedShare { x: interleaver.x });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Deinterleaver<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+    lanes: Vec<VecDeque<u8>>,
+}
+
+impl<R: Read> Deinterleaver<R> {
+    // Reads the next chunk of rows and distributes it among the lanes. A body that ends partway
+    // through a row leaves some lanes a byte longer than the others, which the partitioner reports
+    // as a truncated share.
+    fn fill(&mut self) -> Result<()> {
+        let read_size = read_full(&mut self.reader, &mut self.buf)?;
+        let weight = self.lanes.len();
+        for (i, byte) in self.buf[..read_size].iter().enumerate() {
+            self.lanes[i % weight].push_back(*byte);
+        }
+        Ok(())
+    }
+}
+
+/// One share of a bundle being read.
+pub(crate) struct LaneReader<R: Read> {
+    deinterleaver: Rc<RefCell<Deinterleaver<R>>>,
+    lane: usize,
+}
+
+impl<R: Read> Read for LaneReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut deinterleaver = self.deinterleaver.borrow_mut();
+        if deinterleaver.lanes[self.lane].is_empty() {
+            deinterleaver.fill()?;
+        }
+        let lane = &mut deinterleaver.lanes[self.lane];
+        let read_size = lane.len().min(buf.len());
+        for (b, byte) in buf.iter_mut().zip(lane.drain(..read_size)) {
+            *b = byte;
+        }
+        Ok(read_size)
+    }
+}
+
+// Splits a share file holding the shares with indices x..x + weight into one input per share.
+pub(crate) fn input_lanes<R: Read>(
+    x: u16,
+    weight: u8,
+    reader: R,
+) -> Result<Vec<InputPartition<LaneReader<R>>>> {
+    check_bundle(x, weight)?;
+    let deinterleaver = Rc::new(RefCell::new(Deinterleaver {
+        reader,
+        buf: vec![0u8; CHUNK_SIZE * usize::from(weight)],
+        lanes: vec![VecDeque::new(); weight.into()],
+    }));
+    Ok((0..weight)
+        .map(|lane| InputPartition {
+            x: x + u16::from(lane),
+            reader: LaneReader {
+                deinterleaver: Rc::clone(&deinterleaver),
+                lane: lane.into(),
+            },
+        })
+        .collect())
+}
+
+// Like input_lanes, for a share file whose body is in memory, giving inputs that can seek. A body
+// that ends partway through a row leaves the first lanes a byte longer than the others.
+pub(crate) fn seekable_lanes(
+    x: u16,
+    weight: u8,
+    body: &[u8],
+) -> Result<Vec<InputPartition<Cursor<Vec<u8>>>>> {
+    check_bundle(x, weight)?;
+    let weight = usize::from(weight);
+    Ok((0..weight)
+        .map(|lane| InputPartition {
+            x: x + lane as u16,
+            reader: Cursor::new(body.iter().skip(lane).step_by(weight).copied().collect()),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut bundle = Vec::new();
+        let mut lanes = OutputLanes::new();
+        lanes.add(4, 3, &mut bundle).unwrap();
+        for step in 0..3000u32 {
+            for output in lanes.outputs.iter_mut() {
+                let byte = (step as u8).wrapping_add(output.x as u8);
+                output.writer.write_all(&[byte]).unwrap();
+            }
+        }
+        lanes.finish().unwrap();
+        assert_eq!(bundle[..6], [4, 5, 6, 5, 6, 7]);
+
+        let mut inputs = input_lanes(4, 3, &bundle[..]).unwrap();
+        let mut bufs = vec![[0u8; 1000]; 3];
+        for step in 0..3 {
+            for (input, buf) in inputs.iter_mut().zip(bufs.iter_mut()) {
+                input.reader.read_exact(buf).unwrap();
+                assert_eq!(buf[0], ((step * 1000) as u8).wrapping_add(input.x as u8));
+            }
+        }
+        assert_eq!(inputs[0].reader.read(&mut bufs[0]).unwrap(), 0);
+
+        for input in seekable_lanes(4, 3, &bundle).unwrap() {
+            let lane = input.reader.into_inner();
+            assert_eq!(lane.len(), 3000);
+            assert_eq!(lane[1000], (1000u32 as u8).wrapping_add(input.x as u8));
+        }
+    }
+
+    #[test]
+    fn invalid_bundle() {
+        assert!(matches!(
+            OutputLanes::new().add(65530, 7, Vec::new()),
+            Err(SplitSecretError::InvalidIndex(65530))
+        ));
+    }
+}
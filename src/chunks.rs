@@ -0,0 +1,303 @@
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Result, SplitSecretError};
+use crate::header::ShareHeader;
+
+use sha2::{Digest, Sha256};
+
+/// Number of bytes of the original whose shares each chunk of a share file holds. Every chunk is
+/// split on its own, so a split or join can resume at any chunk boundary.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+// Every chunk is framed by its index, a byte saying whether it is the last one, and the length of
+// its shares, and followed by a digest of all that and the share header.
+const FRAME_HEADER_SIZE: usize = 13;
+const DIGEST_SIZE: usize = 32;
+
+/// One chunk of a share file, holding the shares of bytes `index * CHUNK_SIZE..` of the original.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub index: u64,
+    /// Whether this is the last chunk of the share file.
+    pub last: bool,
+    /// The shares of this chunk, interleaved as in the rest of the file if it holds several.
+    pub shares: Vec<u8>,
+}
+
+impl Chunk {
+    /// Size in bytes of the chunk in a share file, framing included.
+    pub fn frame_size(&self) -> u64 {
+        (FRAME_HEADER_SIZE + self.shares.len() + DIGEST_SIZE) as u64
+    }
+}
+
+fn frame_header(index: u64, last: bool, len: u32) -> [u8; FRAME_HEADER_SIZE] {
+    let mut buf = [0u8; FRAME_HEADER_SIZE];
+    buf[0..8].copy_from_slice(&index.to_be_bytes());
+    buf[8] = last as u8;
+    buf[9..13].copy_from_slice(&len.to_be_bytes());
+    buf
+}
+
+// The digest covers the share header too, so that a chunk copied into another share by mistake
+// is caught. Anyone can recompute it, so it only catches accidents; of the schemes, only the AEAD
+// hybrid detects deliberate changes.
+fn digest(header: &[u8], frame_header: &[u8], shares: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(header);
+    hasher.update(frame_header);
+    hasher.update(shares);
+    hasher.finalize().into()
+}
+
+/// Writes the chunks of a share file, after its header.
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+    header: Vec<u8>,
+    index: u64,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// `header` is the header of the share file, which the first chunk written follows unless
+    /// `index` is past 0 to continue a file with that many chunks.
+    pub fn new(writer: W, header: &ShareHeader, index: u64) -> Self {
+        Self::with_header(writer, &header.to_bytes(), index)
+    }
+
+    /// Like [`ChunkWriter::new`], for files with a header other than a [`ShareHeader`], such as
+    /// policy shares. `header` is everything in the file before the first chunk.
+    pub fn with_header(writer: W, header: &[u8], index: u64) -> Self {
+        Self {
+            writer,
+            header: header.to_vec(),
+            index,
+        }
+    }
+
+    /// Writes the next chunk, holding `shares`; `last` ends the share file.
+    pub fn write_chunk(&mut self, shares: &[u8], last: bool) -> Result<()> {
+        let len = u32::try_from(shares.len()).map_err(|_| SplitSecretError::SecretTooLarge {
+            max: u32::MAX as usize,
+        })?;
+        let frame_header = frame_header(self.index, last, len);
+        self.writer.write_all(&frame_header)?;
+        self.writer.write_all(shares)?;
+        self.writer
+            .write_all(&digest(&self.header, &frame_header, shares))?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+/// Reads and verifies the chunks written by [`ChunkWriter`].
+///
+/// A chunk is only returned once its digest has been verified, so a damaged share is detected
+/// before any of its damaged data is used, and a share cut short or with chunks out of order is
+/// detected too. The digests are not keyed, so they do not stop someone who means to change a
+/// share; see [`crate::ShamirIdaAead`] for that.
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    header: Vec<u8>,
+    index: u64,
+    // Reported in errors.
+    input: usize,
+    x: Option<u16>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// `header` is the header of the share file, which `reader` is positioned after. `input`
+    /// identifies the share in errors, e.g. its position among the inputs of a join.
+    pub fn new(reader: R, header: &ShareHeader, input: usize) -> Self {
+        Self::with_header(reader, &header.to_bytes(), Some(header.x), input)
+    }
+
+    /// Like [`ChunkReader::new`], for the chunks written by [`ChunkWriter::with_header`]. `x` is
+    /// the share index reported in errors, if the file has one.
+    pub fn with_header(reader: R, header: &[u8], x: Option<u16>, input: usize) -> Self {
+        Self {
+            reader,
+            header: header.to_vec(),
+            index: 0,
+            input,
+            x,
+        }
+    }
+
+    fn corrupt(&self) -> SplitSecretError {
+        SplitSecretError::CorruptShare {
+            input: self.input,
+            x: self.x,
+        }
+    }
+
+    // A share that ends early, or that cannot be decoded such as an armored one with bad base64,
+    // is corrupt.
+    fn check<T>(&self, result: std::io::Result<T>) -> Result<T> {
+        match result {
+            Err(e) if matches!(e.kind(), ErrorKind::UnexpectedEof | ErrorKind::InvalidData) => {
+                Err(self.corrupt())
+            }
+            result => Ok(result?),
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let result = self.reader.read_exact(buf);
+        self.check(result)
+    }
+
+    fn read_frame_header(&mut self) -> Result<([u8; FRAME_HEADER_SIZE], bool, u32)> {
+        let mut buf = [0u8; FRAME_HEADER_SIZE];
+        self.read_exact(&mut buf)?;
+        if buf[0..8] != self.index.to_be_bytes() || buf[8] > 1 {
+            return Err(self.corrupt());
+        }
+        let len = u32::from_be_bytes([buf[9], buf[10], buf[11], buf[12]]);
+        Ok((buf, buf[8] == 1, len))
+    }
+
+    /// Reads and verifies the next chunk. Reading past the last chunk is an error.
+    pub fn next_chunk(&mut self) -> Result<Chunk> {
+        let (frame_header, last, len) = self.read_frame_header()?;
+        // Read no more than the share holds, so that a damaged length cannot exhaust memory.
+        let mut shares = Vec::new();
+        let result = (&mut self.reader).take(len.into()).read_to_end(&mut shares);
+        self.check(result)?;
+        if shares.len() != len as usize {
+            return Err(self.corrupt());
+        }
+        let mut expected = [0u8; DIGEST_SIZE];
+        self.read_exact(&mut expected)?;
+        if digest(&self.header, &frame_header, &shares) != expected {
+            return Err(self.corrupt());
+        }
+        let chunk = Chunk {
+            index: self.index,
+            last,
+            shares,
+        };
+        self.index += 1;
+        Ok(chunk)
+    }
+}
+
+impl<R: Read + Seek> ChunkReader<R> {
+    /// Reads and verifies the next chunk like [`ChunkReader::next_chunk`], then goes back to
+    /// where it started, so that the chunk is read again next.
+    pub fn peek_chunk(&mut self) -> Result<Chunk> {
+        let chunk = self.next_chunk()?;
+        self.reader
+            .seek(SeekFrom::Current(-(chunk.frame_size() as i64)))?;
+        self.index -= 1;
+        Ok(chunk)
+    }
+
+    /// Skips over the next chunk without verifying it, unless it is the last one, which is left
+    /// to be read. Returns whether a chunk was skipped.
+    pub fn skip_chunk(&mut self) -> Result<bool> {
+        let (_, last, len) = self.read_frame_header()?;
+        if last {
+            self.reader
+                .seek(SeekFrom::Current(-(FRAME_HEADER_SIZE as i64)))?;
+            return Ok(false);
+        }
+        self.reader
+            .seek(SeekFrom::Current(i64::from(len) + DIGEST_SIZE as i64))?;
+        self.index += 1;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{Cipher, Padding, Scheme};
+
+    use std::io::Cursor;
+
+    fn header() -> ShareHeader {
+        ShareHeader {
+            set_id: [9u8; 16],
+            ..ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 7)
+        }
+    }
+
+    fn write(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut file = Vec::new();
+        let mut writer = ChunkWriter::new(&mut file, &header(), 0);
+        for (i, shares) in chunks.iter().enumerate() {
+            writer.write_chunk(shares, i + 1 == chunks.len()).unwrap();
+        }
+        file
+    }
+
+    fn read(file: &[u8]) -> Result<Vec<Chunk>> {
+        let mut reader = ChunkReader::new(file, &header(), 3);
+        let mut chunks = vec![reader.next_chunk()?];
+        while !chunks.last().unwrap().last {
+            chunks.push(reader.next_chunk()?);
+        }
+        Ok(chunks)
+    }
+
+    fn is_corrupt(result: Result<Vec<Chunk>>) -> bool {
+        matches!(
+            result,
+            Err(SplitSecretError::CorruptShare {
+                input: 3,
+                x: Some(7)
+            })
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = write(&[b"first", b"", b"third"]);
+        let chunks = read(&file).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].index, 2);
+        assert_eq!(chunks[2].shares, b"third");
+        assert_eq!(
+            chunks.iter().map(Chunk::frame_size).sum::<u64>(),
+            file.len() as u64
+        );
+    }
+
+    #[test]
+    fn modified() {
+        let file = write(&[b"first", b"second"]);
+        for i in [3, 8, 15, 60, file.len() - 1] {
+            let mut modified = file.clone();
+            modified[i] ^= 1;
+            assert!(is_corrupt(read(&modified)));
+        }
+
+        // A chunk cannot be taken from another share.
+        let mut other = Vec::new();
+        ChunkWriter::new(&mut other, &header().new_split(), 0)
+            .write_chunk(b"first", true)
+            .unwrap();
+        assert!(is_corrupt(read(&other)));
+    }
+
+    #[test]
+    fn truncated() {
+        let file = write(&[b"first", b"second"]);
+        for len in [0, 10, 50, file.len() - 1] {
+            assert!(is_corrupt(read(&file[..len])));
+        }
+    }
+
+    #[test]
+    fn skip() {
+        let file = write(&[b"first", b"second", b"third"]);
+        let mut reader = ChunkReader::new(Cursor::new(&file), &header(), 0);
+        assert!(reader.skip_chunk().unwrap());
+        assert!(reader.skip_chunk().unwrap());
+        assert!(!reader.skip_chunk().unwrap());
+        let chunk = reader.next_chunk().unwrap();
+        assert_eq!((chunk.index, chunk.last), (2, true));
+        assert_eq!(chunk.shares, b"third");
+    }
+}
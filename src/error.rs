@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors returned while splitting or joining shares.
+#[derive(Debug)]
+pub enum SplitSecretError {
+    /// Reading an input or writing an output failed.
+    Io(io::Error),
+    /// The threshold is below the minimum of 2.
+    InvalidThreshold(u8),
+    /// The number of shares given does not work with the threshold `k`.
+    ShareCount { k: u8, n: usize },
+    /// A share has index 0, which is reserved for the secret itself.
+    InvalidIndex(u16),
+    /// Two shares have the same index.
+    DuplicateIndex(u16),
+    /// A refresh was given a different number of outputs than shares to refresh.
+    OutputCount { inputs: usize, outputs: usize },
+    /// A share header is malformed or describes something this version does not support.
+    InvalidHeader(String),
+    /// The header of share `x` does not agree with the headers of the other shares.
+    MismatchedHeader { x: u16 },
+    /// The padding at the end of the reconstructed data is malformed.
+    Padding,
+    /// Share `x` ended before the other shares did.
+    TruncatedShare { x: u16 },
+    /// A share failed its integrity check. `input` is its position among the inputs of the join,
+    /// and `x` its index, if the header could be read.
+    CorruptShare { input: usize, x: Option<u16> },
+    /// Share `x` does not match the commitments it was dealt with.
+    InvalidShare { x: u16 },
+    /// Share `x` carries different commitments than the other shares.
+    CommitmentMismatch { x: u16 },
+    /// `failed` of the `total` shares checked failed verification, each for a reason reported on
+    /// its own.
+    VerificationFailed { failed: usize, total: usize },
+    /// The shares disagree in more places than the surplus shares can correct.
+    InconsistentShares,
+    /// The secret is longer than the scheme can share.
+    SecretTooLarge { max: usize },
+    /// The secret cannot be encoded in the field the scheme works over.
+    InvalidSecret(String),
+    /// A parameter of a scheme is outside the range it allows.
+    InvalidParameter(String),
+    /// The scheme does not support the named operation.
+    Unsupported(&'static str),
+    /// A thread that a split or join spread its work over stopped without finishing it.
+    ThreadFailed,
+    /// Authenticated decryption failed because the ciphertext was modified, truncated or
+    /// reordered.
+    Authentication,
+    /// An access policy is malformed or cannot be satisfied.
+    InvalidPolicy(String),
+    /// The shares given do not satisfy the access policy; `missing` describes every gate that
+    /// still needs branches, outermost first.
+    PolicyUnsatisfied { missing: Vec<String> },
+    /// Word `position` of a mnemonic share, counting from 1, is not in the word list;
+    /// `suggestions` are listed words it may have been meant as.
+    UnknownWord {
+        position: usize,
+        word: String,
+        suggestions: Vec<String>,
+    },
+    /// A mnemonic share has the wrong number of words, fails its checksum or holds a value out of
+    /// range.
+    InvalidMnemonic(String),
+}
+
+pub type Result<T> = std::result::Result<T, SplitSecretError>;
+
+impl fmt::Display for SplitSecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitSecretError::Io(e) => write!(f, "I/O error: {}", e),
+            SplitSecretError::InvalidThreshold(k) => {
+                write!(f, "threshold must be at least 2, got {}", k)
+            }
+            SplitSecretError::ShareCount { k, n } => {
+                write!(f, "a threshold of {} cannot be used with {} shares", k, n)
+            }
+            SplitSecretError::InvalidIndex(x) => write!(f, "share index {} is not allowed", x),
+            SplitSecretError::DuplicateIndex(x) => write!(f, "share index {} appears twice", x),
+            SplitSecretError::OutputCount { inputs, outputs } => {
+                write!(
+                    f,
+                    "{} shares cannot be refreshed into {} outputs",
+                    inputs, outputs
+                )
+            }
+            SplitSecretError::InvalidHeader(message) => {
+                write!(f, "invalid share header: {}", message)
+            }
+            SplitSecretError::MismatchedHeader { x } => {
+                write!(f, "header of share {} does not match the other shares", x)
+            }
+            SplitSecretError::Padding => write!(f, "reconstructed data is not correctly padded"),
+            SplitSecretError::TruncatedShare { x } => write!(f, "share {} is truncated", x),
+            SplitSecretError::CorruptShare { input, x: Some(x) } => write!(
+                f,
+                "share {} (input {}) is damaged and failed its integrity check",
+                x, input
+            ),
+            SplitSecretError::CorruptShare { input, x: None } => {
+                write!(
+                    f,
+                    "input {} is damaged and failed its integrity check",
+                    input
+                )
+            }
+            SplitSecretError::InvalidShare { x } => {
+                write!(f, "share {} does not match the dealer's commitments", x)
+            }
+            SplitSecretError::CommitmentMismatch { x } => write!(
+                f,
+                "share {} was dealt with different commitments than the other shares",
+                x
+            ),
+            SplitSecretError::VerificationFailed { failed, total } => {
+                write!(f, "{} of {} shares failed verification", failed, total)
+            }
+            SplitSecretError::InconsistentShares => write!(
+                f,
+                "shares disagree with each other and there are not enough to correct them"
+            ),
+            SplitSecretError::SecretTooLarge { max } => {
+                write!(f, "secret is longer than the maximum of {} bytes", max)
+            }
+            SplitSecretError::InvalidSecret(message) => write!(f, "invalid secret: {}", message),
+            SplitSecretError::InvalidParameter(message) => {
+                write!(f, "invalid parameter: {}", message)
+            }
+            SplitSecretError::Unsupported(operation) => {
+                write!(f, "this scheme does not support {}", operation)
+            }
+            SplitSecretError::ThreadFailed => {
+                write!(f, "a worker thread stopped before finishing its work")
+            }
+            SplitSecretError::Authentication => {
+                write!(f, "ciphertext was modified, truncated or reordered")
+            }
+            SplitSecretError::InvalidPolicy(message) => {
+                write!(f, "invalid access policy: {}", message)
+            }
+            SplitSecretError::PolicyUnsatisfied { missing } => {
+                write!(f, "access policy is not satisfied: {}", missing.join("; "))
+            }
+            SplitSecretError::UnknownWord {
+                position,
+                word,
+                suggestions,
+            } => {
+                write!(f, "word {}, '{}', is not in the word list", position, word)?;
+                if !suggestions.is_empty() {
+                    write!(f, "; did you mean '{}'?", suggestions.join("' or '"))?;
+                }
+                Ok(())
+            }
+            SplitSecretError::InvalidMnemonic(message) => {
+                write!(f, "invalid mnemonic share: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for SplitSecretError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SplitSecretError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// The streaming adapters can only return io::Error, so they wrap our errors inside one; unwrap
+// them again here so that callers see the original variant.
+impl From<io::Error> for SplitSecretError {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_none() {
+            return SplitSecretError::Io(e);
+        }
+        let kind = e.kind();
+        match e.into_inner().unwrap().downcast::<SplitSecretError>() {
+            Ok(inner) => *inner,
+            Err(inner) => SplitSecretError::Io(io::Error::new(kind, inner)),
+        }
+    }
+}
+
+impl From<SplitSecretError> for io::Error {
+    fn from(e: SplitSecretError) -> Self {
+        match e {
+            SplitSecretError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
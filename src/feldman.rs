@@ -0,0 +1,467 @@
+use std::io::{Read, Write};
+
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{
+    check_indices, check_new_index, check_outputs, InputPartition, JoinReport, OutputPartition,
+    Partitioner,
+};
+
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// The 2048-bit MODP group from RFC 3526. p is a safe prime, and 2 generates its subgroup of
+// prime order q = (p - 1) / 2.
+const MODP_2048: &str = concat!(
+    "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74",
+    "020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437",
+    "4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+    "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05",
+    "98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB",
+    "9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+    "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718",
+    "3995497CEA956AE515D2261898FA051015728E5A8AACAA68FFFFFFFFFFFFFFFF",
+);
+
+/// Size in bytes of a share and of each commitment.
+pub const ELEMENT_SIZE: usize = 256;
+/// Largest secret that fits in one element, leaving room for the length marker.
+pub const MAX_SECRET_SIZE: usize = ELEMENT_SIZE - 2;
+
+/// The dealer's commitments `g^a_j` to the coefficients of the sharing polynomial.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commitments(Vec<BigUint>);
+
+impl Commitments {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(to_element).collect()
+    }
+
+    /// A short digest of the commitments, for the dealer to publish and holders to compare.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    // The commitments of every chunk of a share file, which are dealt separately, as one.
+    pub(crate) fn concat(chunks: Vec<Commitments>) -> Self {
+        Commitments(chunks.into_iter().flat_map(|chunk| chunk.0).collect())
+    }
+}
+
+/// Feldman's verifiable secret sharing in the 2048-bit MODP group from RFC 3526.
+///
+/// Every share starts with the dealer's commitments to the sharing polynomial, followed by the
+/// holder's evaluation of that polynomial. Each holder can check their share against the
+/// commitments without learning anything about the secret beyond `g^secret`, so the secret must
+/// be high-entropy, such as a key. Secrets are limited to [`MAX_SECRET_SIZE`] bytes.
+///
+/// A join leaves out shares that fail verification and reports them, as long as `k` valid shares
+/// remain.
+pub struct Feldman {
+    k: u8,
+    p: BigUint,
+    q: BigUint,
+    g: BigUint,
+}
+
+// A share's index and value.
+type Share = (u16, BigUint);
+
+fn to_element(n: &BigUint) -> Vec<u8> {
+    let bytes = n.to_bytes_be();
+    let mut element = vec![0u8; ELEMENT_SIZE - bytes.len()];
+    element.extend_from_slice(&bytes);
+    element
+}
+
+impl Feldman {
+    /// Creates a scheme where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        if k < 2 {
+            return Err(SplitSecretError::InvalidThreshold(k));
+        }
+        let p = BigUint::parse_bytes(MODP_2048.as_bytes(), 16).unwrap();
+        let q = (&p - 1u32) >> 1;
+        Ok(Feldman {
+            k,
+            p,
+            q,
+            g: BigUint::from(2u32),
+        })
+    }
+
+    /// Size of each share produced from a secret of any size.
+    pub fn share_size(&self) -> usize {
+        (usize::from(self.k) + 1) * ELEMENT_SIZE
+    }
+
+    fn random_scalar(&self) -> BigUint {
+        // q is just below 2^2047, so rejection sampling almost never needs a second attempt.
+        let mut bytes = [0u8; ELEMENT_SIZE];
+        loop {
+            OsRng.fill_bytes(&mut bytes);
+            bytes[0] &= 0x7f;
+            let scalar = BigUint::from_bytes_be(&bytes);
+            if scalar < self.q {
+                return scalar;
+            }
+        }
+    }
+
+    fn eval(&self, coefficients: &[BigUint], x: u16) -> BigUint {
+        coefficients
+            .iter()
+            .rev()
+            .fold(BigUint::from(0u32), |acc, c| (acc * x + c) % &self.q)
+    }
+
+    /// Checks share `y` at index `x` against `commitments`, i.e. that
+    /// `g^y = prod_j commitments[j]^(x^j)`.
+    pub fn verify(&self, commitments: &Commitments, x: u16, y: &BigUint) -> bool {
+        let mut expected = BigUint::from(1u32);
+        let mut x_power = BigUint::from(1u32);
+        for commitment in commitments.0.iter() {
+            expected = expected * commitment.modpow(&x_power, &self.p) % &self.p;
+            x_power = x_power * x % &self.q;
+        }
+        self.g.modpow(y, &self.p) == expected
+    }
+
+    // Reads the commitments and share value from the start of a share.
+    fn read_share<R: Read>(&self, input: &mut InputPartition<R>) -> Result<(Commitments, BigUint)> {
+        let mut buf = vec![0u8; self.share_size()];
+        input
+            .reader
+            .read_exact(&mut buf)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => {
+                    SplitSecretError::TruncatedShare { x: input.x }
+                }
+                _ => e.into(),
+            })?;
+        let mut elements: Vec<BigUint> = buf
+            .chunks(ELEMENT_SIZE)
+            .map(BigUint::from_bytes_be)
+            .collect();
+        let y = elements.pop().unwrap();
+        Ok((Commitments(elements), y))
+    }
+
+    /// Reads a single share and checks it against the commitments it carries, returning those
+    /// commitments so that they can be compared with the ones the dealer published.
+    pub fn verify_share<R: Read>(&self, mut input: InputPartition<R>) -> Result<Commitments> {
+        let (commitments, y) = self.read_share(&mut input)?;
+        if !self.verify(&commitments, input.x, &y) {
+            return Err(SplitSecretError::InvalidShare { x: input.x });
+        }
+        Ok(commitments)
+    }
+}
+
+impl Feldman {
+    // Reads every input and returns their common commitments and the first k shares that pass
+    // verification. The others are left out and reported, as long as k valid shares remain.
+    fn read_valid_shares<R: Read>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+    ) -> Result<(Commitments, Vec<Share>, JoinReport)> {
+        check_indices(self.k, inputs.iter().map(|input| input.x))?;
+        let mut shares = Vec::new();
+        let mut report = JoinReport::default();
+        let mut first_commitments: Option<Commitments> = None;
+        for input in inputs.iter_mut() {
+            let (commitments, y) = self.read_share(input)?;
+            if let Some(first_commitments) = &first_commitments {
+                if *first_commitments != commitments {
+                    return Err(SplitSecretError::CommitmentMismatch { x: input.x });
+                }
+            }
+            if self.verify(&commitments, input.x, &y) {
+                shares.push((input.x, y));
+            } else {
+                report.corrupted.push(input.x);
+            }
+            first_commitments = Some(commitments);
+        }
+        if shares.len() < self.k.into() {
+            return Err(SplitSecretError::InvalidShare {
+                x: report.corrupted[0],
+            });
+        }
+        shares.truncate(self.k.into());
+        // check_indices made sure there was at least one input.
+        Ok((first_commitments.unwrap(), shares, report))
+    }
+
+    // Evaluates the polynomial through `shares` at `x` by Lagrange interpolation in Z_q.
+    fn interpolate(&self, shares: &[Share], x: u16) -> BigUint {
+        let mut result = BigUint::from(0u32);
+        for (share_x, y) in shares.iter() {
+            let mut numerator = BigUint::from(1u32);
+            let mut denominator = BigUint::from(1u32);
+            for (other_x, _) in shares.iter() {
+                if other_x == share_x {
+                    continue;
+                }
+                numerator =
+                    numerator * ((BigUint::from(x) + &self.q - *other_x) % &self.q) % &self.q;
+                denominator = denominator
+                    * ((BigUint::from(*share_x) + &self.q - *other_x) % &self.q)
+                    % &self.q;
+            }
+            let inverse = denominator.modpow(&(&self.q - 2u32), &self.q);
+            result = (result + y * numerator % &self.q * inverse) % &self.q;
+        }
+        result
+    }
+}
+
+impl Partitioner for Feldman {
+    fn split<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        check_indices(self.k, outputs.iter().map(|output| output.x))?;
+        let mut secret = vec![1u8];
+        (&mut input)
+            .take(MAX_SECRET_SIZE as u64 + 1)
+            .read_to_end(&mut secret)?;
+        if secret.len() > MAX_SECRET_SIZE + 1 {
+            return Err(SplitSecretError::SecretTooLarge {
+                max: MAX_SECRET_SIZE,
+            });
+        }
+
+        // The leading 1 byte preserves leading zeros in the secret.
+        let mut coefficients = vec![BigUint::from_bytes_be(&secret)];
+        for _ in 1..self.k {
+            coefficients.push(self.random_scalar());
+        }
+        let commitments = Commitments(
+            coefficients
+                .iter()
+                .map(|a| self.g.modpow(a, &self.p))
+                .collect(),
+        )
+        .to_bytes();
+        for output in outputs.iter_mut() {
+            output.writer.write_all(&commitments)?;
+            output
+                .writer
+                .write_all(&to_element(&self.eval(&coefficients, output.x)))?;
+        }
+        Ok(())
+    }
+
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> Result<JoinReport> {
+        check_indices(self.k, inputs.iter().map(|input| input.x))?;
+
+        let (_, shares, report) = self.read_valid_shares(inputs)?;
+        let secret = self.interpolate(&shares, 0);
+
+        let secret = secret.to_bytes_be();
+        match secret.split_first() {
+            Some((1, secret)) => output.write_all(secret)?,
+            _ => return Err(SplitSecretError::InvalidShare { x: inputs[0].x }),
+        }
+        Ok(report)
+    }
+
+    fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        check_indices(self.k, inputs.iter().map(|input| input.x))?;
+        check_outputs(inputs.len(), outputs.len())?;
+        let mut shares = Vec::new();
+        let mut first_commitments: Option<Commitments> = None;
+        for input in inputs.iter_mut() {
+            let (commitments, y) = self.read_share(input)?;
+            if let Some(first_commitments) = &first_commitments {
+                if *first_commitments != commitments {
+                    return Err(SplitSecretError::CommitmentMismatch { x: input.x });
+                }
+            }
+            if !self.verify(&commitments, input.x, &y) {
+                return Err(SplitSecretError::InvalidShare { x: input.x });
+            }
+            first_commitments = Some(commitments);
+            shares.push((input.x, y));
+        }
+        let commitments = match first_commitments {
+            Some(commitments) => commitments,
+            None => return Ok(()),
+        };
+
+        // Add a random polynomial with a zero constant term to the shares, and its commitments
+        // to the old ones, so that holders can verify their new shares as before.
+        let mut delta = vec![BigUint::from(0u32)];
+        for _ in 1..self.k {
+            delta.push(self.random_scalar());
+        }
+        let commitments = Commitments(
+            commitments
+                .0
+                .iter()
+                .zip(delta.iter())
+                .map(|(c, d)| c * self.g.modpow(d, &self.p) % &self.p)
+                .collect(),
+        )
+        .to_bytes();
+        for ((x, y), output) in shares.iter().zip(outputs.iter_mut()) {
+            output.write_all(&commitments)?;
+            output.write_all(&to_element(&((y + self.eval(&delta, *x)) % &self.q)))?;
+        }
+        Ok(())
+    }
+
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: OutputPartition<W>,
+    ) -> Result<()> {
+        check_new_index(inputs, output.x)?;
+        let (commitments, shares, _) = self.read_valid_shares(inputs)?;
+        output.writer.write_all(&commitments.to_bytes())?;
+        output
+            .writer
+            .write_all(&to_element(&self.interpolate(&shares, output.x)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partitioner::{test_join, InMemoryPartition};
+
+    #[test]
+    fn two_of_three() {
+        let plaintext: Vec<u8> = "\0leading zero".as_bytes().into();
+        let feldman = Feldman::new(2).unwrap();
+        let mut partitions = feldman.split_in_memory(&plaintext, 3).unwrap();
+        for partition in partitions.iter() {
+            assert_eq!(partition.value.len(), feldman.share_size());
+            feldman
+                .verify_share(InputPartition {
+                    x: partition.x,
+                    reader: &partition.value[..],
+                })
+                .unwrap();
+        }
+        test_join(&feldman, &mut partitions[..], 2, &plaintext);
+    }
+
+    #[test]
+    fn inconsistent_share() {
+        let plaintext = vec![3u8; 48];
+        let feldman = Feldman::new(2).unwrap();
+        let mut partitions = feldman.split_in_memory(&plaintext, 3).unwrap();
+        // A dealer that hands out a share off the committed polynomial.
+        let last = partitions[1].value.len() - 1;
+        partitions[1].value[last] ^= 1;
+        assert!(matches!(
+            feldman.verify_share(InputPartition {
+                x: 2,
+                reader: &partitions[1].value[..],
+            }),
+            Err(SplitSecretError::InvalidShare { x: 2 })
+        ));
+        let (first, rest) = partitions.split_at_mut(1);
+        assert!(matches!(
+            feldman.join_in_memory(&mut [&mut first[0], &mut rest[0]]),
+            Err(SplitSecretError::InvalidShare { x: 2 })
+        ));
+
+        // A third share makes up for it.
+        let mut inputs: Vec<_> = partitions
+            .iter()
+            .map(|partition| InputPartition {
+                x: partition.x,
+                reader: &partition.value[..],
+            })
+            .collect();
+        let mut output = Vec::new();
+        let report = feldman.join(&mut inputs, &mut output).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [2]);
+    }
+
+    #[test]
+    fn refresh() {
+        let plaintext = vec![9u8; 32];
+        let feldman = Feldman::new(2).unwrap();
+        let partitions = feldman.split_in_memory(&plaintext, 3).unwrap();
+        let mut refreshed = vec![Vec::new(); 3];
+        feldman
+            .refresh(
+                &mut partitions
+                    .iter()
+                    .map(|partition| InputPartition {
+                        x: partition.x,
+                        reader: &partition.value[..],
+                    })
+                    .collect::<Vec<_>>(),
+                &mut refreshed,
+            )
+            .unwrap();
+        let mut refreshed: Vec<_> = partitions
+            .iter()
+            .zip(refreshed)
+            .map(|(partition, value)| {
+                assert_ne!(partition.value, value);
+                feldman
+                    .verify_share(InputPartition {
+                        x: partition.x,
+                        reader: &value[..],
+                    })
+                    .unwrap();
+                InMemoryPartition {
+                    x: partition.x,
+                    value,
+                }
+            })
+            .collect();
+        test_join(&feldman, &mut refreshed[..], 2, &plaintext);
+    }
+
+    #[test]
+    fn repair() {
+        let plaintext = vec![5u8; 40];
+        let feldman = Feldman::new(3).unwrap();
+        let partitions = feldman.split_in_memory(&plaintext, 5).unwrap();
+        let mut repaired = Vec::new();
+        feldman
+            .repair(
+                &mut partitions[1..4]
+                    .iter()
+                    .map(|partition| InputPartition {
+                        x: partition.x,
+                        reader: &partition.value[..],
+                    })
+                    .collect::<Vec<_>>(),
+                OutputPartition {
+                    x: 5,
+                    writer: &mut repaired,
+                },
+            )
+            .unwrap();
+        assert_eq!(partitions[4].value, repaired);
+    }
+
+    #[test]
+    fn secret_too_large() {
+        let feldman = Feldman::new(2).unwrap();
+        assert!(matches!(
+            feldman.split_in_memory(&[0u8; MAX_SECRET_SIZE + 1], 2),
+            Err(SplitSecretError::SecretTooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,573 @@
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use crate::error::{Result, SplitSecretError};
+use crate::gf2p8_kernel::{mul_add, NibbleTables};
+use crate::padding_streaming::{Op, PaddedReader, PaddedWriter};
+
+use block_padding::Iso7816;
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// A finite field that [`crate::Shamir`] and [`crate::Ida`] can work over.
+///
+/// Partitioners are generic over the field, so its arithmetic is monomorphized into their inner
+/// loops. Constructing a field is cheap: any tables it needs are built at compile time or once per
+/// process, and the partitioners construct theirs once, when they are created.
+///
+/// Streams are processed one element at a time, each element being `ELEMENT_SIZE` big-endian
+/// bytes. Share indices are elements too, so the size of the field bounds the number of shares.
+/// Fields and their elements are owned, so partitioners can hand them to their worker threads.
+pub trait GaloisField: Clone + Send + Sync + 'static {
+    type Element: Copy + Eq + Default + Debug + Send + Sync + 'static;
+
+    /// Number of bytes an element is stored in.
+    const ELEMENT_SIZE: usize;
+
+    /// Whether every string of `ELEMENT_SIZE` bytes encodes an element. Streams over other fields
+    /// are not padded, so they must be a whole number of encoded elements.
+    const DENSE: bool = true;
+
+    fn new() -> Self;
+
+    fn add(&self, lhs: Self::Element, rhs: Self::Element) -> Self::Element;
+    fn sub(&self, lhs: Self::Element, rhs: Self::Element) -> Self::Element;
+    fn mult(&self, lhs: Self::Element, rhs: Self::Element) -> Self::Element;
+    fn div(&self, lhs: Self::Element, rhs: Self::Element) -> Self::Element;
+
+    fn zero() -> Self::Element;
+    fn one() -> Self::Element;
+
+    /// The element standing for share index `x`, or [`SplitSecretError::InvalidIndex`] if the
+    /// field is too small for it.
+    fn index(x: u16) -> Result<Self::Element>;
+
+    /// The element encoded by `bytes`, or `None` if they do not encode one.
+    fn decode(bytes: &[u8]) -> Option<Self::Element>;
+    fn encode(element: Self::Element, bytes: &mut [u8]);
+
+    /// Fills `elements` with uniformly random elements. The default decodes random bytes, which
+    /// is only uniform over dense fields.
+    fn random(&self, elements: &mut [Self::Element]) {
+        let mut bytes = vec![0u8; elements.len() * Self::ELEMENT_SIZE];
+        OsRng.fill_bytes(&mut bytes);
+        for (element, chunk) in elements.iter_mut().zip(bytes.chunks(Self::ELEMENT_SIZE)) {
+            *element = Self::decode(chunk).unwrap();
+        }
+    }
+
+    /// Adds `src` times `scale` to `dst`, elementwise.
+    fn add_scaled(&self, dst: &mut [Self::Element], src: &[Self::Element], scale: Self::Element) {
+        for (d, s) in dst.iter_mut().zip(src.iter()) {
+            *d = self.add(*d, self.mult(*s, scale));
+        }
+    }
+}
+
+/// GF(2^8) with the irreducible polynomial `POLYNOMIAL`, given with the x^8 term as bit 8; every
+/// byte is an element. Its logarithm tables are computed at compile time, and
+/// [`GaloisField::add_scaled`] uses SIMD instructions where the CPU has them.
+#[derive(Clone, Copy, Debug)]
+pub struct Gf2p8<const POLYNOMIAL: u16>;
+
+/// GF(2^8) with the polynomial x^8 + x^4 + x^3 + x^2 + 1, which share files use by default.
+pub type Gf256 = Gf2p8<0x11d>;
+
+struct Gf2p8Tables {
+    // exp[i] = g^i for a generator g, repeated so that a sum of two logarithms can index it.
+    exp: [u8; 510],
+    log: [u8; 256],
+}
+
+// Multiplies without tables, for building them.
+const fn gf2p8_mult(mut lhs: u16, mut rhs: u16, polynomial: u16) -> u16 {
+    let mut product = 0;
+    while rhs != 0 {
+        if rhs & 1 != 0 {
+            product ^= lhs;
+        }
+        lhs <<= 1;
+        if lhs & 0x100 != 0 {
+            lhs ^= polynomial;
+        }
+        rhs >>= 1;
+    }
+    product
+}
+
+const fn gf2p8_tables(polynomial: u16) -> Gf2p8Tables {
+    // Not every irreducible polynomial has x as a generator, so look for the first element whose
+    // powers reach every nonzero element.
+    let mut generator = 2;
+    loop {
+        if generator == 256 {
+            panic!("the polynomial is not irreducible");
+        }
+        let mut x = generator;
+        let mut order = 1;
+        while x != 1 && order < 256 {
+            x = gf2p8_mult(x, generator, polynomial);
+            order += 1;
+        }
+        if order == 255 {
+            break;
+        }
+        generator += 1;
+    }
+
+    let mut tables = Gf2p8Tables {
+        exp: [0; 510],
+        log: [0; 256],
+    };
+    let mut x = 1;
+    let mut i = 0;
+    while i < 255 {
+        tables.exp[i] = x as u8;
+        tables.exp[i + 255] = x as u8;
+        tables.log[x as usize] = i as u8;
+        x = gf2p8_mult(x, generator, polynomial);
+        i += 1;
+    }
+    tables
+}
+
+impl<const POLYNOMIAL: u16> Gf2p8<POLYNOMIAL> {
+    const TABLES: &'static Gf2p8Tables = &gf2p8_tables(POLYNOMIAL);
+}
+
+impl<const POLYNOMIAL: u16> GaloisField for Gf2p8<POLYNOMIAL> {
+    type Element = u8;
+
+    const ELEMENT_SIZE: usize = 1;
+
+    fn new() -> Self {
+        Gf2p8
+    }
+
+    fn add(&self, lhs: u8, rhs: u8) -> u8 {
+        lhs ^ rhs
+    }
+
+    fn sub(&self, lhs: u8, rhs: u8) -> u8 {
+        lhs ^ rhs
+    }
+
+    fn mult(&self, lhs: u8, rhs: u8) -> u8 {
+        if lhs == 0 || rhs == 0 {
+            return 0;
+        }
+        let tables = Self::TABLES;
+        tables.exp
+            [usize::from(tables.log[usize::from(lhs)]) + usize::from(tables.log[usize::from(rhs)])]
+    }
+
+    fn div(&self, lhs: u8, rhs: u8) -> u8 {
+        assert!(rhs != 0, "divide by zero");
+        if lhs == 0 {
+            return 0;
+        }
+        let tables = Self::TABLES;
+        tables.exp[usize::from(tables.log[usize::from(lhs)]) + 255
+            - usize::from(tables.log[usize::from(rhs)])]
+    }
+
+    fn zero() -> u8 {
+        0
+    }
+
+    fn one() -> u8 {
+        1
+    }
+
+    fn index(x: u16) -> Result<u8> {
+        u8::try_from(x).map_err(|_| SplitSecretError::InvalidIndex(x))
+    }
+
+    fn decode(bytes: &[u8]) -> Option<u8> {
+        Some(bytes[0])
+    }
+
+    fn encode(element: u8, bytes: &mut [u8]) {
+        bytes[0] = element;
+    }
+
+    fn add_scaled(&self, dst: &mut [u8], src: &[u8], scale: u8) {
+        if scale == 0 {
+            return;
+        }
+        mul_add(dst, src, &NibbleTables::new(|s| self.mult(s, scale)));
+    }
+}
+
+/// GF(2^16) with the polynomial x^16 + x^12 + x^3 + x + 1, which allows up to 65535 shares. Its
+/// logarithm tables are built the first time it is used.
+#[derive(Clone, Copy)]
+pub struct Gf65536 {
+    tables: &'static Gf65536Tables,
+}
+
+struct Gf65536Tables {
+    // As for GF(2^8), with x as the generator.
+    exp: Vec<u16>,
+    log: Vec<u16>,
+}
+
+const GF65536_POLYNOMIAL: u32 = 0x1100b;
+const GF65536_ORDER: usize = 65535;
+
+static GF65536_TABLES: OnceLock<Gf65536Tables> = OnceLock::new();
+
+fn gf65536_tables() -> Gf65536Tables {
+    let mut exp = vec![0u16; 2 * GF65536_ORDER];
+    let mut log = vec![0u16; GF65536_ORDER + 1];
+    let mut x: u32 = 1;
+    for (i, power) in exp[..GF65536_ORDER].iter_mut().enumerate() {
+        *power = x as u16;
+        log[x as usize] = i as u16;
+        x <<= 1;
+        if x & 0x10000 != 0 {
+            x ^= GF65536_POLYNOMIAL;
+        }
+    }
+    exp.copy_within(..GF65536_ORDER, GF65536_ORDER);
+    Gf65536Tables { exp, log }
+}
+
+impl GaloisField for Gf65536 {
+    type Element = u16;
+
+    const ELEMENT_SIZE: usize = 2;
+
+    fn new() -> Self {
+        Gf65536 {
+            tables: GF65536_TABLES.get_or_init(gf65536_tables),
+        }
+    }
+
+    fn add(&self, lhs: u16, rhs: u16) -> u16 {
+        lhs ^ rhs
+    }
+
+    fn sub(&self, lhs: u16, rhs: u16) -> u16 {
+        lhs ^ rhs
+    }
+
+    fn mult(&self, lhs: u16, rhs: u16) -> u16 {
+        if lhs == 0 || rhs == 0 {
+            return 0;
+        }
+        let tables = self.tables;
+        tables.exp
+            [usize::from(tables.log[usize::from(lhs)]) + usize::from(tables.log[usize::from(rhs)])]
+    }
+
+    fn div(&self, lhs: u16, rhs: u16) -> u16 {
+        assert!(rhs != 0, "divide by zero");
+        if lhs == 0 {
+            return 0;
+        }
+        let tables = self.tables;
+        tables.exp[usize::from(tables.log[usize::from(lhs)]) + GF65536_ORDER
+            - usize::from(tables.log[usize::from(rhs)])]
+    }
+
+    fn zero() -> u16 {
+        0
+    }
+
+    fn one() -> u16 {
+        1
+    }
+
+    fn index(x: u16) -> Result<u16> {
+        Ok(x)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<u16> {
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn encode(element: u16, bytes: &mut [u8]) {
+        bytes[..2].copy_from_slice(&element.to_be_bytes());
+    }
+}
+
+/// A prime below 2^256, for [`PrimeField`].
+pub trait Prime: Clone + Send + Sync + 'static {
+    /// The prime, big-endian.
+    const MODULUS: [u8; 32];
+}
+
+/// The order of the secp256k1 group, for sharing secp256k1 private keys.
+#[derive(Clone, Copy, Debug)]
+pub struct Secp256k1Order;
+
+impl Prime for Secp256k1Order {
+    const MODULUS: [u8; 32] =
+        parse_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141");
+}
+
+/// 2^255 - 19, the prime of Curve25519.
+#[derive(Clone, Copy, Debug)]
+pub struct P25519;
+
+impl Prime for P25519 {
+    const MODULUS: [u8; 32] =
+        parse_hex("7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED");
+}
+
+const fn parse_hex(hex: &str) -> [u8; 32] {
+    const fn digit(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'A'..=b'F' => c - b'A' + 10,
+            _ => panic!("not an uppercase hex digit"),
+        }
+    }
+    let hex = hex.as_bytes();
+    let mut bytes = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        bytes[i] = digit(hex[2 * i]) << 4 | digit(hex[2 * i + 1]);
+        i += 1;
+    }
+    bytes
+}
+
+/// The integers modulo the prime `P`, for sharing secrets that are 32-byte numbers below `P`, such
+/// as private keys, the same way as other tools that share over prime fields.
+///
+/// Elements are stored as 32 big-endian bytes. Not every 32 bytes encode an element, so streams
+/// are not padded: a secret must be a whole number of elements, and usually is exactly one.
+/// Arithmetic goes through [`BigUint`], which is slow, but such secrets are short.
+#[derive(Clone, Debug)]
+pub struct PrimeField<P: Prime> {
+    modulus: BigUint,
+    _p: PhantomData<P>,
+}
+
+impl<P: Prime> PrimeField<P> {
+    fn element(&self, n: BigUint) -> [u8; 32] {
+        let bytes = n.to_bytes_be();
+        let mut element = [0u8; 32];
+        element[32 - bytes.len()..].copy_from_slice(&bytes);
+        element
+    }
+}
+
+impl<P: Prime> GaloisField for PrimeField<P> {
+    type Element = [u8; 32];
+
+    const ELEMENT_SIZE: usize = 32;
+    const DENSE: bool = false;
+
+    fn new() -> Self {
+        PrimeField {
+            modulus: BigUint::from_bytes_be(&P::MODULUS),
+            _p: PhantomData,
+        }
+    }
+
+    fn add(&self, lhs: [u8; 32], rhs: [u8; 32]) -> [u8; 32] {
+        let sum = BigUint::from_bytes_be(&lhs) + BigUint::from_bytes_be(&rhs);
+        self.element(sum % &self.modulus)
+    }
+
+    fn sub(&self, lhs: [u8; 32], rhs: [u8; 32]) -> [u8; 32] {
+        let difference =
+            BigUint::from_bytes_be(&lhs) + &self.modulus - BigUint::from_bytes_be(&rhs);
+        self.element(difference % &self.modulus)
+    }
+
+    fn mult(&self, lhs: [u8; 32], rhs: [u8; 32]) -> [u8; 32] {
+        let product = BigUint::from_bytes_be(&lhs) * BigUint::from_bytes_be(&rhs);
+        self.element(product % &self.modulus)
+    }
+
+    fn div(&self, lhs: [u8; 32], rhs: [u8; 32]) -> [u8; 32] {
+        assert!(rhs != Self::zero(), "divide by zero");
+        // By Fermat's little theorem, rhs^(p - 2) is the inverse of rhs.
+        let inverse = BigUint::from_bytes_be(&rhs).modpow(&(&self.modulus - 2u32), &self.modulus);
+        self.element(BigUint::from_bytes_be(&lhs) * inverse % &self.modulus)
+    }
+
+    fn zero() -> [u8; 32] {
+        [0u8; 32]
+    }
+
+    fn one() -> [u8; 32] {
+        let mut element = [0u8; 32];
+        element[31] = 1;
+        element
+    }
+
+    fn index(x: u16) -> Result<[u8; 32]> {
+        let mut element = [0u8; 32];
+        element[30..].copy_from_slice(&x.to_be_bytes());
+        Ok(element)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<[u8; 32]> {
+        // Big-endian numbers of the same length compare like their bytes.
+        if bytes[..32] >= P::MODULUS[..] {
+            return None;
+        }
+        let mut element = [0u8; 32];
+        element.copy_from_slice(&bytes[..32]);
+        Some(element)
+    }
+
+    fn encode(element: [u8; 32], bytes: &mut [u8]) {
+        bytes[..32].copy_from_slice(&element);
+    }
+
+    fn random(&self, elements: &mut [[u8; 32]]) {
+        // Draw numbers no longer than the modulus until one is below it.
+        let mask = 0xffu8 >> P::MODULUS[0].leading_zeros();
+        for element in elements.iter_mut() {
+            loop {
+                OsRng.fill_bytes(element);
+                element[0] &= mask;
+                if element[..] < P::MODULUS[..] {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Converts between byte buffers and element buffers; bytes.len() is elements.len() elements.
+// Decoding fails if some of the bytes do not encode an element.
+pub(crate) fn decode_elements<F: GaloisField>(
+    bytes: &[u8],
+    elements: &mut [F::Element],
+) -> Option<()> {
+    for (element, chunk) in elements.iter_mut().zip(bytes.chunks(F::ELEMENT_SIZE)) {
+        *element = F::decode(chunk)?;
+    }
+    Some(())
+}
+
+pub(crate) fn encode_elements<F: GaloisField>(elements: &[F::Element], bytes: &mut [u8]) {
+    for (element, chunk) in elements.iter().zip(bytes.chunks_mut(F::ELEMENT_SIZE)) {
+        F::encode(*element, chunk);
+    }
+}
+
+// Decodes bytes of the input being split, returning the number of elements.
+pub(crate) fn decode_secret<F: GaloisField>(
+    bytes: &[u8],
+    elements: &mut [F::Element],
+) -> Result<usize> {
+    let len = bytes.len() / F::ELEMENT_SIZE;
+    if !bytes.len().is_multiple_of(F::ELEMENT_SIZE)
+        || decode_elements::<F>(bytes, &mut elements[..len]).is_none()
+    {
+        return Err(SplitSecretError::InvalidSecret(format!(
+            "must be a whole number of {}-byte values below the modulus of the field",
+            F::ELEMENT_SIZE
+        )));
+    }
+    Ok(len)
+}
+
+pub(crate) fn indices<F: GaloisField>(xs: impl Iterator<Item = u16>) -> Result<Vec<F::Element>> {
+    xs.map(F::index).collect()
+}
+
+// Streams over dense fields are padded with ISO 7816 padding to a whole number of elements.
+// Fields of byte-sized elements need no padding, so their shares are unchanged.
+pub(crate) fn pad_to_elements<'a, F: GaloisField, R: Read + 'a>(input: R) -> Box<dyn Read + 'a> {
+    match F::ELEMENT_SIZE {
+        size if size > 1 && F::DENSE => {
+            Box::new(PaddedReader::<Iso7816, _>::new(size, input, Op::Pad))
+        }
+        _ => Box::new(input),
+    }
+}
+
+pub(crate) fn unpad_from_elements<'a, F: GaloisField, W: Write + 'a>(
+    output: W,
+) -> Box<dyn Write + 'a> {
+    match F::ELEMENT_SIZE {
+        size if size > 1 && F::DENSE => {
+            Box::new(PaddedWriter::<Iso7816, _>::new(size, output, Op::Unpad))
+        }
+        _ => Box::new(output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gf2p8_matches_slow_mult() {
+        // 0x11b, the AES polynomial, does not have x as a generator.
+        fn check<const POLYNOMIAL: u16>() {
+            let field = Gf2p8::<POLYNOMIAL>::new();
+            for a in 0..=255u8 {
+                for b in 0..=255u8 {
+                    let product = field.mult(a, b);
+                    assert_eq!(
+                        u16::from(product),
+                        gf2p8_mult(a.into(), b.into(), POLYNOMIAL)
+                    );
+                    if b != 0 {
+                        assert_eq!(field.div(product, b), a);
+                    }
+                }
+            }
+        }
+        check::<0x11d>();
+        check::<0x11b>();
+    }
+
+    #[test]
+    fn gf65536_generator() {
+        // Every nonzero element is a power of the generator exactly once, so the polynomial is
+        // primitive and the tables are complete.
+        let field = Gf65536::new();
+        let mut seen = vec![false; GF65536_ORDER + 1];
+        for x in field.tables.exp[..GF65536_ORDER].iter() {
+            assert!(*x != 0 && !seen[usize::from(*x)]);
+            seen[usize::from(*x)] = true;
+        }
+    }
+
+    #[test]
+    fn gf65536_arithmetic() {
+        let field = Gf65536::new();
+        for (a, b) in [(1u16, 1u16), (2, 0x8000), (0x1234, 0xfedc), (65535, 65535)] {
+            let product = field.mult(a, b);
+            assert_eq!(field.div(product, b), a);
+            assert_eq!(field.mult(b, a), product);
+        }
+        // x * x^15 = x^16 = x^12 + x^3 + x + 1.
+        assert_eq!(field.mult(2, 0x8000), 0x100b);
+    }
+
+    #[test]
+    fn prime_field() {
+        type Fp = PrimeField<Secp256k1Order>;
+        let field = Fp::new();
+        let minus_one = field.sub(Fp::zero(), Fp::one());
+        let mut expected = Secp256k1Order::MODULUS;
+        expected[31] -= 1;
+        assert_eq!(minus_one, expected);
+        assert_eq!(field.mult(minus_one, minus_one), Fp::one());
+        assert_eq!(field.div(Fp::one(), minus_one), minus_one);
+
+        // Only numbers below the modulus encode elements, and random elements are all below it.
+        type Fq = PrimeField<P25519>;
+        assert_eq!(Fq::decode(&P25519::MODULUS), None);
+        assert_eq!(Fq::decode(&expected), None);
+        let mut elements = [[0u8; 32]; 64];
+        Fq::new().random(&mut elements);
+        assert!(elements.iter().all(|e| Fq::decode(e).is_some()));
+    }
+}
@@ -0,0 +1,180 @@
+// Multiply-accumulate over GF(2^8), the inner loop of every partitioner over the field.
+//
+// Multiplication by a fixed scale distributes over xor, so the product of the scale with a byte s
+// is the xor of its products with the low and high nibbles of s, each looked up in a table of 16
+// entries. A table of 16 bytes fits in a vector register, where a byte shuffle (PSHUFB on x86,
+// TBL on ARM) looks up a whole vector of nibbles at once. The instruction set is detected at run
+// time; anything else, and the tail of a slice that does not fill a vector, uses the same tables
+// a byte at a time.
+
+pub(crate) struct NibbleTables {
+    lo: [u8; 16],
+    hi: [u8; 16],
+}
+
+impl NibbleTables {
+    // Tables for multiplying by the scale that `mult` multiplies by.
+    pub(crate) fn new(mult: impl Fn(u8) -> u8) -> Self {
+        let mut tables = NibbleTables {
+            lo: [0u8; 16],
+            hi: [0u8; 16],
+        };
+        for i in 0..16u8 {
+            tables.lo[usize::from(i)] = mult(i);
+            tables.hi[usize::from(i)] = mult(i << 4);
+        }
+        tables
+    }
+}
+
+// Adds src times the scale of tables to dst, bytewise.
+pub(crate) fn mul_add(dst: &mut [u8], src: &[u8], tables: &NibbleTables) {
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+    let done = mul_add_vectors(dst, src, tables);
+    mul_add_portable(&mut dst[done..], &src[done..], tables);
+}
+
+fn mul_add_portable(dst: &mut [u8], src: &[u8], tables: &NibbleTables) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d ^= tables.lo[usize::from(s & 0x0f)] ^ tables.hi[usize::from(s >> 4)];
+    }
+}
+
+// Processes as many whole vectors as the CPU supports, returning the number of bytes done.
+#[cfg(target_arch = "x86_64")]
+fn mul_add_vectors(dst: &mut [u8], src: &[u8], tables: &NibbleTables) -> usize {
+    if is_x86_feature_detected!("avx2") {
+        // Safety: the CPU supports AVX2.
+        unsafe { x86::mul_add_avx2(dst, src, tables) }
+    } else if is_x86_feature_detected!("ssse3") {
+        // Safety: the CPU supports SSSE3.
+        unsafe { x86::mul_add_ssse3(dst, src, tables) }
+    } else {
+        0
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn mul_add_vectors(dst: &mut [u8], src: &[u8], tables: &NibbleTables) -> usize {
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // Safety: the CPU supports NEON.
+        unsafe { aarch64::mul_add_neon(dst, src, tables) }
+    } else {
+        0
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn mul_add_vectors(_dst: &mut [u8], _src: &[u8], _tables: &NibbleTables) -> usize {
+    0
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::NibbleTables;
+
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn mul_add_ssse3(dst: &mut [u8], src: &[u8], tables: &NibbleTables) -> usize {
+        let lo = _mm_loadu_si128(tables.lo.as_ptr() as *const __m128i);
+        let hi = _mm_loadu_si128(tables.hi.as_ptr() as *const __m128i);
+        let mask = _mm_set1_epi8(0x0f);
+        let mut done = 0;
+        for (d, s) in dst.chunks_exact_mut(16).zip(src.chunks_exact(16)) {
+            let s = _mm_loadu_si128(s.as_ptr() as *const __m128i);
+            let products = _mm_xor_si128(
+                _mm_shuffle_epi8(lo, _mm_and_si128(s, mask)),
+                _mm_shuffle_epi8(hi, _mm_and_si128(_mm_srli_epi64(s, 4), mask)),
+            );
+            let sum = _mm_xor_si128(_mm_loadu_si128(d.as_ptr() as *const __m128i), products);
+            _mm_storeu_si128(d.as_mut_ptr() as *mut __m128i, sum);
+            done += 16;
+        }
+        done
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn mul_add_avx2(dst: &mut [u8], src: &[u8], tables: &NibbleTables) -> usize {
+        // The shuffle looks up each 128-bit half in its own copy of the table.
+        let lo = _mm256_broadcastsi128_si256(_mm_loadu_si128(tables.lo.as_ptr() as *const __m128i));
+        let hi = _mm256_broadcastsi128_si256(_mm_loadu_si128(tables.hi.as_ptr() as *const __m128i));
+        let mask = _mm256_set1_epi8(0x0f);
+        let mut done = 0;
+        for (d, s) in dst.chunks_exact_mut(32).zip(src.chunks_exact(32)) {
+            let s = _mm256_loadu_si256(s.as_ptr() as *const __m256i);
+            let products = _mm256_xor_si256(
+                _mm256_shuffle_epi8(lo, _mm256_and_si256(s, mask)),
+                _mm256_shuffle_epi8(hi, _mm256_and_si256(_mm256_srli_epi64(s, 4), mask)),
+            );
+            let sum = _mm256_xor_si256(_mm256_loadu_si256(d.as_ptr() as *const __m256i), products);
+            _mm256_storeu_si256(d.as_mut_ptr() as *mut __m256i, sum);
+            done += 32;
+        }
+        // A last half vector is still worth a shuffle.
+        done + mul_add_ssse3(&mut dst[done..], &src[done..], tables)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    use std::arch::aarch64::*;
+
+    use super::NibbleTables;
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn mul_add_neon(dst: &mut [u8], src: &[u8], tables: &NibbleTables) -> usize {
+        let lo = vld1q_u8(tables.lo.as_ptr());
+        let hi = vld1q_u8(tables.hi.as_ptr());
+        let mask = vdupq_n_u8(0x0f);
+        let mut done = 0;
+        for (d, s) in dst.chunks_exact_mut(16).zip(src.chunks_exact(16)) {
+            let s = vld1q_u8(s.as_ptr());
+            let products = veorq_u8(
+                vqtbl1q_u8(lo, vandq_u8(s, mask)),
+                vqtbl1q_u8(hi, vshrq_n_u8(s, 4)),
+            );
+            vst1q_u8(d.as_mut_ptr(), veorq_u8(vld1q_u8(d.as_ptr()), products));
+            done += 16;
+        }
+        done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{GaloisField, Gf256};
+
+    #[test]
+    fn matches_portable() {
+        let field = Gf256::new();
+        let src: Vec<u8> = (0..1000).map(|i| (i * 7 + i / 256) as u8).collect();
+        for scale in [0u8, 1, 2, 0x53, 0xff] {
+            let tables = NibbleTables::new(|s| field.mult(s, scale));
+            // Lengths around the vector sizes leave tails of every size.
+            for len in [0usize, 1, 15, 16, 17, 31, 32, 33, 48, 63, 1000] {
+                let initial: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+                let mut expected = initial.clone();
+                for (d, s) in expected.iter_mut().zip(src.iter()) {
+                    *d ^= field.mult(*s, scale);
+                }
+                let mut portable = initial.clone();
+                mul_add_portable(&mut portable, &src[..len], &tables);
+                assert_eq!(portable, expected);
+                let mut dst = initial.clone();
+                mul_add(&mut dst, &src[..len], &tables);
+                assert_eq!(dst, expected);
+
+                #[cfg(target_arch = "x86_64")]
+                if is_x86_feature_detected!("ssse3") {
+                    let mut dst = initial.clone();
+                    let done = unsafe { x86::mul_add_ssse3(&mut dst, &src[..len], &tables) };
+                    mul_add_portable(&mut dst[done..], &src[done..len], &tables);
+                    assert_eq!(dst, expected);
+                }
+            }
+        }
+    }
+}
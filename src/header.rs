@@ -0,0 +1,472 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::error::{Result, SplitSecretError};
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// Bytes at the start of every share file.
+pub const MAGIC: [u8; 4] = *b"SSSh";
+/// Version of the share file format written by this crate.
+pub const FORMAT_VERSION: u8 = 7;
+/// Size in bytes of a share header.
+pub const HEADER_SIZE: usize = 31;
+/// The irreducible polynomials of GF(2^8) that share files can be computed with, with the x^8
+/// term as bit 8: x^8 + x^4 + x^3 + x^2 + 1, the default, and x^8 + x^4 + x^3 + x + 1, the one
+/// AES uses.
+pub const GF2P8_POLYNOMIALS: [u16; 2] = [0x11d, 0x11b];
+
+/// The partitioner a share was produced by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    Shamir = 1,
+    Ida = 2,
+    ShamirIda = 3,
+    /// [`Scheme::ShamirIda`] with the key shared by Feldman's verifiable secret sharing.
+    ShamirIdaVss = 4,
+}
+
+/// The cipher and block mode used to encrypt the dispersed data, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    None = 0,
+    Aes128Cbc = 1,
+    Aes256Cbc = 2,
+    Aes128Cfb = 3,
+    Aes256Cfb = 4,
+    Aes256Gcm = 5,
+    ChaCha20Poly1305 = 6,
+    Aes128Ctr = 7,
+    Aes256Ctr = 8,
+}
+
+/// The finite field the shares are computed over; see [`crate::field::GaloisField`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// GF(2^8) with the given irreducible polynomial, one of [`GF2P8_POLYNOMIALS`], for up to 255
+    /// shares.
+    Gf2p8(u16),
+    /// GF(2^16) with the polynomial x^16 + x^12 + x^3 + x + 1, for up to 65535 shares.
+    Gf65536,
+    /// The integers modulo the order of the secp256k1 group, for 32-byte secrets below it.
+    Secp256k1,
+    /// The integers modulo 2^255 - 19, for 32-byte secrets below it.
+    P25519,
+}
+
+impl Field {
+    /// GF(2^8) with the polynomial x^8 + x^4 + x^3 + x^2 + 1, the field of [`crate::Gf256`].
+    pub const GF256: Field = Field::Gf2p8(0x11d);
+}
+
+/// The padding applied before encrypting or dispersing, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    None = 0,
+    Iso7816 = 1,
+    Pkcs7 = 2,
+}
+
+/// The header at the start of every share file.
+///
+/// All shares from the same split have identical headers apart from `x`, which lets a join
+/// choose the right algorithm and refuse shares that belong to a different split.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShareHeader {
+    pub scheme: Scheme,
+    pub cipher: Cipher,
+    pub padding: Padding,
+    pub field: Field,
+    /// Random identifier shared by all shares of a split.
+    pub set_id: [u8; 16],
+    /// Number of shares needed to reconstruct the original.
+    pub k: u8,
+    /// Index of this share, or of the first share in a bundle.
+    pub x: u16,
+    /// Number of shares bundled in this share file, with indices `x..x + weight`, for a
+    /// participant whose share counts more than once toward the threshold.
+    pub weight: u8,
+}
+
+impl ShareHeader {
+    /// Creates a header for share `x` of a new split over GF(2^8) with a random set identifier.
+    pub fn new(scheme: Scheme, cipher: Cipher, padding: Padding, k: u8, x: u16) -> Self {
+        let mut set_id = [0u8; 16];
+        OsRng.fill_bytes(&mut set_id);
+        ShareHeader {
+            scheme,
+            cipher,
+            padding,
+            field: Field::GF256,
+            set_id,
+            k,
+            x,
+            weight: 1,
+        }
+    }
+
+    /// A header with the same parameters as this one, but the random set identifier of a new
+    /// split, for shares that must not be mixed with this header's.
+    pub fn new_split(&self) -> Self {
+        ShareHeader {
+            field: self.field,
+            weight: self.weight,
+            ..ShareHeader::new(self.scheme, self.cipher, self.padding, self.k, self.x)
+        }
+    }
+
+    /// Whether `other` belongs to the same split as this header.
+    pub fn same_split(&self, other: &ShareHeader) -> bool {
+        ShareHeader {
+            x: other.x,
+            weight: other.weight,
+            ..self.clone()
+        } == *other
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// The header as it is written to a share file.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0u8; HEADER_SIZE];
+        buf[0..4].copy_from_slice(&MAGIC);
+        buf[4] = FORMAT_VERSION;
+        buf[5] = self.scheme as u8;
+        buf[6] = self.cipher as u8;
+        buf[7] = self.padding as u8;
+        buf[8..11].copy_from_slice(&self.field.to_bytes());
+        buf[11..27].copy_from_slice(&self.set_id);
+        buf[27] = self.k;
+        buf[28..30].copy_from_slice(&self.x.to_be_bytes());
+        buf[30] = self.weight;
+        buf
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut buf = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut buf)?;
+        if buf[0..4] != MAGIC {
+            return Err(invalid_header("not a share file"));
+        }
+        if buf[4] != FORMAT_VERSION {
+            return Err(invalid_header(format!(
+                "unsupported format version {}",
+                buf[4]
+            )));
+        }
+        let x = u16::from_be_bytes([buf[28], buf[29]]);
+        if buf[30] == 0 || usize::from(x) + usize::from(buf[30]) > 1 << 16 {
+            return Err(invalid_header(format!(
+                "invalid bundle of {} shares from index {}",
+                buf[30], x
+            )));
+        }
+        let mut set_id = [0u8; 16];
+        set_id.copy_from_slice(&buf[11..27]);
+        Ok(ShareHeader {
+            scheme: Scheme::from_u8(buf[5])?,
+            cipher: Cipher::from_u8(buf[6])?,
+            padding: Padding::from_u8(buf[7])?,
+            field: Field::from_bytes(buf[8], u16::from_be_bytes([buf[9], buf[10]]))?,
+            set_id,
+            k: buf[27],
+            x,
+            weight: buf[30],
+        })
+    }
+}
+
+fn invalid_header(message: impl Into<String>) -> SplitSecretError {
+    SplitSecretError::InvalidHeader(message.into())
+}
+
+impl Scheme {
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Scheme::Shamir),
+            2 => Ok(Scheme::Ida),
+            3 => Ok(Scheme::ShamirIda),
+            4 => Ok(Scheme::ShamirIdaVss),
+            _ => Err(invalid_header(format!("unknown scheme {}", value))),
+        }
+    }
+}
+
+impl Cipher {
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Cipher::None),
+            1 => Ok(Cipher::Aes128Cbc),
+            2 => Ok(Cipher::Aes256Cbc),
+            3 => Ok(Cipher::Aes128Cfb),
+            4 => Ok(Cipher::Aes256Cfb),
+            5 => Ok(Cipher::Aes256Gcm),
+            6 => Ok(Cipher::ChaCha20Poly1305),
+            7 => Ok(Cipher::Aes128Ctr),
+            8 => Ok(Cipher::Aes256Ctr),
+            _ => Err(invalid_header(format!("unknown cipher {}", value))),
+        }
+    }
+}
+
+// A field is stored as a byte naming it followed by the polynomial of GF(2^8), or 0.
+impl Field {
+    fn to_bytes(self) -> [u8; 3] {
+        let (value, polynomial) = match self {
+            Field::Gf2p8(polynomial) => (1, polynomial),
+            Field::Gf65536 => (2, 0),
+            Field::Secp256k1 => (3, 0),
+            Field::P25519 => (4, 0),
+        };
+        let polynomial = polynomial.to_be_bytes();
+        [value, polynomial[0], polynomial[1]]
+    }
+
+    fn from_bytes(value: u8, polynomial: u16) -> Result<Self> {
+        let field = match value {
+            1 if GF2P8_POLYNOMIALS.contains(&polynomial) => return Ok(Field::Gf2p8(polynomial)),
+            1 => {
+                return Err(invalid_header(format!(
+                    "unsupported GF(2^8) polynomial {:#x}",
+                    polynomial
+                )))
+            }
+            2 => Field::Gf65536,
+            3 => Field::Secp256k1,
+            4 => Field::P25519,
+            _ => return Err(invalid_header(format!("unknown field {}", value))),
+        };
+        if polynomial != 0 {
+            return Err(invalid_header(format!(
+                "field {} does not take a polynomial",
+                field
+            )));
+        }
+        Ok(field)
+    }
+}
+
+impl Padding {
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Padding::None),
+            1 => Ok(Padding::Iso7816),
+            2 => Ok(Padding::Pkcs7),
+            _ => Err(invalid_header(format!("unknown padding {}", value))),
+        }
+    }
+}
+
+// Names used on the command line.
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scheme::Shamir => "shamir",
+            Scheme::Ida => "ida",
+            Scheme::ShamirIda => "shamir-ida",
+            Scheme::ShamirIdaVss => "shamir-ida-vss",
+        })
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "shamir" => Ok(Scheme::Shamir),
+            "ida" => Ok(Scheme::Ida),
+            "shamir-ida" => Ok(Scheme::ShamirIda),
+            "shamir-ida-vss" => Ok(Scheme::ShamirIdaVss),
+            _ => Err(format!("unknown scheme '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Cipher::None => "none",
+            Cipher::Aes128Cbc => "aes128-cbc",
+            Cipher::Aes256Cbc => "aes256-cbc",
+            Cipher::Aes128Cfb => "aes128-cfb",
+            Cipher::Aes256Cfb => "aes256-cfb",
+            Cipher::Aes256Gcm => "aes256-gcm",
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+            Cipher::Aes128Ctr => "aes128-ctr",
+            Cipher::Aes256Ctr => "aes256-ctr",
+        })
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Cipher::None),
+            "aes128-cbc" => Ok(Cipher::Aes128Cbc),
+            "aes256-cbc" => Ok(Cipher::Aes256Cbc),
+            "aes128-cfb" => Ok(Cipher::Aes128Cfb),
+            "aes256-cfb" => Ok(Cipher::Aes256Cfb),
+            "aes256-gcm" => Ok(Cipher::Aes256Gcm),
+            "chacha20-poly1305" => Ok(Cipher::ChaCha20Poly1305),
+            "aes128-ctr" => Ok(Cipher::Aes128Ctr),
+            "aes256-ctr" => Ok(Cipher::Aes256Ctr),
+            _ => Err(format!("unknown cipher '{}'", s)),
+        }
+    }
+}
+
+// GF(2^8) with a polynomial other than the default is named with the polynomial, as gf256-11b.
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Gf2p8(0x11d) => f.write_str("gf256"),
+            Field::Gf2p8(polynomial) => write!(f, "gf256-{:x}", polynomial),
+            Field::Gf65536 => f.write_str("gf65536"),
+            Field::Secp256k1 => f.write_str("secp256k1"),
+            Field::P25519 => f.write_str("p25519"),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let polynomial = s
+            .strip_prefix("gf256-")
+            .and_then(|polynomial| u16::from_str_radix(polynomial, 16).ok());
+        match (s, polynomial) {
+            ("gf256", _) => Ok(Field::GF256),
+            (_, Some(polynomial)) if GF2P8_POLYNOMIALS.contains(&polynomial) => {
+                Ok(Field::Gf2p8(polynomial))
+            }
+            ("gf65536", _) => Ok(Field::Gf65536),
+            ("secp256k1", _) => Ok(Field::Secp256k1),
+            ("p25519", _) => Ok(Field::P25519),
+            _ => Err(format!("unknown field '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Padding::None => "none",
+            Padding::Iso7816 => "iso7816",
+            Padding::Pkcs7 => "pkcs7",
+        })
+    }
+}
+
+impl FromStr for Padding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Padding::None),
+            "iso7816" => Ok(Padding::Iso7816),
+            "pkcs7" => Ok(Padding::Pkcs7),
+            _ => Err(format!("unknown padding '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes128Cfb, Padding::Pkcs7, 3, 7);
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), HEADER_SIZE);
+        assert_eq!(ShareHeader::read(&mut &buf[..]).unwrap(), header);
+
+        let header = ShareHeader {
+            field: Field::Gf65536,
+            ..ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 200, 40000)
+        };
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        assert_eq!(ShareHeader::read(&mut &buf[..]).unwrap(), header);
+
+        let header = ShareHeader {
+            field: Field::Gf2p8(0x11b),
+            ..ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 3, 7)
+        };
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        assert_eq!(ShareHeader::read(&mut &buf[..]).unwrap(), header);
+        assert_eq!("gf256-11b".parse::<Field>(), Ok(header.field));
+        assert_eq!(header.field.to_string(), "gf256-11b");
+    }
+
+    #[test]
+    fn unknown_polynomial() {
+        let mut buf = Vec::new();
+        ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 2, 1)
+            .write(&mut buf)
+            .unwrap();
+        // 0x11f is not irreducible, and 0x12b is but is not supported.
+        for polynomial in [0x11fu16, 0x12b] {
+            buf[9..11].copy_from_slice(&polynomial.to_be_bytes());
+            assert!(matches!(
+                ShareHeader::read(&mut &buf[..]),
+                Err(SplitSecretError::InvalidHeader(_))
+            ));
+        }
+        assert!("gf256-12b".parse::<Field>().is_err());
+    }
+
+    #[test]
+    fn same_split() {
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 1);
+        assert!(header.same_split(&ShareHeader {
+            x: 2,
+            ..header.clone()
+        }));
+        assert!(!header.same_split(&ShareHeader {
+            k: 3,
+            ..header.clone()
+        }));
+        let other = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 2);
+        assert!(!header.same_split(&other));
+    }
+
+    #[test]
+    fn new_split() {
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 1);
+        let renewed = header.new_split();
+        assert!(!header.same_split(&renewed));
+        assert_eq!(
+            header,
+            ShareHeader {
+                set_id: header.set_id,
+                ..renewed
+            }
+        );
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut buf = Vec::new();
+        ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 2, 1)
+            .write(&mut buf)
+            .unwrap();
+        buf[0] = b'x';
+        assert!(matches!(
+            ShareHeader::read(&mut &buf[..]),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+    }
+}
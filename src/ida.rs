@@ -1,20 +1,40 @@
-use std::cmp;
-use std::io::{Read, Write};
+use std::collections::BTreeSet;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
 
+use crate::error::{Result, SplitSecretError};
+use crate::field::{decode_secret, encode_elements, indices, GaloisField, Gf256};
 use crate::padding_streaming::{Op, PaddedReader, PaddedWriter};
-use crate::partitioner::{InputPartition, OutputPartition, Partitioner};
-use crate::poly::lagrange_eval;
+use crate::partitioner::{
+    check_indices, decode_shares, read_shares, repair_elementwise, InputPartition, JoinReport,
+    OutputPartition, Partitioner,
+};
+use crate::pipeline::{chunk_size, default_threads, read_chunk, run_pipeline, Pool};
+use crate::poly::{berlekamp_welch, lagrange_eval, poly_eval};
 
 use block_padding::RawPadding;
-use galois_2p8::{Field, IrreducablePolynomial, PrimitivePolynomialField};
 
-pub struct Ida<P>
+/// Rabin's information dispersal algorithm over a [`GaloisField`], GF(2^8) unless chosen
+/// otherwise.
+///
+/// The input is padded with `P` to a multiple of `k` elements, and every share is `1/k` the size
+/// of the padded input. `P` must support blocks of that many bytes; PKCS#7 only goes up to 255.
+/// Shares are not confidential; see [`crate::ShamirIda`] for that.
+///
+/// A join needs any `k` shares. Given `m` more, it corrects up to `m / 2` shares with wrong
+/// contents at any one position, and reports which shares they were. A lost share can be
+/// regenerated from `k` others.
+///
+/// Splits and joins of large inputs are spread over one thread per core, or as many as
+/// [`Ida::with_threads`] says.
+pub struct Ida<P, F = Gf256>
 where
     P: RawPadding,
+    F: GaloisField,
 {
     k: u8,
-    base: IrreducablePolynomial,
+    field: F,
+    pool: Pool,
     _p: PhantomData<P>,
 }
 
@@ -22,133 +42,515 @@ impl<P> Ida<P>
 where
     P: RawPadding,
 {
-    pub fn new(k: u8) -> Self {
-        assert!(k > 1);
-        return Ida {
+    /// Creates a scheme over GF(2^8) where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        Ida::with_field(k)
+    }
+}
+
+impl<P, F> Ida<P, F>
+where
+    P: RawPadding,
+    F: GaloisField,
+{
+    /// Creates a scheme over `F` where any `k` shares are required to reconstruct the input.
+    pub fn with_field(k: u8) -> Result<Self> {
+        if k < 2 {
+            return Err(SplitSecretError::InvalidThreshold(k));
+        }
+        if !F::DENSE {
+            // Padded blocks could be numbers outside the field.
+            return Err(SplitSecretError::Unsupported(
+                "dispersal over a field that is not dense",
+            ));
+        }
+        Ok(Ida {
             k: k,
-            base: IrreducablePolynomial::Poly84320,
+            field: F::new(),
+            pool: Pool::new(default_threads()),
             _p: PhantomData,
-        };
+        })
+    }
+
+    /// Splits and joins on `threads` threads; 1 keeps all the work on the calling thread. The
+    /// output is the same either way.
+    ///
+    /// The threads are started by the first split or join that needs them and kept until the
+    /// scheme is dropped.
+    pub fn with_threads(self, threads: usize) -> Self {
+        Ida {
+            pool: Pool::new(threads),
+            ..self
+        }
+    }
+
+    // The input is dispersed in blocks of k elements.
+    fn block_size(&self) -> usize {
+        usize::from(self.k) * F::ELEMENT_SIZE
     }
 }
 
-const BUF_SIZE: usize = 1024;
+// Splits blocks of k elements into k columns of len elements, column j holding element j of every
+// block, so that arithmetic on a column covers many blocks at once.
+fn transpose_blocks<E: Copy>(blocks: &[E], columns: &mut [Vec<E>], len: usize) {
+    let k = columns.len();
+    for (j, column) in columns.iter_mut().enumerate() {
+        for (value, element) in column[..len].iter_mut().zip(blocks[j..].iter().step_by(k)) {
+            *value = *element;
+        }
+    }
+}
 
-impl<P> Partitioner for Ida<P>
+// The inverse of transpose_blocks.
+fn interleave_blocks<E: Copy>(columns: &[Vec<E>], blocks: &mut [E], len: usize) {
+    let k = columns.len();
+    for (j, column) in columns.iter().enumerate() {
+        for (value, element) in column[..len].iter().zip(blocks[j..].iter_mut().step_by(k)) {
+            *element = *value;
+        }
+    }
+}
+
+// Disperses a chunk of whole blocks of k elements of the padded input, one share per row of
+// lagrange.
+fn split_chunk<F: GaloisField>(
+    field: &F,
+    k: usize,
+    chunk: &[u8],
+    lagrange: &[Vec<F::Element>],
+) -> Result<Vec<Vec<u8>>> {
+    let block_size = k * F::ELEMENT_SIZE;
+    if !chunk.len().is_multiple_of(block_size) {
+        return Err(SplitSecretError::Padding);
+    }
+    let mut data = vec![F::zero(); chunk.len() / F::ELEMENT_SIZE];
+    let len = decode_secret::<F>(chunk, &mut data)?;
+    // Every share is the same combination of the k columns of the blocks.
+    let share_len = len / k;
+    let mut columns = vec![vec![F::zero(); share_len]; k];
+    transpose_blocks(&data, &mut columns, share_len);
+    let mut share = vec![F::zero(); share_len];
+    let mut bytes = Vec::with_capacity(lagrange.len());
+    for output_lagrange in lagrange.iter() {
+        share.fill(F::zero());
+        for (column, scale) in columns.iter().zip(output_lagrange.iter()) {
+            field.add_scaled(&mut share, column, *scale);
+        }
+        let mut share_bytes = vec![0u8; chunk.len() / k];
+        encode_elements::<F>(&share, &mut share_bytes);
+        bytes.push(share_bytes);
+    }
+    Ok(bytes)
+}
+
+impl<P, F> Ida<P, F>
 where
     P: RawPadding,
+    F: GaloisField,
 {
-    fn split<R: Read, W: Write>(&self, input: R, outputs: &mut [OutputPartition<W>]) {
-        let n = outputs.len() as u8;
-        assert!(n >= self.k);
-        // TODO: check that all the indicies in the outputs are unique
-
+    fn block_decoder(&self, input_xs: Vec<u16>) -> Result<BlockDecoder<F>> {
         let k_usize: usize = self.k.into();
-        let mut input = PaddedReader::<P, _>::new(k_usize, input, Op::Pad);
-        let target_read_size = BUF_SIZE - BUF_SIZE % k_usize;
+        check_indices(self.k, input_xs.iter().copied())?;
 
-        let field = PrimitivePolynomialField::new_might_panic(self.base);
+        // The first k inputs are used to decode, and the rest to check them.
+        let xs = indices::<F>(input_xs.iter().copied())?;
+        let data_xs = indices::<F>(0..u16::from(self.k))?;
+        let lagrange = lagrange_eval(&self.field, &xs[..k_usize], &data_xs[..]);
+        let check = lagrange_eval(&self.field, &xs[..k_usize], &xs[k_usize..]);
+        Ok(BlockDecoder {
+            k: k_usize,
+            field: self.field.clone(),
+            input_xs,
+            xs,
+            data_xs,
+            lagrange,
+            check,
+        })
+    }
 
-        let mut read_buf: [u8; BUF_SIZE] = [0u8; BUF_SIZE];
-        let mut write_bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; n.into()];
+    // For each output of a split, its coefficients for the k columns of the blocks.
+    fn split_lagrange(&self, output_xs: &[F::Element]) -> Result<Vec<Vec<F::Element>>> {
+        let data_xs = indices::<F>(0..u16::from(self.k))?;
+        Ok(lagrange_eval(&self.field, &data_xs[..], output_xs))
+    }
 
-        let data_xs: Vec<u8> = (0u8..self.k).collect();
-        let output_xs: Vec<u8> = outputs.iter().map(|output| output.x).collect();
-        let lagrange = lagrange_eval(&field, &data_xs[..], &output_xs[..]);
+    // Each input is read in chunks this large, which decode to k times as much.
+    fn join_chunk_size(&self, inputs: usize) -> usize {
+        chunk_size(inputs * usize::from(self.k), F::ELEMENT_SIZE)
+    }
 
-        loop {
-            let mut read_size = 0;
-            loop {
-                match input.read(&mut read_buf[read_size..target_read_size]) {
-                    Err(_) | Ok(0) => break,
-                    Ok(block_read_size) => {
-                        read_size += block_read_size;
-                    }
-                }
-            }
-            if read_size % k_usize != 0 {
-                panic!("input was not correctly padded");
+    /// Returns a reader of the original stream joined from `inputs`, for joining into something
+    /// other than a writer. Errors found in the inputs are corrected as in [`Partitioner::join`],
+    /// but not reported.
+    pub(crate) fn join_reader<'a, R: Read>(
+        &self,
+        inputs: &'a mut [InputPartition<R>],
+    ) -> Result<PaddedReader<P, Decoder<'a, R, F>>> {
+        let decoder = Decoder {
+            decoder: self.block_decoder(inputs.iter().map(|input| input.x).collect())?,
+            chunk_size: self.join_chunk_size(inputs.len()),
+            inputs,
+            decoded: Vec::new(),
+            position: 0,
+        };
+        Ok(PaddedReader::new(self.block_size(), decoder, Op::Unpad))
+    }
+}
+
+// Decodes chunks of the inputs of a join into chunks of the padded original.
+struct BlockDecoder<F: GaloisField> {
+    k: usize,
+    field: F,
+    input_xs: Vec<u16>,
+    xs: Vec<F::Element>,
+    data_xs: Vec<F::Element>,
+    // For each position in a block, its coefficients for the first k inputs.
+    lagrange: Vec<Vec<F::Element>>,
+    check: Vec<Vec<F::Element>>,
+}
+
+impl<F: GaloisField> BlockDecoder<F> {
+    // Returns the blocks in read_size bytes of every input, and the indices of the inputs that had
+    // errors in them.
+    fn decode(&self, bufs: &[Vec<u8>], read_size: usize) -> Result<(Vec<u8>, Vec<u16>)> {
+        let field = &self.field;
+        let k_usize = self.k;
+        let mut shares = vec![vec![F::zero(); read_size / F::ELEMENT_SIZE]; bufs.len()];
+        let len = decode_shares::<F, _>(&self.input_xs, bufs, read_size, &mut shares)?;
+
+        // Every column of the blocks is the same combination of the first k inputs throughout.
+        let (data_shares, surplus_shares) = shares.split_at(k_usize);
+        let mut columns = vec![vec![F::zero(); len]; k_usize];
+        for (column, coefficients) in columns.iter_mut().zip(self.lagrange.iter()) {
+            for (share, scale) in data_shares.iter().zip(coefficients.iter()) {
+                field.add_scaled(column, share, *scale);
             }
-            if read_size == 0 {
-                break;
+        }
+        let mut decoded = vec![F::zero(); len * k_usize];
+        interleave_blocks(&columns, &mut decoded, len);
+
+        let mut corrupted = Vec::new();
+        let mut predicted = vec![F::zero(); len];
+        let mut ys = vec![F::zero(); shares.len()];
+        for (surplus_share, check_coefficients) in surplus_shares.iter().zip(self.check.iter()) {
+            predicted.fill(F::zero());
+            for (share, scale) in data_shares.iter().zip(check_coefficients.iter()) {
+                field.add_scaled(&mut predicted, share, *scale);
             }
-            for (i, slice) in read_buf[0..read_size].chunks(k_usize).enumerate() {
-                for (write_buf, output_lagrange) in write_bufs.iter_mut().zip(lagrange.iter()) {
-                    write_buf[i] = 0u8;
-                    for (y, lagrange_coefficient) in slice.iter().zip(output_lagrange.iter()) {
-                        write_buf[i] =
-                            field.add(write_buf[i], field.mult(*y, *lagrange_coefficient));
+            for i in 0..len {
+                if predicted[i] == surplus_share[i] {
+                    continue;
+                }
+                // Some input disagrees with the first k at this position, so decode it with the
+                // error-correcting decoder and note which inputs were off the polynomial it found.
+                for (y, share) in ys.iter_mut().zip(shares.iter()) {
+                    *y = share[i];
+                }
+                let coefficients = berlekamp_welch(field, &self.xs, &ys, k_usize)
+                    .ok_or(SplitSecretError::InconsistentShares)?;
+                for (input_x, (x, y)) in self.input_xs.iter().zip(self.xs.iter().zip(ys.iter())) {
+                    if poly_eval(field, &coefficients, *x) != *y {
+                        corrupted.push(*input_x);
                     }
                 }
-            }
-            let write_size = read_size / k_usize;
-            for (write_buf, output) in write_bufs.iter().zip(outputs.iter_mut()) {
-                output
-                    .writer
-                    .write_all(&write_buf[0..write_size])
-                    .expect("write failed");
+                let block = &mut decoded[i * k_usize..(i + 1) * k_usize];
+                for (value, x) in block.iter_mut().zip(self.data_xs.iter()) {
+                    *value = poly_eval(field, &coefficients, *x);
+                }
             }
         }
+
+        let mut bytes = vec![0u8; read_size * k_usize];
+        encode_elements::<F>(&decoded, &mut bytes);
+        Ok((bytes, corrupted))
     }
+}
 
-    fn join<R: Read, W: Write>(&self, inputs: &mut [InputPartition<R>], output: W) {
-        let k_usize: usize = self.k.into();
-        assert!(inputs.len() == k_usize);
-        let mut output = PaddedWriter::<P, _>::new(k_usize, output, Op::Unpad);
+// Reads the inputs of a join into chunks for a BlockDecoder.
+fn read_chunks<R: Read>(
+    inputs: &mut [InputPartition<R>],
+    chunk_size: usize,
+) -> Result<Option<(Vec<Vec<u8>>, usize)>> {
+    let mut bufs = vec![vec![0u8; chunk_size]; inputs.len()];
+    match read_shares(inputs, &mut bufs)? {
+        0 => Ok(None),
+        read_size => Ok(Some((bufs, read_size))),
+    }
+}
 
-        let field = PrimitivePolynomialField::new_might_panic(self.base);
+// Decodes the padded original from the inputs of a join, one chunk of each input at a time.
+pub(crate) struct Decoder<'a, R: Read, F: GaloisField> {
+    decoder: BlockDecoder<F>,
+    inputs: &'a mut [InputPartition<R>],
+    chunk_size: usize,
+    decoded: Vec<u8>,
+    // The start of the part of decoded that has not been read yet.
+    position: usize,
+}
 
-        let mut read_bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; k_usize];
-        let mut write_buf: Vec<u8> = vec![0u8; BUF_SIZE * k_usize];
+impl<'a, R: Read, F: GaloisField> Read for Decoder<'a, R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position == self.decoded.len() {
+            self.position = 0;
+            self.decoded = match read_chunks(self.inputs, self.chunk_size)? {
+                Some((bufs, read_size)) => self.decoder.decode(&bufs, read_size)?.0,
+                None => Vec::new(),
+            };
+        }
+        let read_size = (self.decoded.len() - self.position).min(buf.len());
+        let read_to = self.position + read_size;
+        buf[..read_size].copy_from_slice(&self.decoded[self.position..read_to]);
+        self.position = read_to;
+        Ok(read_size)
+    }
+}
 
-        let input_xs: Vec<u8> = inputs.iter().map(|input| input.x).collect();
-        let data_xs: Vec<u8> = (0u8..self.k).collect();
-        let lagrange_t = lagrange_eval(&field, &input_xs[..], &data_xs[..]);
-        let lagrange: Vec<Vec<u8>> = (0..k_usize)
-            .map(|i| lagrange_t.iter().map(|l| l[i]).collect())
-            .collect();
+impl<P, F> Partitioner for Ida<P, F>
+where
+    P: RawPadding,
+    F: GaloisField,
+{
+    fn split<R: Read, W: Write>(&self, input: R, outputs: &mut [OutputPartition<W>]) -> Result<()> {
+        check_indices(self.k, outputs.iter().map(|output| output.x))?;
+        let output_xs = indices::<F>(outputs.iter().map(|output| output.x))?;
 
-        loop {
-            let mut read_size = BUF_SIZE;
-            for (input, read_buf) in inputs.iter_mut().zip(read_bufs.iter_mut()) {
-                match input.reader.read(read_buf) {
-                    Err(_) => {
-                        read_size = 0;
-                        break;
-                    }
-                    Ok(n) => read_size = cmp::min(read_size, n),
+        let block_size = self.block_size();
+        let mut input = PaddedReader::<P, _>::new(block_size, input, Op::Pad);
+        let chunk_size = chunk_size(outputs.len(), block_size);
+        let lagrange = self.split_lagrange(&output_xs)?;
+        let field = self.field.clone();
+        let k_usize = usize::from(self.k);
+
+        run_pipeline(
+            &self.pool,
+            || read_chunk(&mut input, chunk_size),
+            move |chunk| split_chunk(&field, k_usize, &chunk, &lagrange),
+            |shares| {
+                for (share, output) in shares.iter().zip(outputs.iter_mut()) {
+                    output.writer.write_all(share)?;
                 }
+                Ok(())
+            },
+        )
+    }
+
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: W,
+    ) -> Result<JoinReport> {
+        let decoder = self.block_decoder(inputs.iter().map(|input| input.x).collect())?;
+        let chunk_size = self.join_chunk_size(inputs.len());
+        let mut output = PaddedWriter::<P, _>::new(self.block_size(), output, Op::Unpad);
+
+        let mut corrupted = BTreeSet::new();
+        run_pipeline(
+            &self.pool,
+            || read_chunks(inputs, chunk_size),
+            move |(bufs, read_size)| decoder.decode(&bufs, read_size),
+            |(blocks, chunk_corrupted)| {
+                output.write_all(&blocks)?;
+                corrupted.extend(chunk_corrupted);
+                Ok(())
+            },
+        )?;
+        output.flush()?;
+        Ok(JoinReport {
+            corrupted: corrupted.into_iter().collect(),
+        })
+    }
+
+    fn join_range<R: Read + Seek, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        offset: u64,
+        len: u64,
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let decoder = self.block_decoder(inputs.iter().map(|input| input.x).collect())?;
+        let block_size = self.block_size() as u64;
+        let element_size = F::ELEMENT_SIZE as u64;
+
+        // Block i of the padded original is decoded from element i of every share.
+        let mut starts = Vec::with_capacity(inputs.len());
+        let mut share_sizes = Vec::with_capacity(inputs.len());
+        for input in inputs.iter_mut() {
+            let start = input.reader.stream_position()?;
+            share_sizes.push(input.reader.seek(SeekFrom::End(0))? - start);
+            starts.push(start);
+        }
+        let share_size = share_sizes.iter().copied().max().unwrap_or(0);
+        if let Some((input, _)) = inputs
+            .iter()
+            .zip(share_sizes.iter())
+            .find(|(_, size)| **size != share_size || *size % element_size != 0)
+        {
+            return Err(SplitSecretError::TruncatedShare { x: input.x });
+        }
+        let blocks = share_size / element_size;
+        let end = offset.saturating_add(len);
+        let first_block = offset / block_size;
+        let last_block = end.div_ceil(block_size).min(blocks);
+        if first_block >= last_block {
+            return Ok(JoinReport::default());
+        }
+        for (input, start) in inputs.iter_mut().zip(starts.iter()) {
+            input
+                .reader
+                .seek(SeekFrom::Start(start + first_block * element_size))?;
+        }
+
+        let chunk_size = self.join_chunk_size(inputs.len()) as u64;
+        let mut corrupted = BTreeSet::new();
+        let mut position = first_block * block_size;
+        let mut remaining = (last_block - first_block) * element_size;
+        while remaining > 0 {
+            let read_size = remaining.min(chunk_size) as usize;
+            let mut bufs = vec![vec![0u8; read_size]; inputs.len()];
+            if read_shares(inputs, &mut bufs)? != read_size {
+                return Err(SplitSecretError::TruncatedShare { x: inputs[0].x });
             }
-            if read_size == 0 {
-                break;
+            remaining -= read_size as u64;
+            let (mut decoded, chunk_corrupted) = decoder.decode(&bufs, read_size)?;
+            corrupted.extend(chunk_corrupted);
+            if position + decoded.len() as u64 == blocks * block_size {
+                // The last block holds the padding.
+                let last = decoded.len() - self.block_size();
+                let unpadded = P::raw_unpad(&decoded[last..])
+                    .map_err(|_| SplitSecretError::Padding)?
+                    .len();
+                decoded.truncate(last + unpadded);
             }
+            let from = offset.saturating_sub(position).min(decoded.len() as u64) as usize;
+            let to = (end - position).min(decoded.len() as u64) as usize;
+            output.write_all(&decoded[from..to])?;
+            position += decoded.len() as u64;
+        }
+        output.flush()?;
+        Ok(JoinReport {
+            corrupted: corrupted.into_iter().collect(),
+        })
+    }
 
-            write_buf.fill(0u8);
-            for (i, slice) in write_buf.chunks_mut(k_usize).take(read_size).enumerate() {
-                for (read_buf, input_lagrange) in read_bufs.iter().zip(lagrange.iter()) {
-                    field.add_scaled_multiword(slice, input_lagrange, read_buf[i]);
-                }
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: OutputPartition<W>,
+    ) -> Result<()> {
+        repair_elementwise(&self.field, self.k, inputs, output)
+    }
+}
+
+#[cfg(feature = "async")]
+mod nonblocking {
+    use super::*;
+    use crate::async_partitioner::{AsyncPartitioner, Blocks, JoinDecoder, SplitEncoder, Units};
+
+    struct Encoder<'a, P: RawPadding, F: GaloisField> {
+        ida: &'a Ida<P, F>,
+        lagrange: Vec<Vec<F::Element>>,
+        blocks: Blocks<P>,
+    }
+
+    impl<'a, P: RawPadding, F: GaloisField> Encoder<'a, P, F> {
+        fn encode(&self, chunk: &[u8], outputs: &mut [Vec<u8>]) -> Result<()> {
+            if chunk.is_empty() {
+                return Ok(());
             }
+            let ida = self.ida;
+            let shares = split_chunk(&ida.field, usize::from(ida.k), chunk, &self.lagrange)?;
+            for (share, output) in shares.iter().zip(outputs.iter_mut()) {
+                output.extend_from_slice(share);
+            }
+            Ok(())
+        }
+    }
 
-            output
-                .write_all(&write_buf[0..read_size * k_usize])
-                .unwrap();
+    impl<'a, P: RawPadding, F: GaloisField> SplitEncoder for Encoder<'a, P, F> {
+        fn update(&mut self, input: &[u8], outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.update(input);
+            self.encode(&chunk, outputs)
+        }
+
+        fn finish(&mut self, outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.finish();
+            self.encode(&chunk, outputs)
+        }
+    }
+
+    struct Decoder<P: RawPadding, F: GaloisField> {
+        decoder: BlockDecoder<F>,
+        units: Units,
+        output: PaddedWriter<P, Vec<u8>>,
+        corrupted: BTreeSet<u16>,
+    }
+
+    impl<P: RawPadding, F: GaloisField> JoinDecoder for Decoder<P, F> {
+        fn update(&mut self, inputs: &[&[u8]], output: &mut Vec<u8>) -> Result<()> {
+            let bufs = self.units.update(inputs);
+            if bufs[0].is_empty() {
+                return Ok(());
+            }
+            let (blocks, corrupted) = self.decoder.decode(&bufs, bufs[0].len())?;
+            self.corrupted.extend(corrupted);
+            self.output.write_all(&blocks)?;
+            output.append(self.output.get_mut());
+            Ok(())
+        }
+
+        fn finish(&mut self, output: &mut Vec<u8>) -> Result<JoinReport> {
+            let input_xs = &self.decoder.input_xs;
+            if self.units.truncated() {
+                return Err(SplitSecretError::TruncatedShare { x: input_xs[0] });
+            }
+            self.output.flush()?;
+            output.append(self.output.get_mut());
+            Ok(JoinReport {
+                corrupted: self.corrupted.iter().copied().collect(),
+            })
+        }
+    }
+
+    impl<P, F> AsyncPartitioner for Ida<P, F>
+    where
+        P: RawPadding + Send + Sync,
+        F: GaloisField,
+    {
+        fn split_encoder(&self, xs: &[u16]) -> Result<Box<dyn SplitEncoder + Send + '_>> {
+            check_indices(self.k, xs.iter().copied())?;
+            let output_xs = indices::<F>(xs.iter().copied())?;
+            Ok(Box::new(Encoder {
+                ida: self,
+                lagrange: self.split_lagrange(&output_xs)?,
+                blocks: Blocks::new(self.block_size(), true),
+            }))
+        }
+
+        fn join_decoder(&self, xs: &[u16]) -> Result<Box<dyn JoinDecoder + Send + '_>> {
+            Ok(Box::new(Decoder::<P, F> {
+                decoder: self.block_decoder(xs.to_vec())?,
+                units: Units::new(F::ELEMENT_SIZE, xs.len()),
+                output: PaddedWriter::new(self.block_size(), Vec::new(), Op::Unpad),
+                corrupted: BTreeSet::new(),
+            }))
         }
-        output.flush().unwrap();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::partitioner::test_join;
+    use crate::field::Gf65536;
+    use crate::partitioner::{test_join, InMemoryPartition};
+
+    use std::io::Cursor;
 
     use block_padding::{Iso7816, Pkcs7};
 
     fn base_two_of_three<P: RawPadding>() {
         let plaintext: Vec<u8> = "hello worlds".as_bytes().into();
-        let ida = Ida::<P>::new(2);
-        let mut partitions = ida.split_in_memory(&plaintext, 3);
+        let ida = Ida::<P>::new(2).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 3).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
             assert!(plaintext.len() > partition.value.len());
@@ -169,12 +571,175 @@ mod tests {
     #[test]
     fn five_of_ten() {
         let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
-        let ida = Ida::<Iso7816>::new(5);
-        let mut partitions = ida.split_in_memory(&plaintext, 10);
+        let ida = Ida::<Iso7816>::new(5).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 10).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
             assert!(plaintext.len() > partition.value.len());
         }
         test_join(&ida, &mut partitions[..], 5, &plaintext);
     }
+
+    #[test]
+    fn empty() {
+        let ida = Ida::<Iso7816>::new(2).unwrap();
+        let mut partitions = ida.split_in_memory(&[], 3).unwrap();
+        assert!(partitions
+            .iter()
+            .all(|partition| partition.value.is_empty()));
+        test_join(&ida, &mut partitions[..], 2, &Vec::new());
+    }
+
+    #[test]
+    fn not_enough_shares() {
+        let plaintext: Vec<u8> = "hello worlds".as_bytes().into();
+        let ida = Ida::<Iso7816>::new(3).unwrap();
+        assert!(matches!(
+            ida.split_in_memory(&plaintext, 2),
+            Err(SplitSecretError::ShareCount { k: 3, n: 2 })
+        ));
+        let mut partitions = ida.split_in_memory(&plaintext, 3).unwrap();
+        let (first, rest) = partitions.split_at_mut(1);
+        assert!(matches!(
+            ida.join_in_memory(&mut [&mut first[0], &mut rest[0]]),
+            Err(SplitSecretError::ShareCount { k: 3, n: 2 })
+        ));
+    }
+
+    fn join_all(
+        ida: &Ida<Iso7816>,
+        partitions: &[InMemoryPartition],
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        let mut inputs: Vec<_> = partitions
+            .iter()
+            .map(|partition| InputPartition {
+                x: partition.x,
+                reader: &partition.value[..],
+            })
+            .collect();
+        let mut output = Vec::new();
+        let report = ida.join(&mut inputs, &mut output)?;
+        Ok((output, report))
+    }
+
+    #[test]
+    fn threads() {
+        // Enough input for several chunks, spread over the threads.
+        let plaintext: Vec<u8> = (0..1_000_000u32)
+            .map(|i| (i * 31 + i / 997) as u8)
+            .collect();
+        let sequential = Ida::<Iso7816>::new(3).unwrap().with_threads(1);
+        let parallel = Ida::<Iso7816>::new(3).unwrap().with_threads(4);
+        let mut partitions = sequential.split_in_memory(&plaintext, 5).unwrap();
+        for (partition, parallel_partition) in partitions
+            .iter()
+            .zip(parallel.split_in_memory(&plaintext, 5).unwrap())
+        {
+            assert_eq!(partition.value, parallel_partition.value);
+        }
+
+        partitions[1].value[300_000] ^= 1;
+        for ida in [&sequential, &parallel] {
+            let (output, report) = join_all(ida, &partitions).unwrap();
+            assert_eq!(output, plaintext);
+            assert_eq!(report.corrupted, [2]);
+        }
+    }
+
+    #[test]
+    fn corrects_errors() {
+        let plaintext: Vec<u8> = (0..5000).map(|i| (i * 7) as u8).collect();
+        let ida = Ida::<Iso7816>::new(3).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 7).unwrap();
+
+        let (output, report) = join_all(&ida, &partitions).unwrap();
+        assert_eq!(plaintext, output);
+        assert!(report.corrupted.is_empty());
+
+        // Four extra shares correct two wrong shares at any position.
+        partitions[0].value[10] ^= 0xff;
+        partitions[4].value[10] ^= 0x01;
+        partitions[4].value[1000] ^= 0x10;
+        partitions[6].value[1500] = 0;
+        let (output, report) = join_all(&ida, &partitions).unwrap();
+        assert_eq!(plaintext, output);
+        assert_eq!(report.corrupted, [1, 5, 7]);
+    }
+
+    #[test]
+    fn detects_errors() {
+        let plaintext: Vec<u8> = "hello worlds".as_bytes().into();
+        let ida = Ida::<Iso7816>::new(3).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 4).unwrap();
+        // A single extra share is only enough to notice an error.
+        partitions[2].value[0] ^= 0x01;
+        assert!(matches!(
+            join_all(&ida, &partitions),
+            Err(SplitSecretError::InconsistentShares)
+        ));
+    }
+
+    #[test]
+    fn gf65536() {
+        let plaintext: Vec<u8> = (0..5001).map(|i| (i * 7) as u8).collect();
+        let ida = Ida::<Iso7816, Gf65536>::with_field(3).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 400).unwrap();
+        partitions.drain(..350);
+        let mut inputs: Vec<_> = partitions.iter_mut().take(3).collect();
+        assert_eq!(ida.join_in_memory(&mut inputs).unwrap(), plaintext);
+
+        partitions[1].value[100] ^= 0x80;
+        let mut inputs: Vec<_> = partitions
+            .iter()
+            .take(6)
+            .map(|partition| InputPartition {
+                x: partition.x,
+                reader: &partition.value[..],
+            })
+            .collect();
+        let mut output = Vec::new();
+        let report = ida.join(&mut inputs, &mut output).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [352]);
+    }
+
+    #[test]
+    fn join_range() {
+        let plaintext: Vec<u8> = (0..5000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+        let ida = Ida::<Iso7816>::new(3).unwrap();
+        let mut partitions = ida.split_in_memory(&plaintext, 5).unwrap();
+        // Block 100, bytes 300 to 302 of the original.
+        partitions[3].value[100] ^= 1;
+        for (offset, len, corrupted) in [
+            (0u64, 5000u64, &[4u16][..]),
+            (0, 1, &[]),
+            (297, 3, &[]),
+            (301, 1, &[4]),
+            (1234, 100, &[]),
+            (4990, 100, &[]),
+            (4999, 1, &[]),
+            (5000, 10, &[]),
+            (3, 0, &[]),
+        ] {
+            // Every share follows something else, like the header of a share file.
+            let mut inputs: Vec<_> = partitions
+                .iter()
+                .map(|partition| {
+                    let mut reader = Cursor::new([&b"head"[..], &partition.value].concat());
+                    reader.set_position(4);
+                    InputPartition {
+                        x: partition.x,
+                        reader,
+                    }
+                })
+                .collect();
+            let mut output = Vec::new();
+            let report = ida
+                .join_range(&mut inputs, offset, len, &mut output)
+                .unwrap();
+            let end = (offset + len).min(5000) as usize;
+            assert_eq!(output, plaintext[(offset as usize).min(end)..end]);
+            assert_eq!(report.corrupted, corrupted);
+        }
+    }
 }
@@ -0,0 +1,115 @@
+//! Secret sharing and information dispersal over GF(2^8) and GF(2^16), and secret sharing of
+//! 32-byte numbers over prime fields.
+//!
+//! Every scheme implements [`Partitioner`], which splits a byte stream into a number of shares
+//! and joins a sufficient subset of those shares back into the original stream:
+//!
+//! - [`Shamir`] is Shamir's secret sharing; every share is as large as the input, and fewer than
+//!   `k` shares reveal nothing about it.
+//! - [`Ida`] is Rabin's information dispersal algorithm; every share is roughly `1/k` the size of
+//!   the input, but shares leak information about it.
+//! - [`ShamirIda`] encrypts the input with a random key, disperses the ciphertext with [`Ida`] and
+//!   shares the key with [`Shamir`].
+//! - [`ShamirIdaAead`] does the same with an authenticated cipher, so that tampering with the
+//!   dispersed ciphertext is detected during the join.
+//! - [`ShamirIdaCtr`] does the same with a cipher in counter mode, so that any byte range of the
+//!   original can be joined on its own.
+//! - [`Policy`] shares a secret among named participants according to a tree of AND, OR and
+//!   threshold gates, by composing [`Shamir`] splits.
+//! - [`Feldman`] is Feldman's verifiable secret sharing, which lets every holder check their share
+//!   against commitments published by the dealer. Both hybrid schemes can use it to share their
+//!   key.
+//!
+//! [`split_shares`] and [`join_shares`] additionally frame every share with a [`ShareHeader`]
+//! recording how it was produced, so that a join can pick the right partitioner by itself. They
+//! split the original in chunks that are each verified by a digest, so that a damaged share is
+//! identified instead of silently corrupting the output, and so that
+//! [`resume_split_weighted_shares`] and [`resume_join_shares`] can pick up an interrupted split or
+//! join where it stopped, and [`join_range_shares`] can join a byte range of the original without
+//! reading the rest. [`ArmorWriter`] turns a share file into text for email or tickets, and
+//! [`ShareReader`] reads armored and binary share files alike.
+//!
+//! [`split_mnemonic`] shares secrets of up to 64 bytes, such as wallet seeds, as lists of words
+//! with a checksum that can be written down by hand; see [`MnemonicShare`]. [`Slip39`] splits
+//! wallet master secrets into the SLIP-0039 mnemonic shares that hardware wallets use.
+//!
+//! With the `async` feature, [`Shamir`], [`Ida`] and [`ShamirIda`] also implement
+//! `AsyncPartitioner`, which splits into and joins from tokio's `AsyncRead` and `AsyncWrite`
+//! streams.
+//!
+//! ```
+//! use split_secret::block_padding::Iso7816;
+//! use split_secret::{Ida, Partitioner};
+//!
+//! # fn main() -> split_secret::Result<()> {
+//! let ida = Ida::<Iso7816>::new(2)?;
+//! let mut shares = ida.split_in_memory(b"hello world", 3)?;
+//! let (first, rest) = shares.split_at_mut(1);
+//! let joined = ida.join_in_memory(&mut [&mut first[0], &mut rest[1]])?;
+//! assert_eq!(joined, b"hello world");
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod aead_streaming;
+pub mod armor;
+#[cfg(feature = "async")]
+pub mod async_partitioner;
+pub mod block_mode_streaming;
+mod bundle;
+pub mod chunks;
+pub mod error;
+pub mod feldman;
+pub mod field;
+mod gf2p8_kernel;
+pub mod header;
+pub mod ida;
+pub mod mnemonic;
+pub mod padding_streaming;
+pub mod partitioner;
+mod pipeline;
+pub mod policy;
+mod poly;
+pub mod shamir;
+pub mod shamir_ida;
+pub mod shamir_ida_aead;
+pub mod shamir_ida_ctr;
+pub mod shares;
+pub mod slip39;
+pub mod stream_cipher_streaming;
+mod utils;
+
+pub use crate::aead_streaming::{AeadDecryptWriteStream, AeadEncryptReadStream};
+pub use crate::armor::{ArmorReader, ArmorWriter, ShareReader};
+#[cfg(feature = "async")]
+pub use crate::async_partitioner::{AsyncPartitioner, JoinDecoder, SplitEncoder};
+pub use crate::block_mode_streaming::{DecryptWriteStream, EncryptReadStream};
+pub use crate::error::{Result, SplitSecretError};
+pub use crate::feldman::{Commitments, Feldman};
+pub use crate::field::{
+    GaloisField, Gf256, Gf2p8, Gf65536, Prime, PrimeField, Secp256k1Order, P25519,
+};
+pub use crate::header::{Cipher, Field, Padding, Scheme, ShareHeader};
+pub use crate::ida::Ida;
+pub use crate::mnemonic::{join_mnemonic, split_mnemonic, MnemonicShare};
+pub use crate::padding_streaming::{Op, PaddedReader, PaddedWriter};
+pub use crate::partitioner::{
+    InMemoryPartition, InputPartition, JoinReport, OutputPartition, Partitioner,
+};
+pub use crate::policy::{Policy, PolicyShare};
+pub use crate::shamir::Shamir;
+pub use crate::shamir_ida::ShamirIda;
+pub use crate::shamir_ida_aead::ShamirIdaAead;
+pub use crate::shamir_ida_ctr::ShamirIdaCtr;
+pub use crate::shares::{
+    join_range_shares, join_shares, refresh_shares, repair_share, reshare_shares,
+    resume_join_shares, resume_split_weighted_shares, split_shares, split_weighted_shares,
+    verify_share, WeightedOutput,
+};
+pub use crate::slip39::{join_slip39, Slip39, Slip39Share};
+pub use crate::stream_cipher_streaming::{StreamCipherReadStream, StreamCipherWriteStream};
+
+// Re-exported so that callers can name paddings and ciphers from the same versions we build with.
+pub use aead;
+pub use block_padding;
+pub use cipher;
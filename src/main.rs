@@ -1,21 +1,19 @@
-mod block_mode_streaming;
-mod ida;
-mod padding_streaming;
-mod partitioner;
-mod poly;
-mod shamir;
-mod shamir_ida;
-mod utils;
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::process;
 
-use std::fs::File;
-use std::io::{Read, Write};
+use split_secret::slip39::WordList;
+use split_secret::{
+    join_mnemonic, join_range_shares, join_shares, join_slip39, refresh_shares, repair_share,
+    reshare_shares, resume_join_shares, resume_split_weighted_shares, split_mnemonic,
+    split_weighted_shares, verify_share, ArmorWriter, Cipher, Field, MnemonicShare,
+    OutputPartition, Padding, Policy, PolicyShare, Result, Scheme, ShareHeader, ShareReader,
+    Slip39, Slip39Share, SplitSecretError, WeightedOutput,
+};
 
-use crate::partitioner::{InputPartition, OutputPartition, Partitioner};
-
-use aes::Aes256;
-use block_padding::Iso7816;
-
-use clap::{Args, Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(
@@ -34,18 +32,76 @@ enum Commands {
     Split(SplitOpts),
     #[command(about = "Combine shares into the original file")]
     Join(JoinOpts),
+    #[command(about = "Re-randomize shares without changing the original")]
+    Refresh(RefreshOpts),
+    #[command(about = "Regenerate a lost share from the others")]
+    Repair(RepairOpts),
+    #[command(about = "Produce a new set of shares with a different threshold or share count")]
+    Reshare(ReshareOpts),
+    #[command(about = "Check a shamir-ida-vss share against the dealer's commitments")]
+    Verify(VerifyOpts),
+    #[command(about = "Split a file among named participants according to an access policy")]
+    SplitPolicy(SplitPolicyOpts),
+    #[command(about = "Combine policy shares into the original file")]
+    JoinPolicy(JoinOpts),
+    #[command(about = "Split a secret of up to 64 bytes into shares written as words")]
+    SplitMnemonic(SplitMnemonicOpts),
+    #[command(about = "Combine shares written as words into the original secret")]
+    JoinMnemonic(JoinMnemonicOpts),
+    #[command(about = "Split a wallet master secret into SLIP-0039 shares")]
+    SplitSlip39(SplitSlip39Opts),
+    #[command(about = "Recover a wallet master secret from SLIP-0039 shares")]
+    JoinSlip39(JoinSlip39Opts),
 }
 
 #[derive(Args)]
 struct SplitOpts {
     #[arg(short, help = "number of shares to generate")]
-    n: u8,
+    n: u16,
     #[arg(
         short,
         help = "number of shares required to reconstruct original (default: n)"
     )]
     k: Option<u8>,
-    #[arg(help = "input file")]
+    #[arg(
+        long,
+        default_value = "shamir-ida",
+        help = "scheme: shamir, ida, shamir-ida or shamir-ida-vss"
+    )]
+    scheme: Scheme,
+    #[arg(
+        long,
+        help = "cipher for shamir-ida and shamir-ida-vss: aes128-cbc, aes256-cbc, aes128-cfb, aes256-cfb, aes128-ctr, aes256-ctr, aes256-gcm or chacha20-poly1305 (default: aes256-gcm)"
+    )]
+    cipher: Option<Cipher>,
+    #[arg(
+        long,
+        help = "padding for ida, shamir-ida and shamir-ida-vss: iso7816 or pkcs7 (default: iso7816)"
+    )]
+    padding: Option<Padding>,
+    #[arg(
+        long,
+        default_value = "gf256",
+        help = "field: gf256 for up to 255 shares (gf256-11b with the AES polynomial), gf65536 for up to 65535 with shamir and ida, or secp256k1 (its group order) or p25519 (2^255 - 19) for 32-byte secrets with shamir"
+    )]
+    field: Field,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "comma-separated number of shares each of the n outputs counts as (default: 1 each); an output of weight w holds shares x to x + w - 1 and is named after x"
+    )]
+    weights: Vec<u8>,
+    #[arg(
+        long,
+        help = "continue an interrupted split with the same options, keeping the chunks already written"
+    )]
+    resume: bool,
+    #[arg(
+        long,
+        help = "write shares as text, with BEGIN and END lines around base64, for pasting into email or tickets"
+    )]
+    armor: bool,
+    #[arg(help = "input file, or - for stdin")]
     input: String,
     #[arg(
         short,
@@ -59,98 +115,663 @@ struct SplitOpts {
 struct JoinOpts {
     #[arg(required = true, help = "input share files")]
     inputs: Vec<String>,
-    #[arg(short, long, help = "output file for original")]
+    #[arg(short, long, help = "output file for original, or - for stdout")]
+    output: String,
+    #[arg(
+        long,
+        help = "continue an interrupted join, keeping the chunks already written"
+    )]
+    resume: bool,
+    #[arg(long, help = "join only the original from this byte on")]
+    offset: Option<u64>,
+    #[arg(long, help = "join only this many bytes of the original")]
+    length: Option<u64>,
+}
+
+#[derive(Args)]
+struct RefreshOpts {
+    #[arg(
+        required = true,
+        help = "share files to refresh; shares left out cannot be used with the new ones"
+    )]
+    inputs: Vec<String>,
+    #[arg(
+        short,
+        long,
+        help = "prefix for output files; output will be in [output].1, [output].2, etc."
+    )]
+    output: String,
+}
+
+#[derive(Args)]
+struct RepairOpts {
+    #[arg(short, help = "index of the share to regenerate")]
+    x: u16,
+    #[arg(required = true, help = "input share files")]
+    inputs: Vec<String>,
+    #[arg(short, long, help = "output file for the regenerated share")]
+    output: String,
+}
+
+#[derive(Args)]
+struct ReshareOpts {
+    #[arg(short, help = "number of new shares to generate")]
+    n: u16,
+    #[arg(
+        short,
+        help = "number of new shares required to reconstruct original (default: n)"
+    )]
+    k: Option<u8>,
+    #[arg(required = true, help = "input share files")]
+    inputs: Vec<String>,
+    #[arg(
+        short,
+        long,
+        help = "prefix for output files; output will be in [output].1, [output].2, etc."
+    )]
+    output: String,
+}
+
+#[derive(Args)]
+struct VerifyOpts {
+    #[arg(required = true, help = "share files to check")]
+    inputs: Vec<String>,
+    #[arg(long, help = "commitment fingerprint published by the dealer")]
+    fingerprint: Option<String>,
+}
+
+#[derive(Args)]
+struct SplitPolicyOpts {
+    #[arg(
+        long,
+        help = "access policy, e.g. \"and(2of(alice, bob, carol), or(dave, erin))\""
+    )]
+    policy: Policy,
+    #[arg(help = "input file")]
+    input: String,
+    #[arg(
+        short,
+        long,
+        help = "prefix for output files; output will be in [output].alice, [output].bob, etc."
+    )]
+    output: String,
+}
+
+#[derive(Args)]
+struct SplitMnemonicOpts {
+    #[arg(short, help = "number of shares to generate")]
+    n: u8,
+    #[arg(
+        short,
+        help = "number of shares required to reconstruct original (default: n)"
+    )]
+    k: Option<u8>,
+    #[arg(help = "input file holding the secret, or - for stdin")]
+    input: String,
+    #[arg(
+        short,
+        long,
+        help = "prefix for output files; output will be in [output].1, [output].2, etc. (default: print one share per line)"
+    )]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct JoinMnemonicOpts {
+    #[arg(help = "files holding one share each (default: read one share per line from stdin)")]
+    inputs: Vec<String>,
+    #[arg(short, long, help = "output file for original, or - for stdout")]
+    output: String,
+}
+
+#[derive(Args)]
+struct SplitSlip39Opts {
+    #[arg(
+        long,
+        default_value = "1",
+        help = "number of groups required to reconstruct original"
+    )]
+    group_threshold: u8,
+    #[arg(
+        long,
+        required = true,
+        value_delimiter = ',',
+        value_parser = parse_group,
+        help = "comma-separated groups, each as its member threshold and count, e.g. 1of1,2of3"
+    )]
+    groups: Vec<(u8, u8)>,
+    #[arg(long, help = "file holding the passphrase (default: no passphrase)")]
+    passphrase_file: Option<String>,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "passphrase encryption takes 10000 << e iterations"
+    )]
+    iteration_exponent: u8,
+    #[arg(
+        long,
+        help = "write shares without the extendable flag, for wallets that predate it"
+    )]
+    no_extendable: bool,
+    #[arg(help = "input file holding the master secret, or - for stdin")]
+    input: String,
+    #[arg(
+        short,
+        long,
+        help = "prefix for output files; output will be in [output].[group].[member] (default: print one share per line, groups separated by blank lines)"
+    )]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct JoinSlip39Opts {
+    #[arg(help = "files holding one share each (default: read one share per line from stdin)")]
+    inputs: Vec<String>,
+    #[arg(long, help = "file holding the passphrase (default: no passphrase)")]
+    passphrase_file: Option<String>,
+    #[arg(short, long, help = "output file for original, or - for stdout")]
     output: String,
 }
 
-struct ShareHeader {
-    k: u8, // number of shares needed to reconstruct original (polynomial is of degree k - 1)
-    x: u8, // index of this share
+// Parses a SLIP-0039 group such as 2of3.
+fn parse_group(s: &str) -> std::result::Result<(u8, u8), String> {
+    s.trim()
+        .split_once("of")
+        .and_then(|(k, n)| Some((k.trim().parse().ok()?, n.trim().parse().ok()?)))
+        .ok_or_else(|| format!("expected a group such as 2of3, got '{}'", s))
+}
+
+// The path that stands for stdin or stdout.
+const STDIO: &str = "-";
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The threshold when none is given is the number of shares, which has to fit in one.
+fn default_threshold(n: u16) -> u8 {
+    u8::try_from(n).unwrap_or_else(|_| {
+        Opts::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "-k is required with more than 255 shares",
+            )
+            .exit()
+    })
+}
+
+// Exits with a usage error for options that cannot be used together.
+fn conflict(message: &str) -> ! {
+    Opts::command()
+        .error(ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+// Attaches the path to I/O errors, since the OS error alone does not say which file failed.
+fn with_path<T>(path: &str, result: std::io::Result<T>) -> Result<T> {
+    result.map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path, e)).into())
 }
 
-fn write_share_header(writer: &mut impl Write, share_header: &ShareHeader) {
-    writer.write(&[share_header.k, share_header.x]).unwrap();
+// Opens a share file, which may be armored or binary.
+fn open_share(path: &str) -> Result<ShareReader<BufReader<File>>> {
+    ShareReader::new(BufReader::new(with_path(path, File::open(path))?))
 }
 
-fn read_share_header(reader: &mut impl Read) -> ShareHeader {
-    let mut buf = [0u8; 2];
-    reader.read(&mut buf).unwrap();
-    ShareHeader {
-        k: buf[0],
-        x: buf[1],
+// Opens an output file, keeping what is in it if an interrupted command is being resumed.
+fn open_output(path: &str, resume: bool) -> std::io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(!resume)
+        .open(path)
+}
+
+// Cuts a resumed output off where the command finished writing it.
+fn truncate(file: &mut File) -> Result<()> {
+    let end = file.stream_position()?;
+    file.set_len(end)?;
+    Ok(())
+}
+
+// Reads the input file or stdin.
+fn read_input(path: &str) -> Result<Vec<u8>> {
+    let mut input: Box<dyn Read> = match path {
+        STDIO => Box::new(io::stdin().lock()),
+        path => Box::new(with_path(path, File::open(path))?),
+    };
+    let mut contents = Vec::new();
+    input.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+// Reads shares written as words: a whole share per file, however its words are broken over
+// lines, or with no files, a share per line of stdin. Each is named for error messages.
+fn read_word_shares(inputs: &[String]) -> Result<Vec<(String, String)>> {
+    let mut texts = Vec::new();
+    for input in inputs.iter() {
+        let text = with_path(input, std::fs::read_to_string(input))?;
+        texts.push((input.clone(), text));
+    }
+    if inputs.is_empty() {
+        for (i, line) in io::stdin().lock().lines().enumerate() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                texts.push((format!("line {}", i + 1), line));
+            }
+        }
     }
+    Ok(texts)
 }
 
-fn main() {
-    let opts: Opts = Opts::parse();
+// Reads the SLIP-0039 passphrase, without the line break that ends the file.
+fn read_passphrase(passphrase_file: &Option<String>) -> Result<Vec<u8>> {
+    Ok(match passphrase_file {
+        Some(path) => {
+            let mut passphrase = with_path(path, std::fs::read(path))?;
+            while passphrase
+                .last()
+                .is_some_and(|c| *c == b'\n' || *c == b'\r')
+            {
+                passphrase.pop();
+            }
+            passphrase
+        }
+        None => Vec::new(),
+    })
+}
 
+// Writes the original to the output file or stdout.
+fn write_original(path: &str, original: &[u8]) -> Result<()> {
+    if path == STDIO {
+        return Ok(io::stdout().lock().write_all(original)?);
+    }
+    with_path(
+        path,
+        File::create(path).and_then(|mut file| file.write_all(original)),
+    )
+}
+
+fn run(opts: Opts) -> Result<()> {
     match opts.subcommand {
         Commands::Split(opts) => {
             let n = opts.n;
-            let k = opts.k.unwrap_or(opts.n);
-            let shamir_ida = shamir_ida::ShamirIda::<
-                cbc::Encryptor<Aes256>,
-                cbc::Decryptor<Aes256>,
-                Iso7816,
-            >::new(k);
-
-            let mut input_file = File::open(&opts.input).unwrap();
-            let mut output_files: Vec<_> = (1u8..=n)
-                .map(|x| {
-                    File::create(format!("{}.{}", &opts.output, x))
-                        .expect("Error creating output file")
-                })
-                .collect();
-            output_files
-                .iter_mut()
-                .enumerate()
-                .for_each(|(x, output_file)| {
-                    write_share_header(output_file, &ShareHeader { k: k, x: (x + 1) as u8 })
+            let k = opts.k.unwrap_or_else(|| default_threshold(n));
+            let (cipher, padding) = match opts.scheme {
+                Scheme::Shamir => (Cipher::None, Padding::None),
+                Scheme::Ida => (Cipher::None, Padding::Iso7816),
+                Scheme::ShamirIda | Scheme::ShamirIdaVss => (Cipher::Aes256Gcm, Padding::Iso7816),
+            };
+            let header = ShareHeader {
+                field: opts.field,
+                ..ShareHeader::new(
+                    opts.scheme,
+                    opts.cipher.unwrap_or(cipher),
+                    opts.padding.unwrap_or(padding),
+                    k,
+                    0,
+                )
+            };
+
+            let weights = match opts.weights.len() {
+                0 => vec![1u8; n.into()],
+                len if len == usize::from(n) => opts.weights,
+                len => Opts::command()
+                    .error(
+                        ErrorKind::WrongNumberOfValues,
+                        format!("{} weights given for {} outputs", len, n),
+                    )
+                    .exit(),
+            };
+
+            if opts.resume && opts.armor {
+                conflict("--resume cannot continue armored shares");
+            }
+            // Stdin is read to its end a chunk at a time, so its length need not be known, but
+            // it cannot be read again from where an interrupted split stopped.
+            let input_file = match opts.input.as_str() {
+                STDIO if opts.resume => conflict("--resume needs an input file, not stdin"),
+                STDIO => None,
+                path => Some(with_path(path, File::open(path))?),
+            };
+            let mut output_partitions = Vec::new();
+            let mut next_x = 1u32;
+            for weight in weights {
+                let x =
+                    u16::try_from(next_x).map_err(|_| SplitSecretError::InvalidIndex(u16::MAX))?;
+                let path = format!("{}.{}", &opts.output, x);
+                output_partitions.push(WeightedOutput {
+                    x,
+                    weight,
+                    writer: with_path(&path, open_output(&path, opts.resume))?,
                 });
+                next_x += u32::from(weight);
+            }
+
+            match input_file {
+                Some(input_file) if opts.resume => {
+                    resume_split_weighted_shares(&header, input_file, &mut output_partitions)?;
+                    for output in output_partitions.iter_mut() {
+                        truncate(&mut output.writer)?;
+                    }
+                }
+                input_file => {
+                    let input: Box<dyn Read> = match input_file {
+                        Some(input_file) => Box::new(input_file),
+                        None => Box::new(io::stdin().lock()),
+                    };
+                    if opts.armor {
+                        let mut armored_outputs: Vec<_> = output_partitions
+                            .drain(..)
+                            .map(|output| WeightedOutput {
+                                x: output.x,
+                                weight: output.weight,
+                                writer: ArmorWriter::new(BufWriter::new(output.writer)),
+                            })
+                            .collect();
+                        split_weighted_shares(&header, input, &mut armored_outputs)?;
+                        for output in armored_outputs {
+                            output.writer.finish()?;
+                        }
+                    } else {
+                        split_weighted_shares(&header, input, &mut output_partitions)?;
+                    }
+                }
+            }
+            drop(output_partitions);
+
+            if header.scheme == Scheme::ShamirIdaVss {
+                // Every share carries the same commitments, so publish them from the first.
+                let path = format!("{}.1", &opts.output);
+                let (_, commitments) = verify_share(open_share(&path)?)?;
+                println!(
+                    "commitment fingerprint: {}",
+                    hex(&commitments.fingerprint())
+                );
+            }
+            Ok(())
+        }
+        Commands::Join(opts) => {
+            let mut input_files = Vec::new();
+            for input in opts.inputs.iter() {
+                input_files.push(open_share(input)?);
+            }
+
+            let range = match (opts.offset, opts.length) {
+                (None, None) => None,
+                _ if opts.resume => conflict("--resume cannot join only part of the original"),
+                (offset, length) => Some((offset.unwrap_or(0), length.unwrap_or(u64::MAX))),
+            };
+
+            let result = if opts.output == STDIO {
+                if opts.resume {
+                    conflict("--resume needs an output file, not stdout");
+                }
+                let output = BufWriter::new(io::stdout().lock());
+                match range {
+                    Some((offset, length)) => {
+                        join_range_shares(&mut input_files, offset, length, output)
+                    }
+                    None => join_shares(&mut input_files, output),
+                }
+            } else {
+                let mut output_file =
+                    with_path(&opts.output, open_output(&opts.output, opts.resume))?;
+                if opts.resume {
+                    resume_join_shares(&mut input_files, &mut output_file)
+                        .and_then(|report| truncate(&mut output_file).map(|_| report))
+                } else if let Some((offset, length)) = range {
+                    join_range_shares(&mut input_files, offset, length, &mut output_file)
+                } else {
+                    join_shares(&mut input_files, &mut output_file)
+                }
+            };
+            if let Err(SplitSecretError::CorruptShare { input, .. }) = result {
+                eprintln!(
+                    "{} is damaged and needs to be re-issued",
+                    opts.inputs[input]
+                );
+            }
+            for x in result?.corrupted {
+                eprintln!(
+                    "share {} contained errors that the other shares made up for, and needs to be re-issued",
+                    x
+                );
+            }
+            Ok(())
+        }
+        Commands::Refresh(opts) => {
+            let mut input_files = Vec::new();
+            let mut output_paths = Vec::new();
+            let mut output_files = Vec::new();
+            let mut scheme = None;
+            for input in opts.inputs.iter() {
+                // Refreshed shares keep their index, which names the output file.
+                let header = ShareHeader::read(&mut open_share(input)?)?;
+                scheme = Some(header.scheme);
+                input_files.push(open_share(input)?);
+                let path = format!("{}.{}", &opts.output, header.x);
+                output_files.push(with_path(&path, File::create(&path))?);
+                output_paths.push(path);
+            }
+
+            let result = refresh_shares(&mut input_files, &mut output_files);
+            if let Err(SplitSecretError::CorruptShare { input, .. }) = result {
+                eprintln!(
+                    "{} is damaged and needs to be re-issued",
+                    opts.inputs[input]
+                );
+            }
+            result?;
+            drop(output_files);
+
+            if scheme == Some(Scheme::ShamirIdaVss) {
+                // The commitments changed along with the shares.
+                let path = &output_paths[0];
+                let (_, commitments) = verify_share(open_share(path)?)?;
+                println!(
+                    "commitment fingerprint: {}",
+                    hex(&commitments.fingerprint())
+                );
+            }
+            Ok(())
+        }
+        Commands::Repair(opts) => {
+            let mut input_files = Vec::new();
+            for input in opts.inputs.iter() {
+                input_files.push(open_share(input)?);
+            }
+            let mut output_file = with_path(&opts.output, File::create(&opts.output))?;
+
+            let result = repair_share(&mut input_files, opts.x, &mut output_file);
+            if let Err(SplitSecretError::CorruptShare { input, .. }) = result {
+                eprintln!(
+                    "{} is damaged and needs to be re-issued",
+                    opts.inputs[input]
+                );
+            }
+            result
+        }
+        Commands::Reshare(opts) => {
+            let mut input_files = Vec::new();
+            for input in opts.inputs.iter() {
+                input_files.push(open_share(input)?);
+            }
+            let mut output_files = Vec::new();
+            for x in 1u16..=opts.n {
+                let path = format!("{}.{}", &opts.output, x);
+                output_files.push(with_path(&path, File::create(&path))?);
+            }
             let mut output_partitions: Vec<_> = output_files
                 .iter_mut()
                 .enumerate()
                 .map(|(i, output_file)| OutputPartition {
-                    x: (i + 1) as u8,
+                    x: (i + 1) as u16,
                     writer: output_file,
                 })
                 .collect();
 
-            shamir_ida.split(&mut input_file, &mut output_partitions);
+            let result = reshare_shares(
+                &mut input_files,
+                opts.k.unwrap_or_else(|| default_threshold(opts.n)),
+                &mut output_partitions,
+            );
+            if let Err(SplitSecretError::CorruptShare { input, .. }) = result {
+                eprintln!(
+                    "{} is damaged and needs to be re-issued",
+                    opts.inputs[input]
+                );
+            }
+            result
         }
-        Commands::Join(opts) => {
-            let mut input_files = Vec::new();
-            let mut k = None;
-            for input in opts.inputs {
-                let mut input_file = File::open(input).unwrap();
-                let share_header = read_share_header(&mut input_file);
-                assert!(share_header.k == k.unwrap_or(share_header.k));
-                k = Some(share_header.k);
-
-                input_files.push((share_header.x, input_file));
-                if input_files.len() == k.unwrap().into() {
-                    break;
+        Commands::Verify(opts) => {
+            // Every share is checked and reported on its own, and the failures are counted.
+            let mut failed = 0;
+            for input in opts.inputs.iter() {
+                // A damaged armor is reported like any other damage to the share.
+                let file = with_path(input, File::open(input))?;
+                let result = ShareReader::new(BufReader::new(file))
+                    .and_then(verify_share)
+                    .and_then(|(header, commitments)| {
+                        let fingerprint = hex(&commitments.fingerprint());
+                        match &opts.fingerprint {
+                            Some(expected) if !expected.eq_ignore_ascii_case(&fingerprint) => {
+                                Err(SplitSecretError::CommitmentMismatch { x: header.x })
+                            }
+                            _ => Ok((header, fingerprint)),
+                        }
+                    });
+                match result {
+                    Ok((header, fingerprint)) => {
+                        println!("{}: share {} ok ({})", input, header.x, fingerprint)
+                    }
+                    Err(e) => {
+                        eprintln!("{}: {}", input, e);
+                        failed += 1;
+                    }
                 }
             }
-            let k = k.unwrap_or(0);
-            assert!(input_files.len() == k.into());
-            assert!(k > 0);
-            let mut input_partitions: Vec<_> = input_files
-                .iter_mut()
-                .map(|(x, input_file)| InputPartition {
-                    x: *x,
-                    reader: input_file,
-                })
-                .collect();
-            let mut output_file = File::create(opts.output).unwrap();
+            match failed {
+                0 => Ok(()),
+                failed => Err(SplitSecretError::VerificationFailed {
+                    failed,
+                    total: opts.inputs.len(),
+                }),
+            }
+        }
+        Commands::SplitPolicy(opts) => {
+            let mut secret = Vec::new();
+            with_path(
+                &opts.input,
+                File::open(&opts.input).and_then(|mut file| file.read_to_end(&mut secret)),
+            )?;
+            for share in opts.policy.split(&secret)? {
+                let path = format!("{}.{}", &opts.output, share.name);
+                share.write(with_path(&path, File::create(&path))?)?;
+            }
+            Ok(())
+        }
+        Commands::JoinPolicy(opts) => {
+            if opts.resume {
+                conflict("join-policy cannot resume; policy shares are joined in one piece");
+            }
+            if opts.offset.is_some() || opts.length.is_some() {
+                conflict(
+                    "join-policy cannot join only part of the original; policy shares are joined \
+                     in one piece",
+                );
+            }
+            let mut shares = Vec::new();
+            for (i, input) in opts.inputs.iter().enumerate() {
+                shares.push(PolicyShare::read(with_path(input, File::open(input))?, i)?);
+            }
+            let secret = shares[0].policy.join(&shares)?;
+            write_original(&opts.output, &secret)
+        }
+        Commands::SplitMnemonic(opts) => {
+            let secret = read_input(&opts.input)?;
+            let k = opts.k.unwrap_or_else(|| default_threshold(opts.n.into()));
+            for share in split_mnemonic(&secret, k, opts.n)? {
+                match &opts.output {
+                    Some(output) => {
+                        let path = format!("{}.{}", output, share.x);
+                        with_path(
+                            &path,
+                            File::create(&path).and_then(|mut file| writeln!(file, "{}", share)),
+                        )?;
+                    }
+                    None => println!("{}", share),
+                }
+            }
+            Ok(())
+        }
+        Commands::JoinMnemonic(opts) => {
+            let texts = read_word_shares(&opts.inputs)?;
+            let mut shares = Vec::new();
+            for (name, text) in texts.iter() {
+                let share = text.parse::<MnemonicShare>();
+                if share.is_err() {
+                    eprintln!("{} is not a valid share", name);
+                }
+                shares.push(share?);
+            }
+            write_original(&opts.output, &join_mnemonic(&shares)?)
+        }
+        Commands::SplitSlip39(opts) => {
+            let word_list = WordList::english();
+            let passphrase = read_passphrase(&opts.passphrase_file)?;
+            let master_secret = read_input(&opts.input)?;
+            let groups = Slip39::new(opts.group_threshold, &opts.groups)?
+                .with_iteration_exponent(opts.iteration_exponent)?
+                .with_extendable(!opts.no_extendable)
+                .split(&master_secret, &passphrase)?;
+            for (i, members) in groups.iter().enumerate() {
+                if opts.output.is_none() && i > 0 {
+                    println!();
+                }
+                for share in members {
+                    let words = share.to_words(&word_list)?;
+                    match &opts.output {
+                        Some(output) => {
+                            let path = format!(
+                                "{}.{}.{}",
+                                output,
+                                share.group_index + 1,
+                                share.member_index + 1
+                            );
+                            with_path(
+                                &path,
+                                File::create(&path)
+                                    .and_then(|mut file| writeln!(file, "{}", words)),
+                            )?;
+                        }
+                        None => println!("{}", words),
+                    }
+                }
+            }
+            Ok(())
+        }
+        Commands::JoinSlip39(opts) => {
+            let word_list = WordList::english();
+            let passphrase = read_passphrase(&opts.passphrase_file)?;
+            let mut shares = Vec::new();
+            for (name, text) in read_word_shares(&opts.inputs)?.iter() {
+                let share = Slip39Share::from_words(text, &word_list);
+                if share.is_err() {
+                    eprintln!("{} is not a valid share", name);
+                }
+                shares.push(share?);
+            }
+            write_original(&opts.output, &join_slip39(&shares, &passphrase)?)
+        }
+    }
+}
 
-            let shamir_ida = shamir_ida::ShamirIda::<
-                cbc::Encryptor<Aes256>,
-                cbc::Decryptor<Aes256>,
-                Iso7816,
-            >::new(k);
+fn main() {
+    let opts: Opts = Opts::parse();
 
-            shamir_ida.join(&mut input_partitions, &mut output_file);
-        }
+    if let Err(e) = run(opts) {
+        eprintln!("error: {}", e);
+        process::exit(1);
     }
 }
@@ -0,0 +1,444 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{InputPartition, Partitioner};
+use crate::shamir::Shamir;
+
+use rand::rngs::OsRng;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Longest secret, in bytes, that [`split_mnemonic`] shares.
+pub const MAX_SECRET_SIZE: usize = 64;
+
+// The BIP-39 English word list: 2048 words, sorted, and each one identified by its first four
+// letters.
+const WORD_LIST: &str = include_str!("bip39_english.txt");
+
+const BITS_PER_WORD: usize = 11;
+// The identifier, threshold, index and secret length before the share itself.
+const ID_BITS: usize = 15;
+const PREFIX_BITS: usize = ID_BITS + 8 + 8 + 6;
+// The checksum takes at least this many bits, and whatever else it takes to fill the last word.
+const MIN_CHECKSUM_BITS: usize = 20;
+// Typos further than this from every listed word get no suggestions.
+const MAX_DISTANCE: usize = 2;
+const MAX_SUGGESTIONS: usize = 3;
+
+fn words() -> Vec<&'static str> {
+    WORD_LIST.lines().collect()
+}
+
+pub(crate) fn push_bits(bits: &mut Vec<bool>, value: u64, count: usize) {
+    for i in (0..count).rev() {
+        bits.push((value >> i) & 1 == 1);
+    }
+}
+
+pub(crate) fn take_bits(bits: &[bool]) -> u64 {
+    bits.iter().fold(0, |value, &bit| (value << 1) | bit as u64)
+}
+
+fn invalid(message: String) -> SplitSecretError {
+    SplitSecretError::InvalidMnemonic(message)
+}
+
+/// A [`Shamir`] share of a secret of at most [`MAX_SECRET_SIZE`] bytes, written as words of the
+/// BIP-39 English word list so that it can be copied out by hand, as for a wallet seed or a master
+/// password.
+///
+/// Every word holds 11 bits: an identifier of the split, the threshold, the index of the share,
+/// the length of the secret, the share itself and a checksum of at least 20 bits filling out the
+/// last word. A share of a 16-byte secret is 17 words and one of a 32-byte secret 29 words.
+///
+/// Parsing ignores case and how the words are spaced or broken over lines. A word that is not in
+/// the list is reported with the listed words closest to it, and a share that fails its checksum
+/// with the single-word changes that would fix it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MnemonicShare {
+    /// Random identifier shared by all shares of one split, so that shares of different splits
+    /// are not joined.
+    pub id: u16,
+    pub k: u8,
+    pub x: u8,
+    pub value: Vec<u8>,
+}
+
+impl MnemonicShare {
+    // What the checksum covers: everything else in the share.
+    fn checksum(&self, count: usize) -> Vec<bool> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.id.to_be_bytes());
+        hasher.update([self.k, self.x, self.value.len() as u8]);
+        hasher.update(&self.value);
+        let mut bits = Vec::new();
+        for byte in hasher.finalize() {
+            push_bits(&mut bits, byte.into(), 8);
+        }
+        bits.truncate(count);
+        bits
+    }
+
+    // The indices in the word list of the words of the share.
+    fn to_indices(&self) -> Vec<usize> {
+        let mut bits = Vec::new();
+        push_bits(&mut bits, self.id.into(), ID_BITS);
+        push_bits(&mut bits, self.k.into(), 8);
+        push_bits(&mut bits, self.x.into(), 8);
+        push_bits(&mut bits, self.value.len() as u64 - 1, 6);
+        for &byte in &self.value {
+            push_bits(&mut bits, byte.into(), 8);
+        }
+        let padding =
+            (BITS_PER_WORD - (bits.len() + MIN_CHECKSUM_BITS) % BITS_PER_WORD) % BITS_PER_WORD;
+        bits.extend(self.checksum(MIN_CHECKSUM_BITS + padding));
+        bits.chunks(BITS_PER_WORD)
+            .map(|word| take_bits(word) as usize)
+            .collect()
+    }
+
+    // Decodes the words at `indices`, or returns None if the checksum does not match.
+    fn from_indices(indices: &[usize]) -> Result<Option<Self>> {
+        let mut bits = Vec::new();
+        for &index in indices {
+            push_bits(&mut bits, index as u64, BITS_PER_WORD);
+        }
+        if bits.len() < PREFIX_BITS + 8 + MIN_CHECKSUM_BITS {
+            return Err(invalid(format!(
+                "{} words are too few for a share",
+                indices.len()
+            )));
+        }
+        let len = take_bits(&bits[ID_BITS + 16..PREFIX_BITS]) as usize + 1;
+        let end = PREFIX_BITS + 8 * len;
+        let checksum_bits = bits.len().saturating_sub(end);
+        if !(MIN_CHECKSUM_BITS..MIN_CHECKSUM_BITS + BITS_PER_WORD).contains(&checksum_bits) {
+            return Err(invalid(format!(
+                "{} words are the wrong number for a share of a {}-byte secret",
+                indices.len(),
+                len
+            )));
+        }
+        let share = MnemonicShare {
+            id: take_bits(&bits[..ID_BITS]) as u16,
+            k: take_bits(&bits[ID_BITS..ID_BITS + 8]) as u8,
+            x: take_bits(&bits[ID_BITS + 8..ID_BITS + 16]) as u8,
+            value: bits[PREFIX_BITS..end]
+                .chunks(8)
+                .map(|byte| take_bits(byte) as u8)
+                .collect(),
+        };
+        if share.checksum(checksum_bits) != bits[end..] {
+            return Ok(None);
+        }
+        if share.k < 2 || share.x == 0 {
+            return Err(invalid("the share has a threshold or index of 0".into()));
+        }
+        Ok(Some(share))
+    }
+
+    // The single-word changes, as positions and words, that make a share that does not decode
+    // pass its checksum.
+    fn corrections(list: &[&'static str], indices: &[usize]) -> Vec<(usize, &'static str)> {
+        let mut corrections = Vec::new();
+        let mut changed = indices.to_vec();
+        for (position, &index) in indices.iter().enumerate() {
+            for (candidate, word) in list.iter().enumerate() {
+                if candidate == index || distance(list[index], word) > MAX_DISTANCE {
+                    continue;
+                }
+                changed[position] = candidate;
+                if let Ok(Some(_)) = Self::from_indices(&changed) {
+                    corrections.push((position, *word));
+                }
+            }
+            changed[position] = index;
+        }
+        corrections
+    }
+}
+
+/// Writes the words of the share, separated by single spaces.
+impl fmt::Display for MnemonicShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = words();
+        let words: Vec<&str> = self.to_indices().into_iter().map(|i| list[i]).collect();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl FromStr for MnemonicShare {
+    type Err = SplitSecretError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let list = words();
+        let mut indices = Vec::new();
+        for (i, word) in s.split_whitespace().enumerate() {
+            let word = word.to_lowercase();
+            match list.binary_search(&word.as_str()) {
+                Ok(index) => indices.push(index),
+                Err(_) => {
+                    return Err(SplitSecretError::UnknownWord {
+                        position: i + 1,
+                        suggestions: suggestions(&list, &word),
+                        word,
+                    })
+                }
+            }
+        }
+
+        let error = match Self::from_indices(&indices) {
+            Ok(Some(share)) => return Ok(share),
+            Ok(None) => invalid("the checksum does not match".into()),
+            Err(e) => e,
+        };
+        let corrections = Self::corrections(&list, &indices);
+        if corrections.is_empty() {
+            return Err(error);
+        }
+        let corrections: Vec<String> = corrections
+            .iter()
+            .map(|&(position, word)| {
+                format!(
+                    "word {}, '{}', were '{}'",
+                    position + 1,
+                    list[indices[position]],
+                    word
+                )
+            })
+            .collect();
+        Err(invalid(format!(
+            "the share does not decode, but would if {}",
+            corrections.join(" or if ")
+        )))
+    }
+}
+
+// The listed words that `word` was probably meant as: the one starting with the same four letters,
+// which no other word does, or failing that the closest few within a couple of typos.
+pub(crate) fn suggestions(list: &[&str], word: &str) -> Vec<String> {
+    if let Some(prefix) = word.get(..4) {
+        if let Some(found) = list.iter().find(|listed| listed.starts_with(prefix)) {
+            return vec![found.to_string()];
+        }
+    }
+    let mut close: Vec<(usize, &str)> = list
+        .iter()
+        .map(|listed| (distance(word, listed), *listed))
+        .filter(|&(d, _)| d <= MAX_DISTANCE)
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, listed)| listed.to_string())
+        .collect()
+}
+
+// Levenshtein distance: the fewest insertions, deletions and substitutions turning `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != cb) as usize;
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Splits `secret`, of 1 to [`MAX_SECRET_SIZE`] bytes, into `n` mnemonic shares of which any `k`
+/// join to it.
+pub fn split_mnemonic(secret: &[u8], k: u8, n: u8) -> Result<Vec<MnemonicShare>> {
+    if secret.is_empty() {
+        return Err(SplitSecretError::InvalidSecret(
+            "the secret is empty".into(),
+        ));
+    }
+    if secret.len() > MAX_SECRET_SIZE {
+        return Err(SplitSecretError::SecretTooLarge {
+            max: MAX_SECRET_SIZE,
+        });
+    }
+    let id = OsRng.gen::<u16>() >> (16 - ID_BITS);
+    let shares = Shamir::new(k)?.split_in_memory(secret, n.into())?;
+    Ok(shares
+        .into_iter()
+        .map(|share| MnemonicShare {
+            id,
+            k,
+            x: share.x as u8,
+            value: share.value,
+        })
+        .collect())
+}
+
+/// Joins mnemonic shares from the same split back into the secret.
+pub fn join_mnemonic(shares: &[MnemonicShare]) -> Result<Vec<u8>> {
+    let first = shares
+        .first()
+        .ok_or(SplitSecretError::ShareCount { k: 0, n: 0 })?;
+    if let Some(share) = shares.iter().find(|share| {
+        share.id != first.id || share.k != first.k || share.value.len() != first.value.len()
+    }) {
+        return Err(SplitSecretError::MismatchedHeader { x: share.x.into() });
+    }
+    let mut inputs: Vec<_> = shares
+        .iter()
+        .map(|share| InputPartition {
+            x: share.x.into(),
+            reader: &share.value[..],
+        })
+        .collect();
+    let mut secret = Vec::new();
+    Shamir::new(first.k)?.join(&mut inputs, &mut secret)?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<MnemonicShare> {
+        text.parse()
+    }
+
+    fn share() -> MnemonicShare {
+        MnemonicShare {
+            id: 1234,
+            k: 2,
+            x: 1,
+            value: b"correct horse".to_vec(),
+        }
+    }
+
+    #[test]
+    fn word_list() {
+        let list = words();
+        assert_eq!(list.len(), 1 << BITS_PER_WORD);
+        assert!(list.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn round_trip() {
+        for len in [1, 16, 32, MAX_SECRET_SIZE] {
+            let secret: Vec<u8> = (0..len as u8).map(|i| i.wrapping_mul(37)).collect();
+            let shares = split_mnemonic(&secret, 3, 5).unwrap();
+            let parsed: Vec<_> = shares
+                .iter()
+                .map(|share| parse(&share.to_string()).unwrap())
+                .collect();
+            assert_eq!(parsed, shares);
+            assert_eq!(join_mnemonic(&parsed[2..]).unwrap(), secret);
+        }
+
+        let shares = split_mnemonic(&[7u8; 16], 2, 2).unwrap();
+        assert_eq!(shares[0].to_string().split(' ').count(), 17);
+        let shares = split_mnemonic(&[7u8; 32], 2, 2).unwrap();
+        assert_eq!(shares[0].to_string().split(' ').count(), 29);
+
+        assert!(split_mnemonic(&[], 2, 3).is_err());
+        assert!(split_mnemonic(&[0u8; MAX_SECRET_SIZE + 1], 2, 3).is_err());
+    }
+
+    #[test]
+    fn typed() {
+        let share = &split_mnemonic(b"correct horse", 2, 3).unwrap()[1];
+        let text = share.to_string();
+        let words: Vec<&str> = text.split(' ').collect();
+        let typed = format!(
+            "  {}\n{}\r\n\t{}  ",
+            words[..4].join("  ").to_uppercase(),
+            words[4..9].join(" "),
+            words[9..].join(" ")
+        );
+        assert_eq!(&parse(&typed).unwrap(), share);
+    }
+
+    #[test]
+    fn misspelled() {
+        let text = share().to_string();
+        let mut words: Vec<String> = text.split(' ').map(String::from).collect();
+        let word = words[3].clone();
+
+        // A word cut short or with a letter swapped for another.
+        words[3] = word[..word.len() - 1].to_string() + "q";
+        match parse(&words.join(" ")) {
+            Err(SplitSecretError::UnknownWord {
+                position,
+                suggestions,
+                ..
+            }) => {
+                assert_eq!(position, 4);
+                assert!(suggestions.contains(&word));
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // Nothing close.
+        words[3] = "qqqqqqqq".into();
+        match parse(&words.join(" ")) {
+            Err(SplitSecretError::UnknownWord { suggestions, .. }) => {
+                assert!(suggestions.is_empty())
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        let list = words();
+        let share = share();
+        let mut indices = share.to_indices();
+
+        // A listed word swapped for one a letter away fails the checksum, which names the fix.
+        let (position, typo) = (0..indices.len())
+            .find_map(|position| {
+                let original = list[indices[position]];
+                (0..list.len())
+                    .find(|&i| distance(list[i], original) == 1)
+                    .map(|typo| (position, typo))
+            })
+            .unwrap();
+        let original = list[indices[position]];
+        indices[position] = typo;
+        let typed: Vec<&str> = indices.iter().map(|&i| list[i]).collect();
+        match parse(&typed.join(" ")) {
+            Err(SplitSecretError::InvalidMnemonic(message)) => assert!(message.contains(&format!(
+                "word {}, '{}', were '{}'",
+                position + 1,
+                list[typo],
+                original
+            ))),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // A missing word.
+        let text = share.to_string();
+        let words: Vec<&str> = text.split(' ').collect();
+        assert!(matches!(
+            parse(&words[1..].join(" ")),
+            Err(SplitSecretError::InvalidMnemonic(_))
+        ));
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn mismatched() {
+        let first = split_mnemonic(b"correct horse", 2, 3).unwrap();
+        let mut second = split_mnemonic(b"correct horse", 2, 3).unwrap();
+        second[1].id = first[0].id ^ 1;
+        assert!(matches!(
+            join_mnemonic(&[first[0].clone(), second[1].clone()]),
+            Err(SplitSecretError::MismatchedHeader { x: 2 })
+        ));
+        assert!(matches!(
+            join_mnemonic(&first[..1]),
+            Err(SplitSecretError::ShareCount { .. })
+        ));
+    }
+}
@@ -1,7 +1,8 @@
 use std::cmp::min;
-use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::io::{Error, Read, Result, Write};
 use std::marker::PhantomData;
 
+use crate::error::SplitSecretError;
 use crate::utils::read_full;
 
 use block_padding::RawPadding;
@@ -15,11 +16,15 @@ fn mod_positive(n: usize, k: usize) -> usize {
     }
 }
 
+/// Whether a padding stream adds or removes padding.
 pub enum Op {
     Pad,
     Unpad,
 }
 
+/// Adds or removes padding at the end of everything read from the wrapped reader.
+///
+/// The last block read from the wrapped reader is held back until the wrapped reader ends.
 pub struct PaddedReader<P, R>
 where
     P: RawPadding,
@@ -29,11 +34,11 @@ where
     block_size: usize,
     reader: R,
     op: Op,
+    // Bytes read from the wrapped reader but not yet returned. Until the wrapped reader ends, at
+    // least a block is kept here; after that, the operation has been applied to its contents.
     buf: Vec<u8>,
     bytes_read: usize,
-    // If output_buf is Some, then we've reached the end of the wrapped reader and applied the
-    // operation, and all that is left to do is output these remaining bytes and finish.
-    output_buf: Option<Vec<u8>>,
+    finished: bool,
 }
 
 impl<P, R> PaddedReader<P, R>
@@ -47,11 +52,39 @@ where
             block_size,
             reader,
             op,
-            buf: vec![0u8; block_size],
+            buf: Vec::new(),
             bytes_read: 0,
-            output_buf: None,
+            finished: false,
         }
     }
+
+    // Applies the operation to the last block, which is at the end of self.buf.
+    fn finish(&mut self) -> Result<()> {
+        self.finished = true;
+        if self.bytes_read == 0 {
+            // Wrapped reader must have been empty.
+            return Ok(());
+        }
+        match &self.op {
+            Op::Pad => {
+                let last_block_size = self.bytes_read % self.block_size;
+                let start = self.buf.len() - last_block_size;
+                self.buf.resize(start + self.block_size, 0u8);
+                P::raw_pad(&mut self.buf[start..], last_block_size);
+            }
+            Op::Unpad => {
+                if !self.bytes_read.is_multiple_of(self.block_size) {
+                    return Err(SplitSecretError::Padding.into());
+                }
+                let start = self.buf.len() - self.block_size;
+                let unpadded_size = P::raw_unpad(&self.buf[start..])
+                    .map_err(|_| Error::from(SplitSecretError::Padding))?
+                    .len();
+                self.buf.truncate(start + unpadded_size);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<P, R> Read for PaddedReader<P, R>
@@ -65,90 +98,35 @@ where
             return Ok(0);
         }
 
-        match &self.output_buf {
-            Some(output_buf) => {
-                let read_size = min(output_buf.len(), buf_len);
-                buf[..read_size].copy_from_slice(&output_buf[..read_size]);
-                self.output_buf = Some(output_buf[read_size..].to_vec());
-                return Ok(read_size);
+        // Fill self.buf with enough to fill buf and still hold back a block, unless the wrapped
+        // reader ends first.
+        let target_size = buf_len + self.block_size;
+        if !self.finished && self.buf.len() < target_size {
+            let buf_bytes = self.buf.len();
+            self.buf.resize(target_size, 0u8);
+            let bytes_here = read_full(&mut self.reader, &mut self.buf[buf_bytes..])?;
+            self.buf.truncate(buf_bytes + bytes_here);
+            self.bytes_read += bytes_here;
+            if self.buf.len() < target_size {
+                self.finish()?;
             }
-            None => {}
         }
 
-        let buf_bytes = min(self.block_size, self.bytes_read);
-
-        let mut bytes_here = buf_bytes;
-        if buf_len < buf_bytes {
-            // Fill buf with bytes from self.buf
-            buf[..].copy_from_slice(&self.buf[..buf_len]);
-            // Move remaining bytes in self.buf backwards
-            self.buf.copy_within(buf_len..buf_bytes, 0);
-            // Try to fill up the rest of self.buf
-            bytes_here += read_full(&mut self.reader, &mut self.buf[buf_bytes - buf_len..])?;
+        let available = if self.finished {
+            self.buf.len()
         } else {
-            // Copy over all the bytes from self.buf
-            buf[..buf_bytes].copy_from_slice(&self.buf[..buf_bytes]);
-            // Try to fill up the rest of buf and self.buf
-            bytes_here += read_full(&mut self.reader, &mut buf[buf_bytes..])?;
-            bytes_here += read_full(&mut self.reader, &mut self.buf[..])?;
-        }
-        self.bytes_read += bytes_here - buf_bytes;
-
-        if bytes_here == 0 {
-            // Wrapped reader must have been empty.
-            return Ok(0);
-        }
-
-        if bytes_here == buf_len + self.buf.len() {
-            // The internal buffer is full, which means everything in buf is safe to read.
-            Ok(buf_len)
-        } else {
-            // We've reached the end of the wrapped reader. Add or remove padding and store any
-            // remaining bytes to be read in output_buf.
-
-            // Figure out where the penultimate block ends, and copy everything after that into
-            // last_block.
-            let mut last_block = vec![0u8; self.block_size];
-            let last_block_size = self.bytes_read % self.block_size;
-            let mut read_size = bytes_here - last_block_size;
-            let last_block_bytes_in_buf = min(buf_len, bytes_here) - read_size;
-            last_block[..last_block_bytes_in_buf]
-                .copy_from_slice(&buf[read_size..read_size + last_block_bytes_in_buf]);
-            if last_block_bytes_in_buf < last_block_size {
-                last_block[last_block_bytes_in_buf..last_block_size]
-                    .copy_from_slice(&self.buf[..last_block_size - last_block_bytes_in_buf]);
-            }
-
-            // Apply the padding or unpadding to last block
-            let output = match &self.op {
-                Op::Pad => {
-                    P::raw_pad(&mut last_block[..], last_block_size);
-                    &last_block[..]
-                }
-                Op::Unpad => {
-                    if last_block_size != self.block_size {
-                        return Err(Error::new(
-                            ErrorKind::InvalidData,
-                            "input reader did not contain a multiple of block_size bytes",
-                        ));
-                    }
-                    P::raw_unpad(&last_block[..last_block_size])
-                        .map_err(|_| Error::new(ErrorKind::InvalidData, "error unpadding data"))?
-                }
-            };
-
-            // Copy as much as possible into buf, save the rest in self.output_buf
-            let bytes_copied_to_buf = min(buf_len - read_size, output.len());
-            buf[read_size..read_size + bytes_copied_to_buf]
-                .copy_from_slice(&output[..bytes_copied_to_buf]);
-            read_size += bytes_copied_to_buf;
-            self.output_buf = Some(output[bytes_copied_to_buf..].to_vec());
-
-            Ok(read_size)
-        }
+            self.buf.len() - self.block_size
+        };
+        let read_size = min(available, buf_len);
+        buf[..read_size].copy_from_slice(&self.buf[..read_size]);
+        self.buf.drain(..read_size);
+        Ok(read_size)
     }
 }
 
+/// Adds or removes padding at the end of everything written to it.
+///
+/// The final block is held back until the stream is flushed. An empty stream is left empty.
 pub struct PaddedWriter<P, W>
 where
     P: RawPadding,
@@ -179,6 +157,11 @@ where
             flushed: false,
         }
     }
+
+    /// Returns the wrapped writer, which has everything but the final block written so far.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
 }
 
 impl<P, W> Write for PaddedWriter<P, W>
@@ -220,6 +203,10 @@ where
             return Ok(());
         }
         self.flushed = true;
+        if self.bytes_written == 0 {
+            // Like PaddedReader, leave an empty stream empty.
+            return self.writer.flush();
+        }
         let last_block_size = mod_positive(self.bytes_written, self.block_size);
         let mut last_block = vec![0u8; self.block_size];
         last_block[..last_block_size].copy_from_slice(&self.buf[..last_block_size]);
@@ -236,16 +223,68 @@ where
             }
             Op::Unpad => {
                 if last_block_size != self.block_size {
-                    return Err(Error::new(
-                        ErrorKind::InvalidData,
-                        "number of bytes written was not a multiple of block_size",
-                    ));
+                    return Err(SplitSecretError::Padding.into());
                 }
-                P::raw_unpad(&last_block[..])
-                    .map_err(|_| Error::new(ErrorKind::InvalidData, "error unpadding data"))?
+                P::raw_unpad(&last_block[..]).map_err(|_| Error::from(SplitSecretError::Padding))?
             }
         };
         self.writer.write_all(&to_write)?;
         self.writer.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use block_padding::{Iso7816, Pkcs7};
+
+    // Reads everything from reader in reads of at most read_size bytes.
+    fn read_in_steps(mut reader: impl Read, read_size: usize) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut buf = vec![0u8; read_size];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(output),
+                n => output.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    fn round_trip<P: RawPadding>() {
+        for len in [0usize, 1, 7, 8, 9, 16, 100] {
+            let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for read_size in [1usize, 3, 8, 1024] {
+                let padded =
+                    read_in_steps(PaddedReader::<P, _>::new(8, &input[..], Op::Pad), read_size)
+                        .unwrap();
+                // An empty input stays empty; anything else gains at least a byte of padding.
+                assert_eq!(padded.len() % 8, 0);
+                assert!(len == 0 || padded.len() > len);
+                let unpadded = read_in_steps(
+                    PaddedReader::<P, _>::new(8, &padded[..], Op::Unpad),
+                    read_size,
+                )
+                .unwrap();
+                assert_eq!(input, unpadded);
+
+                let mut writer = PaddedWriter::<P, _>::new(8, Vec::new(), Op::Unpad);
+                for step in padded.chunks(read_size) {
+                    writer.write_all(step).unwrap();
+                }
+                writer.flush().unwrap();
+                assert_eq!(&input, writer.get_mut());
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_iso() {
+        round_trip::<Iso7816>();
+    }
+
+    #[test]
+    fn round_trip_pkcs() {
+        round_trip::<Pkcs7>();
+    }
+}
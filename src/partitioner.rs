@@ -1,27 +1,60 @@
-use std::io::{Cursor, Read, Write};
+use std::collections::BTreeSet;
+use std::io::{self, Cursor, Read, Seek, Write};
 
-#[allow(dead_code)]
+use crate::error::{Result, SplitSecretError};
+use crate::field::{decode_elements, encode_elements, indices, GaloisField};
+use crate::poly::lagrange_eval;
+use crate::utils::read_full;
+
+const BUF_SIZE: usize = 1024;
+
+/// A share held entirely in memory.
 pub struct InMemoryPartition {
-    pub x: u8,
+    /// Index of the share; shares are evaluations of a polynomial at `x`, so it must be nonzero.
+    pub x: u16,
     pub value: Vec<u8>,
 }
 
-pub struct InputPartition<R: Read> {
-    pub x: u8,
+/// A share being read during a join.
+pub struct InputPartition<R> {
+    /// Index of the share, as it was given to [`OutputPartition::x`] during the split.
+    pub x: u16,
     pub reader: R,
 }
 
-pub struct OutputPartition<W: Write> {
-    pub x: u8,
+/// A share being written during a split.
+pub struct OutputPartition<W> {
+    /// Index of the share; must be nonzero and unique among the outputs of a split.
+    pub x: u16,
     pub writer: W,
 }
 
+/// What a join learned about its inputs besides the original stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinReport {
+    /// Indices of the shares that contained errors, which were corrected using the other shares
+    /// or left out of the join, in increasing order.
+    pub corrupted: Vec<u16>,
+}
+
+impl JoinReport {
+    /// Adds the findings of `other`, for schemes that join their shares in several parts.
+    pub fn merge(&mut self, other: JoinReport) {
+        self.corrupted.extend(other.corrupted);
+        self.corrupted.sort_unstable();
+        self.corrupted.dedup();
+    }
+}
+
+/// A scheme for splitting a stream into shares and joining shares back into the original stream.
 pub trait Partitioner {
-    fn split<R: Read, W: Write>(&self, input: R, outputs: &mut [OutputPartition<W>]);
+    /// Reads `input` to the end and writes one share to each of `outputs`.
+    fn split<R: Read, W: Write>(&self, input: R, outputs: &mut [OutputPartition<W>]) -> Result<()>;
 
-    fn split_in_memory(&self, input: &[u8], n: u8) -> Vec<InMemoryPartition> {
+    /// Splits `input` into `n` shares with indices `1..=n`.
+    fn split_in_memory(&self, input: &[u8], n: u16) -> Result<Vec<InMemoryPartition>> {
         let mut outputs = Vec::new();
-        for x in 1u8..=n {
+        for x in 1u16..=n {
             outputs.push(InMemoryPartition {
                 x: x,
                 value: Vec::new(),
@@ -36,15 +69,92 @@ pub trait Partitioner {
                     writer: &mut p.value,
                 })
                 .collect::<Vec<_>>(),
-        );
+        )?;
+
+        Ok(outputs)
+    }
+
+    /// Reads `inputs` to the end and writes the original stream to `output`.
+    ///
+    /// At least `k` inputs are required. Schemes that can use more than `k` to detect or correct
+    /// errors do so, and report the shares they found to be wrong.
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: W,
+    ) -> Result<JoinReport>;
+
+    /// Joins only the `len` bytes of the original stream starting at `offset`, writing them to
+    /// `output`. A range that runs past the end of the original stream is cut short.
+    ///
+    /// Every input must be positioned at the start of its share, and is left somewhere inside it.
+    /// Schemes whose shares can be decoded from any position, like [`crate::Ida`] and
+    /// [`crate::ShamirIdaCtr`], read from each input just the part of its share that covers the
+    /// range, and correct and report errors in that part as [`Partitioner::join`] does. The
+    /// default implementation joins everything and drops what lies outside the range.
+    fn join_range<R: Read + Seek, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        offset: u64,
+        len: u64,
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let report = self.join(
+            inputs,
+            RangeWriter {
+                writer: &mut output,
+                position: 0,
+                start: offset,
+                end: offset.saturating_add(len),
+            },
+        )?;
+        output.flush()?;
+        Ok(report)
+    }
+
+    /// Re-randomizes the shares in `inputs` without changing the original they join to, writing
+    /// the new share for `inputs[i]` to `outputs[i]`.
+    ///
+    /// Refreshed shares cannot be joined with shares from before the refresh, so every share that
+    /// is to remain usable has to be refreshed together. The default implementation returns
+    /// [`SplitSecretError::Unsupported`].
+    fn refresh<R: Read, W: Write>(
+        &self,
+        _inputs: &mut [InputPartition<R>],
+        _outputs: &mut [W],
+    ) -> Result<()> {
+        Err(SplitSecretError::Unsupported("refresh"))
+    }
 
-        outputs
+    /// Regenerates the share with index `output.x` from `k` of the others in `inputs`.
+    ///
+    /// The regenerated share is identical to the one produced by the original split, so it can
+    /// be joined with the rest of the set. The default implementation returns
+    /// [`SplitSecretError::Unsupported`].
+    fn repair<R: Read, W: Write>(
+        &self,
+        _inputs: &mut [InputPartition<R>],
+        _output: OutputPartition<W>,
+    ) -> Result<()> {
+        Err(SplitSecretError::Unsupported("repair"))
     }
 
-    fn join<R: Read, W: Write>(&self, inputs: &mut [InputPartition<R>], output: W);
+    /// Joins `inputs` and splits the original again into a new set of shares with threshold
+    /// `k`, one for each of `outputs`, without writing the original anywhere.
+    ///
+    /// The default implementation returns [`SplitSecretError::Unsupported`].
+    fn reshare<R: Read, W: Write>(
+        &self,
+        _inputs: &mut [InputPartition<R>],
+        _k: u8,
+        _outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        Err(SplitSecretError::Unsupported("reshare"))
+    }
 
-    fn join_in_memory(&self, inputs: &mut [&mut InMemoryPartition]) -> Vec<u8> {
-        let mut input_readers: Vec<(u8, Cursor<_>)> = inputs
+    /// Joins shares produced by [`Partitioner::split_in_memory`].
+    fn join_in_memory(&self, inputs: &mut [&mut InMemoryPartition]) -> Result<Vec<u8>> {
+        let mut input_readers: Vec<(u16, Cursor<_>)> = inputs
             .iter_mut()
             .map(|input| (input.x, Cursor::new(&mut input.value)))
             .collect();
@@ -56,8 +166,156 @@ pub trait Partitioner {
             })
             .collect::<Vec<_>>();
         let mut output = Vec::new();
-        self.join(&mut inputs, &mut output);
-        output
+        self.join(&mut inputs, &mut output)?;
+        Ok(output)
+    }
+}
+
+// Passes on only the bytes written to it from position start up to end.
+struct RangeWriter<W: Write> {
+    writer: W,
+    position: u64,
+    start: u64,
+    end: u64,
+}
+
+impl<W: Write> Write for RangeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let from = self
+            .start
+            .saturating_sub(self.position)
+            .min(buf.len() as u64) as usize;
+        let to = self.end.saturating_sub(self.position).min(buf.len() as u64) as usize;
+        if from < to {
+            self.writer.write_all(&buf[from..to])?;
+        }
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Checks that there are at least k share indices, and that they are all nonzero and distinct.
+pub(crate) fn check_indices(k: u8, xs: impl Iterator<Item = u16>) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for x in xs {
+        if x == 0 {
+            return Err(SplitSecretError::InvalidIndex(x));
+        }
+        if !seen.insert(x) {
+            return Err(SplitSecretError::DuplicateIndex(x));
+        }
+    }
+    let n = seen.len();
+    if n < usize::from(k) {
+        return Err(SplitSecretError::ShareCount { k, n });
+    }
+    Ok(())
+}
+
+// Checks that a refresh has an output for each of its inputs, and no others.
+pub(crate) fn check_outputs(inputs: usize, outputs: usize) -> Result<()> {
+    if inputs != outputs {
+        return Err(SplitSecretError::OutputCount { inputs, outputs });
+    }
+    Ok(())
+}
+
+// Checks that a share with index x can be added to the inputs: that x is nonzero and none of them
+// has it. Whether there are enough inputs is left to check_indices or the caller.
+pub(crate) fn check_new_index<R: Read>(inputs: &[InputPartition<R>], x: u16) -> Result<()> {
+    if x == 0 {
+        return Err(SplitSecretError::InvalidIndex(x));
+    }
+    if inputs.iter().any(|input| input.x == x) {
+        return Err(SplitSecretError::DuplicateIndex(x));
+    }
+    Ok(())
+}
+
+// Repairs a share of a scheme whose shares are elementwise evaluations of polynomials of degree
+// less than k, like Shamir and IDA. Every element of the share at x is the same linear
+// combination of the elements of k other shares, given by the Lagrange basis polynomials for
+// their indices.
+pub(crate) fn repair_elementwise<F: GaloisField, R: Read, W: Write>(
+    field: &F,
+    k: u8,
+    inputs: &mut [InputPartition<R>],
+    mut output: OutputPartition<W>,
+) -> Result<()> {
+    check_indices(k, inputs.iter().map(|input| input.x))?;
+    check_new_index(inputs, output.x)?;
+    let inputs = &mut inputs[..usize::from(k)];
+
+    let input_xs: Vec<u16> = inputs.iter().map(|input| input.x).collect();
+    let xs = indices::<F>(input_xs.iter().copied())?;
+    let lagrange = lagrange_eval(field, &xs, &[F::index(output.x)?]).remove(0);
+
+    let elements = BUF_SIZE / F::ELEMENT_SIZE;
+    let mut read_bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; inputs.len()];
+    let mut input_elements = vec![vec![F::zero(); elements]; inputs.len()];
+    let mut output_elements = vec![F::zero(); elements];
+    let mut write_buf: [u8; BUF_SIZE] = [0u8; BUF_SIZE];
+    loop {
+        let read_size = read_shares(inputs, &mut read_bufs)?;
+        if read_size == 0 {
+            break;
+        }
+        let len = decode_shares::<F, _>(&input_xs, &read_bufs, read_size, &mut input_elements)?;
+        output_elements.fill(F::zero());
+        for (input_elements, scale) in input_elements.iter().zip(lagrange.iter()) {
+            field.add_scaled(&mut output_elements[..len], &input_elements[..len], *scale);
+        }
+        encode_elements::<F>(&output_elements[..len], &mut write_buf[..read_size]);
+        output.writer.write_all(&write_buf[0..read_size])?;
+    }
+    Ok(())
+}
+
+// Decodes read_size bytes of every input into its buffer of elements, returning the number of
+// elements. The inputs hold whole elements unless they are truncated or corrupt.
+pub(crate) fn decode_shares<F: GaloisField, B: AsRef<[u8]>>(
+    xs: &[u16],
+    read_bufs: &[B],
+    read_size: usize,
+    shares: &mut [Vec<F::Element>],
+) -> Result<usize> {
+    if !read_size.is_multiple_of(F::ELEMENT_SIZE) {
+        return Err(SplitSecretError::TruncatedShare { x: xs[0] });
+    }
+    let len = read_size / F::ELEMENT_SIZE;
+    for (i, (read_buf, share)) in read_bufs.iter().zip(shares.iter_mut()).enumerate() {
+        decode_elements::<F>(&read_buf.as_ref()[..read_size], &mut share[..len]).ok_or(
+            SplitSecretError::CorruptShare {
+                input: i,
+                x: Some(xs[i]),
+            },
+        )?;
+    }
+    Ok(len)
+}
+
+// Fills each buffer from the corresponding input and returns the number of bytes read, which is
+// the same for every input unless one of them is truncated.
+pub(crate) fn read_shares<R: Read, B: AsMut<[u8]>>(
+    inputs: &mut [InputPartition<R>],
+    bufs: &mut [B],
+) -> Result<usize> {
+    let mut read_sizes = Vec::with_capacity(inputs.len());
+    for (input, buf) in inputs.iter_mut().zip(bufs.iter_mut()) {
+        read_sizes.push(read_full(&mut input.reader, buf.as_mut())?);
+    }
+    let read_size = read_sizes.iter().copied().max().unwrap_or(0);
+    match inputs
+        .iter()
+        .zip(read_sizes.iter())
+        .find(|(_, size)| **size != read_size)
+    {
+        Some((input, _)) => Err(SplitSecretError::TruncatedShare { x: input.x }),
+        None => Ok(read_size),
     }
 }
 
@@ -78,7 +336,7 @@ pub fn test_join(
             .enumerate()
             .filter_map(|(j, input)| if (i >> j) & 1 != 0 { Some(input) } else { None })
             .collect();
-        let output = partitioner.join_in_memory(&mut inputs_subset);
+        let output = partitioner.join_in_memory(&mut inputs_subset).unwrap();
         assert_eq!(*expected_output, output);
     }
 }
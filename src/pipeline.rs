@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+
+use crate::error::{Result, SplitSecretError};
+use crate::utils::read_full;
+
+// Splits and joins encode their streams in chunks, each of which is encoded independently, so
+// chunks can go to different threads without changing the output. The chunks of all the streams
+// read or written together add up to about CHUNK_SIZE bytes, but each is at least MIN_CHUNK_SIZE.
+const CHUNK_SIZE: usize = 256 * 1024;
+const MIN_CHUNK_SIZE: usize = 1024;
+
+// The chunk size for each of `streams` streams, which is a multiple of unit bytes.
+pub(crate) fn chunk_size(streams: usize, unit: usize) -> usize {
+    let size = (CHUNK_SIZE / streams.max(1)).max(MIN_CHUNK_SIZE);
+    (size / unit).max(1) * unit
+}
+
+// The number of threads partitioners use unless told otherwise: one per core.
+pub(crate) fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Reads the next chunk of up to size bytes, or None at the end of the stream.
+pub(crate) fn read_chunk(reader: &mut impl Read, size: usize) -> Result<Option<Vec<u8>>> {
+    let mut chunk = vec![0u8; size];
+    match read_full(reader, &mut chunk)? {
+        0 => Ok(None),
+        read_size => {
+            chunk.truncate(read_size);
+            Ok(Some(chunk))
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// The threads a partitioner works on chunks with. They are started the first time a stream has
+// more than one chunk, and stop when the partitioner is dropped, so that a partitioner called on
+// every chunk of a share file starts them only once.
+pub(crate) struct Pool {
+    threads: usize,
+    workers: OnceLock<Workers>,
+}
+
+struct Workers {
+    jobs: Option<mpsc::Sender<Job>>,
+    handles: Vec<thread::JoinHandle<()>>,
+}
+
+impl Pool {
+    pub(crate) fn new(threads: usize) -> Self {
+        Pool {
+            threads,
+            workers: OnceLock::new(),
+        }
+    }
+
+    // Sends jobs to whichever thread is free.
+    fn jobs(&self) -> &mpsc::Sender<Job> {
+        let workers = self.workers.get_or_init(|| {
+            let (jobs, receiver) = mpsc::channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            let handles = (0..self.threads)
+                .map(|_| {
+                    let receiver = Arc::clone(&receiver);
+                    thread::spawn(move || loop {
+                        let job = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => return,
+                        };
+                        match job {
+                            // A job that panics drops its result sender, which fails its
+                            // pipeline, but leaves the thread for the next job.
+                            Ok(job) => drop(panic::catch_unwind(AssertUnwindSafe(job))),
+                            Err(_) => return,
+                        }
+                    })
+                })
+                .collect();
+            Workers {
+                jobs: Some(jobs),
+                handles,
+            }
+        });
+        workers.jobs.as_ref().expect("jobs are only taken on drop")
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if let Some(mut workers) = self.workers.take() {
+            drop(workers.jobs.take());
+            for handle in workers.handles {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+// Calls read for chunks until it returns None, calls work on every chunk, and passes the results
+// to write in the order the chunks were read.
+//
+// With more than one thread in the pool, work runs on the pool while the calling thread reads and
+// writes, keeping at most two chunks per thread in flight. A stream of a single chunk is worked
+// on in the calling thread. A chunk whose work panics fails the pipeline with
+// SplitSecretError::ThreadFailed.
+pub(crate) fn run_pipeline<T, U, Rd, Wk, Wr>(
+    pool: &Pool,
+    mut read: Rd,
+    work: Wk,
+    mut write: Wr,
+) -> Result<()>
+where
+    T: Send + 'static,
+    U: Send + 'static,
+    Rd: FnMut() -> Result<Option<T>>,
+    Wk: Fn(T) -> Result<U> + Send + Sync + 'static,
+    Wr: FnMut(U) -> Result<()>,
+{
+    let first = match read()? {
+        Some(chunk) => chunk,
+        None => return Ok(()),
+    };
+    let mut second = read()?;
+    if pool.threads <= 1 || second.is_none() {
+        write(work(first)?)?;
+        while let Some(chunk) = second {
+            write(work(chunk)?)?;
+            second = read()?;
+        }
+        return Ok(());
+    }
+
+    let jobs = pool.jobs();
+    let work = Arc::new(work);
+    // Every chunk in flight sends its result back on its own channel, kept in the order read.
+    let mut results = VecDeque::with_capacity(2 * pool.threads);
+    let mut pending = std::iter::once(first).chain(second);
+    let mut finished = false;
+    loop {
+        while !finished && results.len() < 2 * pool.threads {
+            let chunk = match pending.next() {
+                Some(chunk) => Some(chunk),
+                None => read()?,
+            };
+            match chunk {
+                Some(chunk) => {
+                    let (sender, receiver) = mpsc::channel::<Result<U>>();
+                    let work = Arc::clone(&work);
+                    let job: Job = Box::new(move || {
+                        let _ = sender.send(work(chunk));
+                    });
+                    jobs.send(job).map_err(|_| SplitSecretError::ThreadFailed)?;
+                    results.push_back(receiver);
+                }
+                None => finished = true,
+            }
+        }
+        let result = match results.pop_front() {
+            Some(receiver) => receiver
+                .recv()
+                .map_err(|_| SplitSecretError::ThreadFailed)?,
+            None => return Ok(()),
+        };
+        write(result?)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn keeps_order() {
+        let input: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        for threads in [1, 2, 7] {
+            let mut reader = &input[..];
+            let mut output = Vec::new();
+            run_pipeline(
+                &Pool::new(threads),
+                || read_chunk(&mut reader, 1000),
+                |chunk| Ok(chunk.iter().map(|b| b ^ 0x5a).collect::<Vec<u8>>()),
+                |chunk| {
+                    output.extend(chunk.iter().map(|b| b ^ 0x5a));
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(input, output);
+        }
+    }
+
+    #[test]
+    fn reuses_threads() {
+        // Pipelines run on the same pool share its threads instead of starting their own.
+        let pool = Pool::new(3);
+        let ids = Arc::new(Mutex::new(HashSet::new()));
+        for _ in 0..5 {
+            let input = vec![0u8; 10_000];
+            let mut reader = &input[..];
+            let ids = Arc::clone(&ids);
+            run_pipeline(
+                &pool,
+                || read_chunk(&mut reader, 1000),
+                move |chunk| {
+                    ids.lock().unwrap().insert(thread::current().id());
+                    Ok(chunk)
+                },
+                |_| Ok(()),
+            )
+            .unwrap();
+        }
+        assert!(ids.lock().unwrap().len() <= 3);
+    }
+
+    #[test]
+    fn thread_panics() {
+        let input = vec![0u8; 100_000];
+        let mut reader = &input[..];
+        let result = run_pipeline(
+            &Pool::new(4),
+            || read_chunk(&mut reader, 1000),
+            |chunk| match chunk[0] {
+                0 => panic!("worker failed"),
+                _ => Ok(chunk),
+            },
+            |_| Ok(()),
+        );
+        assert!(matches!(result, Err(SplitSecretError::ThreadFailed)));
+    }
+}
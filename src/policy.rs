@@ -0,0 +1,567 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use crate::chunks::{ChunkReader, ChunkWriter, CHUNK_SIZE};
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{InMemoryPartition, Partitioner};
+use crate::shamir::Shamir;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// Bytes at the start of every policy share file.
+pub const POLICY_MAGIC: [u8; 4] = *b"SSSp";
+/// Version of the policy share file format written by this crate.
+pub const POLICY_FORMAT_VERSION: u8 = 2;
+
+/// An access structure: which sets of participants can recover a secret.
+///
+/// A policy is a tree whose leaves are named participants and whose inner nodes are threshold
+/// gates, satisfied when at least `k` of their branches are. AND and OR are the gates with `k`
+/// equal to the number of branches and `k = 1`. The text form, accepted by [`Policy::from_str`]
+/// and produced by [`Policy::to_string`], looks like `and(2of(alice, bob, carol), or(dave, erin))`.
+///
+/// [`Policy::split`] shares a secret down the tree: every gate with `k > 1` splits the value it
+/// receives with [`Shamir`], giving one share to each branch, and every other gate hands its value
+/// to all its branches unchanged. A participant's share is the value that reaches their leaf.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    Participant(String),
+    Threshold { k: u8, branches: Vec<Policy> },
+}
+
+/// A participant's share of a secret split by [`Policy::split`].
+///
+/// Every share carries the whole policy, so a join can tell what is still missing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyShare {
+    pub policy: Policy,
+    /// Random identifier shared by all shares of a split.
+    pub set_id: [u8; 16],
+    /// The participant this share belongs to.
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+impl Policy {
+    pub fn participant(name: impl Into<String>) -> Self {
+        Policy::Participant(name.into())
+    }
+
+    /// A gate satisfied when all of `branches` are.
+    pub fn and(branches: Vec<Policy>) -> Self {
+        Policy::Threshold {
+            k: branches.len().min(255) as u8,
+            branches,
+        }
+    }
+
+    /// A gate satisfied when any of `branches` is.
+    pub fn or(branches: Vec<Policy>) -> Self {
+        Policy::Threshold { k: 1, branches }
+    }
+
+    /// A gate satisfied when at least `k` of `branches` are.
+    pub fn threshold(k: u8, branches: Vec<Policy>) -> Self {
+        Policy::Threshold { k, branches }
+    }
+
+    // Checks that every gate can be satisfied and every participant appears once.
+    fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        self.validate_node(&mut names)
+    }
+
+    fn validate_node<'a>(&'a self, names: &mut HashSet<&'a str>) -> Result<()> {
+        match self {
+            Policy::Participant(name) => {
+                if !is_name(name) {
+                    return Err(invalid_policy(format!(
+                        "'{}' is not a valid participant name",
+                        name
+                    )));
+                }
+                if !names.insert(name) {
+                    return Err(invalid_policy(format!("{} appears more than once", name)));
+                }
+            }
+            Policy::Threshold { k, branches } => {
+                if branches.is_empty() || branches.len() > 255 {
+                    return Err(invalid_policy(format!(
+                        "a gate cannot have {} branches",
+                        branches.len()
+                    )));
+                }
+                if *k == 0 || usize::from(*k) > branches.len() {
+                    return Err(SplitSecretError::ShareCount {
+                        k: *k,
+                        n: branches.len(),
+                    });
+                }
+                for branch in branches.iter() {
+                    branch.validate_node(names)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The participants of the policy, in the order their shares are returned by
+    /// [`Policy::split`].
+    pub fn participants(&self) -> Vec<&str> {
+        match self {
+            Policy::Participant(name) => vec![name],
+            Policy::Threshold { branches, .. } => branches
+                .iter()
+                .flat_map(|branch| branch.participants())
+                .collect(),
+        }
+    }
+
+    /// Whether the participants in `names` together satisfy the policy.
+    pub fn satisfied_by(&self, names: &[&str]) -> bool {
+        match self {
+            Policy::Participant(name) => names.contains(&name.as_str()),
+            Policy::Threshold { k, branches } => {
+                branches
+                    .iter()
+                    .filter(|branch| branch.satisfied_by(names))
+                    .count()
+                    >= usize::from(*k)
+            }
+        }
+    }
+
+    /// Describes what the participants in `names` are missing to satisfy the policy: one line
+    /// for every unsatisfied gate that still needs branches, outermost first. Empty if the policy
+    /// is satisfied.
+    pub fn missing(&self, names: &[&str]) -> Vec<String> {
+        let mut missing = Vec::new();
+        self.missing_node(names, &mut missing);
+        missing
+    }
+
+    fn missing_node(&self, names: &[&str], missing: &mut Vec<String>) {
+        if let Policy::Threshold { k, branches } = self {
+            let unsatisfied: Vec<&Policy> = branches
+                .iter()
+                .filter(|branch| !branch.satisfied_by(names))
+                .collect();
+            let satisfied = branches.len() - unsatisfied.len();
+            if satisfied >= usize::from(*k) {
+                return;
+            }
+            missing.push(format!(
+                "{} needs {} more of: {}",
+                self,
+                usize::from(*k) - satisfied,
+                unsatisfied
+                    .iter()
+                    .map(|branch| branch.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            for branch in unsatisfied {
+                branch.missing_node(names, missing);
+            }
+        }
+    }
+
+    /// Splits `secret` into one share per participant, in the order of
+    /// [`Policy::participants`].
+    pub fn split(&self, secret: &[u8]) -> Result<Vec<PolicyShare>> {
+        self.validate()?;
+        let mut set_id = [0u8; 16];
+        OsRng.fill_bytes(&mut set_id);
+        let mut shares = Vec::new();
+        self.split_node(secret.to_vec(), &mut |name, value| {
+            shares.push(PolicyShare {
+                policy: self.clone(),
+                set_id,
+                name: name.to_string(),
+                value,
+            })
+        })?;
+        Ok(shares)
+    }
+
+    fn split_node(&self, value: Vec<u8>, emit: &mut impl FnMut(&str, Vec<u8>)) -> Result<()> {
+        match self {
+            Policy::Participant(name) => emit(name, value),
+            Policy::Threshold { k: 1, branches } => {
+                for branch in branches.iter() {
+                    branch.split_node(value.clone(), emit)?;
+                }
+            }
+            Policy::Threshold { k, branches } => {
+                let shamir = Shamir::new(*k)?;
+                let shares = shamir.split_in_memory(&value, branches.len() as u16)?;
+                for (branch, share) in branches.iter().zip(shares) {
+                    branch.split_node(share.value, emit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Recovers the secret from shares produced by [`Policy::split`].
+    ///
+    /// Fails with [`SplitSecretError::PolicyUnsatisfied`], listing what is missing (see
+    /// [`Policy::missing`]), if the shares do not satisfy the policy. Shares beyond what the
+    /// policy requires are used by [`Shamir`] to check the others.
+    pub fn join(&self, shares: &[PolicyShare]) -> Result<Vec<u8>> {
+        self.validate()?;
+        if let Some(first) = shares.first() {
+            for share in shares.iter() {
+                if share.set_id != first.set_id || share.policy != *self {
+                    return Err(invalid_policy(format!(
+                        "the share of {} comes from a different split",
+                        share.name
+                    )));
+                }
+            }
+        }
+        let names: Vec<&str> = shares.iter().map(|share| share.name.as_str()).collect();
+        match self.join_node(shares)? {
+            Some(secret) => Ok(secret),
+            None => Err(SplitSecretError::PolicyUnsatisfied {
+                missing: self.missing(&names),
+            }),
+        }
+    }
+
+    // Recovers the value this node received during the split, if the shares satisfy it.
+    fn join_node(&self, shares: &[PolicyShare]) -> Result<Option<Vec<u8>>> {
+        match self {
+            Policy::Participant(name) => Ok(shares
+                .iter()
+                .find(|share| share.name == *name)
+                .map(|share| share.value.clone())),
+            Policy::Threshold { k, branches } => {
+                let mut recovered = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if let Some(value) = branch.join_node(shares)? {
+                        recovered.push(InMemoryPartition {
+                            x: (i + 1) as u16,
+                            value,
+                        });
+                    }
+                }
+                if recovered.len() < usize::from(*k) {
+                    return Ok(None);
+                }
+                if *k == 1 {
+                    return Ok(Some(recovered.swap_remove(0).value));
+                }
+                let mut inputs: Vec<&mut InMemoryPartition> = recovered.iter_mut().collect();
+                Ok(Some(Shamir::new(*k)?.join_in_memory(&mut inputs)?))
+            }
+        }
+    }
+}
+
+fn invalid_policy(message: impl Into<String>) -> SplitSecretError {
+    SplitSecretError::InvalidPolicy(message.into())
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.@".contains(c))
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Participant(name) => f.write_str(name),
+            Policy::Threshold { k, branches } => {
+                if usize::from(*k) == branches.len() {
+                    f.write_str("and(")?;
+                } else if *k == 1 {
+                    f.write_str("or(")?;
+                } else {
+                    write!(f, "{}of(", k)?;
+                }
+                for (i, branch) in branches.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", branch)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        let policy = parser.policy()?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            return Err(format!("unexpected '{}' in policy", &s[parser.pos..]));
+        }
+        Ok(policy)
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.s[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn policy(&mut self) -> std::result::Result<Policy, String> {
+        self.skip_whitespace();
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "_-.@".contains(c)))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!("expected a participant or gate at '{}'", rest));
+        }
+        let word = &rest[..len];
+        self.pos += len;
+        if !self.eat('(') {
+            return Ok(Policy::participant(word));
+        }
+
+        let mut branches = vec![self.policy()?];
+        while self.eat(',') {
+            branches.push(self.policy()?);
+        }
+        if !self.eat(')') {
+            return Err(format!("expected ')' after the branches of {}", word));
+        }
+        match word {
+            "and" => Ok(Policy::and(branches)),
+            "or" => Ok(Policy::or(branches)),
+            _ => match word.strip_suffix("of").map(u8::from_str) {
+                Some(Ok(k)) => Ok(Policy::threshold(k, branches)),
+                _ => Err(format!("unknown gate '{}'", word)),
+            },
+        }
+    }
+}
+
+impl PolicyShare {
+    /// Writes the share, with its value in chunks verified by a digest (see [`ChunkWriter`]) so
+    /// that a damaged share is detected when it is read.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        let policy = self.policy.to_string();
+        let policy_len = u16::try_from(policy.len())
+            .map_err(|_| invalid_policy("the policy is too long to store"))?;
+        let mut header = Vec::new();
+        header.extend_from_slice(&POLICY_MAGIC);
+        header.push(POLICY_FORMAT_VERSION);
+        header.extend_from_slice(&self.set_id);
+        header.extend_from_slice(&policy_len.to_be_bytes());
+        header.extend_from_slice(policy.as_bytes());
+        header.push(self.name.len() as u8);
+        header.extend_from_slice(self.name.as_bytes());
+        writer.write_all(&header)?;
+
+        let mut chunks = ChunkWriter::with_header(&mut writer, &header, 0);
+        let mut values = self.value.chunks(CHUNK_SIZE).peekable();
+        if values.peek().is_none() {
+            chunks.write_chunk(&[], true)?;
+        }
+        while let Some(value) = values.next() {
+            chunks.write_chunk(value, values.peek().is_none())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a share written by [`PolicyShare::write`]. `input` identifies it in errors.
+    pub fn read<R: Read>(mut reader: R, input: usize) -> Result<Self> {
+        let mut buf = [0u8; 23];
+        reader.read_exact(&mut buf)?;
+        if buf[0..4] != POLICY_MAGIC {
+            return Err(SplitSecretError::InvalidHeader(
+                "not a policy share file".into(),
+            ));
+        }
+        if buf[4] != POLICY_FORMAT_VERSION {
+            return Err(SplitSecretError::InvalidHeader(format!(
+                "unsupported policy format version {}",
+                buf[4]
+            )));
+        }
+        let mut set_id = [0u8; 16];
+        set_id.copy_from_slice(&buf[5..21]);
+
+        let mut policy = vec![0u8; u16::from_be_bytes([buf[21], buf[22]]).into()];
+        reader.read_exact(&mut policy)?;
+        let mut header = buf.to_vec();
+        header.extend_from_slice(&policy);
+        let policy = String::from_utf8(policy)
+            .map_err(|_| invalid_policy("the stored policy is not text"))?
+            .parse::<Policy>()
+            .map_err(invalid_policy)?;
+
+        let mut name_len = [0u8; 1];
+        reader.read_exact(&mut name_len)?;
+        let mut name = vec![0u8; name_len[0].into()];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| invalid_policy("the stored participant name is not text"))?;
+        if !policy.participants().contains(&name.as_str()) {
+            return Err(invalid_policy(format!(
+                "{} is not part of the policy",
+                name
+            )));
+        }
+
+        // Every digest covers the header, so a damaged header that still parses is caught here.
+        header.push(name_len[0]);
+        header.extend_from_slice(name.as_bytes());
+        let mut chunks = ChunkReader::with_header(reader, &header, None, input);
+        let mut value = Vec::new();
+        loop {
+            let chunk = chunks.next_chunk()?;
+            value.extend_from_slice(&chunk.shares);
+            if chunk.last {
+                break;
+            }
+        }
+        Ok(PolicyShare {
+            policy,
+            set_id,
+            name,
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shares_of(shares: &[PolicyShare], names: &[&str]) -> Vec<PolicyShare> {
+        shares
+            .iter()
+            .filter(|share| names.contains(&share.name.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let text = "and(2of(alice, bob, carol), or(dave, erin), frank)";
+        let policy: Policy = text.parse().unwrap();
+        assert_eq!(
+            policy,
+            Policy::and(vec![
+                Policy::threshold(
+                    2,
+                    vec![
+                        Policy::participant("alice"),
+                        Policy::participant("bob"),
+                        Policy::participant("carol"),
+                    ]
+                ),
+                Policy::or(vec![
+                    Policy::participant("dave"),
+                    Policy::participant("erin")
+                ]),
+                Policy::participant("frank"),
+            ])
+        );
+        assert_eq!(policy.to_string(), text);
+        assert!("and(alice, bob".parse::<Policy>().is_err());
+        assert!("xof(alice, bob)".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn join_when_satisfied() {
+        let policy: Policy = "and(2of(alice, bob, carol), or(dave, erin))"
+            .parse()
+            .unwrap();
+        let secret = b"the launch codes";
+        let shares = policy.split(secret).unwrap();
+        assert_eq!(
+            policy.participants(),
+            shares
+                .iter()
+                .map(|share| share.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        for names in [
+            &["alice", "bob", "dave"][..],
+            &["bob", "carol", "erin"],
+            &["alice", "bob", "carol", "dave", "erin"],
+        ] {
+            assert_eq!(policy.join(&shares_of(&shares, names)).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn report_missing() {
+        let policy: Policy = "and(2of(alice, bob, carol), or(dave, erin))"
+            .parse()
+            .unwrap();
+        let shares = policy.split(b"the launch codes").unwrap();
+        match policy.join(&shares_of(&shares, &["alice", "carol", "bob"])) {
+            Err(SplitSecretError::PolicyUnsatisfied { missing }) => assert_eq!(
+                missing,
+                vec![
+                    "and(2of(alice, bob, carol), or(dave, erin)) needs 1 more of: or(dave, erin)",
+                    "or(dave, erin) needs 1 more of: dave, erin",
+                ]
+            ),
+            _ => panic!("expected the policy to be unsatisfied"),
+        }
+    }
+
+    #[test]
+    fn invalid_policies() {
+        for text in ["and(alice, alice)", "3of(alice, bob)", "0of(alice)"] {
+            let policy: Policy = text.parse().unwrap();
+            assert!(policy.split(b"secret").is_err());
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let policy: Policy = "or(alice, 2of(bob, carol, dave))".parse().unwrap();
+        let shares = policy.split(b"secret").unwrap();
+        let mut buf = Vec::new();
+        shares[2].write(&mut buf).unwrap();
+        assert_eq!(PolicyShare::read(&buf[..], 0).unwrap(), shares[2]);
+
+        // Damage to the value, or to a header that still parses, is caught by the digest.
+        for i in [buf.len() - 40, 6] {
+            let mut damaged = buf.clone();
+            damaged[i] ^= 1;
+            assert!(matches!(
+                PolicyShare::read(&damaged[..], 3),
+                Err(SplitSecretError::CorruptShare { input: 3, x: None })
+            ));
+        }
+    }
+}
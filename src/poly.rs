@@ -1,11 +1,15 @@
-use galois_2p8::Field;
+use crate::field::GaloisField;
 
 /* Computes the Lagrange basis polynomials for xs[0..k] and evaluates them at each eval_xs.
  * For each x in eval_xs, this function returns the value of the k+1 Lagrange polynomials evaluated
  * at that x.
  */
-pub fn lagrange_eval(field: &dyn Field, xs: &[u8], eval_xs: &[u8]) -> Vec<Vec<u8>> {
-    let mut lagrange_denominator = vec![1u8; xs.len()];
+pub fn lagrange_eval<F: GaloisField>(
+    field: &F,
+    xs: &[F::Element],
+    eval_xs: &[F::Element],
+) -> Vec<Vec<F::Element>> {
+    let mut lagrange_denominator = vec![F::one(); xs.len()];
     for (i, x1) in xs.iter().enumerate() {
         for x2 in xs.iter() {
             if x1 == x2 {
@@ -17,22 +21,22 @@ pub fn lagrange_eval(field: &dyn Field, xs: &[u8], eval_xs: &[u8]) -> Vec<Vec<u8
     eval_xs
         .iter()
         .map(|eval_x| {
-            let mut numerator = 1u8;
+            let mut numerator = F::one();
             for x in xs.iter() {
                 numerator = field.mult(numerator, field.sub(*eval_x, *x));
             }
             xs.iter()
                 .enumerate()
                 .map(|(i, x)| {
-                    if numerator == 0u8 {
+                    if numerator == F::zero() {
                         if *x == *eval_x {
-                            1u8
+                            F::one()
                         } else {
-                            0u8
+                            F::zero()
                         }
                     } else {
                         field.div(
-                            field.div(numerator, field.sub(*x, *eval_x)),
+                            field.div(numerator, field.sub(*eval_x, *x)),
                             lagrange_denominator[i],
                         )
                     }
@@ -42,14 +46,128 @@ pub fn lagrange_eval(field: &dyn Field, xs: &[u8], eval_xs: &[u8]) -> Vec<Vec<u8
         .collect()
 }
 
+/* Evaluates the polynomial with the given coefficients, lowest degree first, at x.
+ */
+pub fn poly_eval<F: GaloisField>(
+    field: &F,
+    coefficients: &[F::Element],
+    x: F::Element,
+) -> F::Element {
+    coefficients
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, c| field.add(field.mult(acc, x), *c))
+}
+
+/* Finds the polynomial of degree less than k that passes through all but at most
+ * (xs.len() - k) / 2 of the points (xs[i], ys[i]), using the Berlekamp-Welch algorithm, and
+ * returns its k coefficients, lowest degree first. Returns None if there is no such polynomial.
+ *
+ * With e = (xs.len() - k) / 2, this solves ys[i] * E(xs[i]) = Q(xs[i]) for a monic E of degree e
+ * and a Q of degree less than k + e; the polynomial is then Q / E.
+ */
+pub fn berlekamp_welch<F: GaloisField>(
+    field: &F,
+    xs: &[F::Element],
+    ys: &[F::Element],
+    k: usize,
+) -> Option<Vec<F::Element>> {
+    let e = (xs.len() - k) / 2;
+    let unknowns = k + 2 * e;
+
+    // One row per point: the coefficients of E below x^e, then those of Q, then the right-hand
+    // side, which comes from the leading 1 of E.
+    let mut rows: Vec<Vec<F::Element>> = xs
+        .iter()
+        .zip(ys.iter())
+        .map(|(x, y)| {
+            let mut row = Vec::with_capacity(unknowns + 1);
+            let mut power = F::one();
+            for _ in 0..e {
+                row.push(field.sub(F::zero(), field.mult(*y, power)));
+                power = field.mult(power, *x);
+            }
+            let rhs = field.mult(*y, power);
+            power = F::one();
+            for _ in 0..k + e {
+                row.push(power);
+                power = field.mult(power, *x);
+            }
+            row.push(rhs);
+            row
+        })
+        .collect();
+
+    // Gauss-Jordan elimination; free unknowns are left at zero, since any solution will do.
+    let mut pivots = Vec::new();
+    for column in 0..unknowns {
+        let pivot_row = pivots.len();
+        let found = match (pivot_row..rows.len()).find(|r| rows[*r][column] != F::zero()) {
+            Some(found) => found,
+            None => continue,
+        };
+        rows.swap(pivot_row, found);
+        let inverse = field.div(F::one(), rows[pivot_row][column]);
+        for value in rows[pivot_row].iter_mut() {
+            *value = field.mult(*value, inverse);
+        }
+        let pivot = rows[pivot_row].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != pivot_row && row[column] != F::zero() {
+                let scale = row[column];
+                for (value, p) in row.iter_mut().zip(pivot.iter()) {
+                    *value = field.sub(*value, field.mult(*p, scale));
+                }
+            }
+        }
+        pivots.push(column);
+    }
+    if rows[pivots.len()..]
+        .iter()
+        .any(|row| row[unknowns] != F::zero())
+    {
+        return None;
+    }
+    let mut solution = vec![F::zero(); unknowns];
+    for (row, column) in rows.iter().zip(pivots.iter()) {
+        solution[*column] = row[unknowns];
+    }
+
+    // Divide Q by E, which must leave no remainder.
+    let mut error_locator = solution[..e].to_vec();
+    error_locator.push(F::one());
+    let mut remainder = solution[e..].to_vec();
+    let mut quotient = vec![F::zero(); k];
+    for i in (0..k).rev() {
+        let scale = remainder[i + e];
+        quotient[i] = scale;
+        for (j, c) in error_locator.iter().enumerate() {
+            remainder[i + j] = field.sub(remainder[i + j], field.mult(*c, scale));
+        }
+    }
+    if remainder.iter().any(|c| *c != F::zero()) {
+        return None;
+    }
+
+    let errors = xs
+        .iter()
+        .zip(ys.iter())
+        .filter(|(x, y)| poly_eval(field, &quotient, **x) != **y)
+        .count();
+    if errors > e {
+        return None;
+    }
+    Some(quotient)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use galois_2p8::{IrreducablePolynomial, PrimitivePolynomialField};
+    use crate::field::{Gf256, Gf65536};
 
     #[test]
     fn test_lagrange() {
-        let field = PrimitivePolynomialField::new_might_panic(IrreducablePolynomial::Poly84320);
+        let field = Gf256::new();
         let result = lagrange_eval(
             &field,
             &[1u8, 2u8, 3u8, 4u8, 5u8],
@@ -68,4 +186,47 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_berlekamp_welch() {
+        let field = Gf256::new();
+        let coefficients = [7u8, 200u8, 13u8];
+        let xs = [1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8];
+        let mut ys: Vec<u8> = xs
+            .iter()
+            .map(|x| poly_eval(&field, &coefficients, *x))
+            .collect();
+        assert_eq!(
+            berlekamp_welch(&field, &xs, &ys, 3),
+            Some(coefficients.to_vec())
+        );
+
+        // Up to (7 - 3) / 2 = 2 errors can be corrected.
+        ys[1] ^= 0x55;
+        ys[5] ^= 0x01;
+        assert_eq!(
+            berlekamp_welch(&field, &xs, &ys, 3),
+            Some(coefficients.to_vec())
+        );
+
+        // With a single extra point, an error can only be detected.
+        assert_eq!(berlekamp_welch(&field, &xs[..4], &ys[..4], 3), None);
+    }
+
+    #[test]
+    fn test_berlekamp_welch_gf65536() {
+        let field = Gf65536::new();
+        let coefficients = [7u16, 40000u16, 1300u16];
+        let xs: Vec<u16> = (300u16..307).collect();
+        let mut ys: Vec<u16> = xs
+            .iter()
+            .map(|x| poly_eval(&field, &coefficients, *x))
+            .collect();
+        ys[0] ^= 0x5555;
+        ys[6] ^= 0x0100;
+        assert_eq!(
+            berlekamp_welch(&field, &xs, &ys, 3),
+            Some(coefficients.to_vec())
+        );
+    }
 }
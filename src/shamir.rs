@@ -1,121 +1,412 @@
-use std::cmp;
+use std::collections::BTreeSet;
 use std::io::{Read, Write};
 
-use crate::partitioner::{InputPartition, OutputPartition, Partitioner};
+use crate::error::{Result, SplitSecretError};
+use crate::field::{
+    decode_secret, encode_elements, indices, pad_to_elements, unpad_from_elements, GaloisField,
+    Gf256,
+};
+use crate::partitioner::{
+    check_indices, check_outputs, decode_shares, read_shares, repair_elementwise, InputPartition,
+    JoinReport, OutputPartition, Partitioner,
+};
+use crate::pipeline::{chunk_size, default_threads, read_chunk, run_pipeline, Pool};
+use crate::poly::{berlekamp_welch, lagrange_eval, poly_eval};
 
-use galois_2p8::{Field, IrreducablePolynomial, PrimitivePolynomialField};
-use rand::rngs::OsRng;
-use rand::RngCore;
-
-pub struct Shamir {
+/// Shamir's secret sharing over a [`GaloisField`], GF(2^8) unless chosen otherwise, applied
+/// independently to every element of the input.
+///
+/// Every share is as long as the input, and any `k - 1` shares reveal nothing about it. Over
+/// fields with elements of more than one byte, the input is first padded to a whole number of
+/// elements.
+///
+/// A join needs any `k` shares. Every surplus share is checked against the polynomial through the
+/// first `k`, so a share altered by a dishonest participant is noticed: given `m` surplus shares,
+/// up to `m / 2` altered shares are corrected and reported, and beyond that the join fails with
+/// [`SplitSecretError::InconsistentShares`] instead of producing a wrong secret.
+///
+/// Shares can be refreshed, which changes every share but not the secret, and a lost share can be
+/// regenerated from `k` others.
+///
+/// Splits and joins of large inputs are spread over one thread per core, or as many as
+/// [`Shamir::with_threads`] says.
+pub struct Shamir<F: GaloisField = Gf256> {
     k: u8,
-    base: IrreducablePolynomial,
+    field: F,
+    pool: Pool,
 }
 
 impl Shamir {
-    pub fn new(k: u8) -> Self {
-        assert!(k > 1);
-        return Shamir {
-            k: k,
-            base: IrreducablePolynomial::Poly84320,
-        };
+    /// Creates a scheme over GF(2^8) where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        Shamir::with_field(k)
     }
 }
 
-const BUF_SIZE: usize = 1024;
+impl<F: GaloisField> Shamir<F> {
+    /// Creates a scheme over `F` where any `k` shares are required to reconstruct the input.
+    pub fn with_field(k: u8) -> Result<Self> {
+        if k < 2 {
+            return Err(SplitSecretError::InvalidThreshold(k));
+        }
+        Ok(Shamir {
+            k: k,
+            field: F::new(),
+            pool: Pool::new(default_threads()),
+        })
+    }
 
-impl Partitioner for Shamir {
-    fn split<R: Read, W: Write>(&self, mut input: R, outputs: &mut [OutputPartition<W>]) {
-        let n = outputs.len() as u8;
-        assert!(n >= self.k);
-        // TODO: check that all the indicies in the outputs are unique
+    /// Splits and joins on `threads` threads; 1 keeps all the work on the calling thread. The
+    /// output is laid out the same either way.
+    ///
+    /// The threads are started by the first split or join that needs them and kept until the
+    /// scheme is dropped.
+    pub fn with_threads(self, threads: usize) -> Self {
+        Shamir {
+            pool: Pool::new(threads),
+            ..self
+        }
+    }
+}
 
-        let field = PrimitivePolynomialField::new_might_panic(self.base);
+// Adds a random polynomial of degree k - 1 with a zero constant term to the first len elements of
+// the shares at xs.
+fn add_random_polynomial<F: GaloisField>(
+    field: &F,
+    k: u8,
+    shares: &mut [Vec<F::Element>],
+    xs: &[F::Element],
+    len: usize,
+) {
+    let mut coefficients = vec![F::zero(); len];
+    let mut powers = vec![F::one(); xs.len()];
+    for _i in 1u8..=k - 1 {
+        for (power, x) in powers.iter_mut().zip(xs.iter()) {
+            *power = field.mult(*power, *x);
+        }
+        field.random(&mut coefficients);
+        for (share, scale) in shares.iter_mut().zip(powers.iter()) {
+            field.add_scaled(&mut share[..len], &coefficients, *scale);
+        }
+    }
+}
 
-        let mut read_buf: [u8; BUF_SIZE] = [0u8; BUF_SIZE];
-        let mut write_bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; n.into()];
-        let mut coefficients_buf: [u8; BUF_SIZE] = [0u8; BUF_SIZE];
+// Shares a chunk of the padded input among the outputs at output_xs, with threshold k.
+fn split_chunk<F: GaloisField>(
+    field: &F,
+    k: u8,
+    chunk: &[u8],
+    output_xs: &[F::Element],
+) -> Result<Vec<Vec<u8>>> {
+    let mut secret = vec![F::zero(); chunk.len() / F::ELEMENT_SIZE];
+    let len = decode_secret::<F>(chunk, &mut secret)?;
+    let mut shares = vec![secret; output_xs.len()];
+    add_random_polynomial(field, k, &mut shares, output_xs, len);
+    Ok(shares
+        .iter()
+        .map(|share| {
+            let mut bytes = vec![0u8; chunk.len()];
+            encode_elements::<F>(share, &mut bytes);
+            bytes
+        })
+        .collect())
+}
 
-        loop {
-            match input.read(&mut read_buf) {
-                Err(_) | Ok(0) => break,
-                Ok(read_size) => {
-                    let slice = &read_buf[0..read_size];
-                    for write_buf in write_bufs.iter_mut() {
-                        write_buf[0..read_size].copy_from_slice(slice);
-                    }
-                    let mut xs = vec![1u8; n.into()];
-                    for _i in 1u8..=self.k - 1 {
-                        for (x, output) in xs.iter_mut().zip(outputs.iter()) {
-                            *x = field.mult(*x, output.x);
-                        }
-                        OsRng.fill_bytes(&mut coefficients_buf[0..read_size]);
-                        for (write_buf, scale) in write_bufs.iter_mut().zip(xs.iter()) {
-                            field.add_scaled_multiword(
-                                &mut write_buf[0..read_size],
-                                &coefficients_buf[0..read_size],
-                                *scale,
-                            );
-                        }
-                    }
-                    for (write_buf, output) in write_bufs.iter().zip(outputs.iter_mut()) {
-                        output
-                            .writer
-                            .write(&write_buf[0..read_size])
-                            .expect("write failed");
-                    }
+// Combines chunks of the inputs of a join into chunks of the secret.
+struct Combiner<F: GaloisField> {
+    field: F,
+    k: usize,
+    input_xs: Vec<u16>,
+    xs: Vec<F::Element>,
+    // The secret is combined from the first k inputs with these coefficients, and the rest are
+    // checked against the first k with the check coefficients.
+    coefficients: Vec<F::Element>,
+    check: Vec<Vec<F::Element>>,
+}
+
+impl<F: GaloisField> Combiner<F> {
+    fn new(field: &F, k: u8, input_xs: Vec<u16>) -> Result<Self> {
+        let k_usize = usize::from(k);
+        let xs = indices::<F>(input_xs.iter().copied())?;
+        let check = lagrange_eval(field, &xs[..k_usize], &xs[k_usize..]);
+        let mut coefficients = Vec::new();
+        for x in xs[..k_usize].iter() {
+            let mut coefficient = F::one();
+            for other_x in xs[..k_usize].iter() {
+                if other_x == x {
+                    continue;
                 }
+                coefficient = field.mult(coefficient, field.div(*other_x, field.sub(*other_x, *x)));
             }
+            coefficients.push(coefficient);
         }
+        Ok(Combiner {
+            field: field.clone(),
+            k: k_usize,
+            input_xs,
+            xs,
+            coefficients,
+            check,
+        })
     }
 
-    fn join<R: Read, W: Write>(&self, inputs: &mut [InputPartition<R>], mut output: W) {
-        assert!(inputs.len() == self.k.into());
+    // Returns the secret in read_size bytes of every input, and the indices of the inputs that
+    // had errors in them.
+    fn combine(&self, bufs: &[Vec<u8>], read_size: usize) -> Result<(Vec<u8>, Vec<u16>)> {
+        let field = &self.field;
+        let mut shares = vec![vec![F::zero(); read_size / F::ELEMENT_SIZE]; bufs.len()];
+        let len = decode_shares::<F, _>(&self.input_xs, bufs, read_size, &mut shares)?;
 
-        let field = PrimitivePolynomialField::new_might_panic(self.base);
-
-        let mut read_bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; self.k.into()];
-        let mut write_buf: [u8; BUF_SIZE] = [0u8; BUF_SIZE];
+        let mut secret = vec![F::zero(); len];
+        for (share, scale) in shares.iter().zip(self.coefficients.iter()) {
+            field.add_scaled(&mut secret, share, *scale);
+        }
 
-        let mut combine_coefficients: Vec<u8> = Vec::new();
-        for input in inputs.iter() {
-            let mut coefficient = 1u8;
-            for other_input in inputs.iter() {
-                if other_input.x == input.x {
+        let mut corrupted = Vec::new();
+        let mut predicted = vec![F::zero(); len];
+        let mut ys = vec![F::zero(); shares.len()];
+        let (data_shares, surplus_shares) = shares.split_at(self.k);
+        for (surplus_share, check_coefficients) in surplus_shares.iter().zip(self.check.iter()) {
+            predicted.fill(F::zero());
+            for (share, scale) in data_shares.iter().zip(check_coefficients.iter()) {
+                field.add_scaled(&mut predicted, share, *scale);
+            }
+            for i in 0..len {
+                if predicted[i] == surplus_share[i] {
                     continue;
                 }
-                coefficient = field.mult(
-                    coefficient,
-                    field.div(other_input.x, field.sub(input.x, other_input.x)),
-                );
+                // Some share is off the polynomial at this element; find the polynomial that
+                // most shares agree on, if there are enough of them to tell.
+                for (y, share) in ys.iter_mut().zip(shares.iter()) {
+                    *y = share[i];
+                }
+                let coefficients = berlekamp_welch(field, &self.xs, &ys, self.k)
+                    .ok_or(SplitSecretError::InconsistentShares)?;
+                for (input_x, (x, y)) in self.input_xs.iter().zip(self.xs.iter().zip(ys.iter())) {
+                    if poly_eval(field, &coefficients, *x) != *y {
+                        corrupted.push(*input_x);
+                    }
+                }
+                secret[i] = coefficients[0];
             }
-            combine_coefficients.push(coefficient);
         }
 
-        loop {
-            let mut read_size = BUF_SIZE;
-            for (input, read_buf) in inputs.iter_mut().zip(read_bufs.iter_mut()) {
-                match input.reader.read(read_buf) {
-                    Err(_) => {
-                        read_size = 0;
-                        break;
-                    }
-                    Ok(n) => read_size = cmp::min(read_size, n),
+        let mut bytes = vec![0u8; read_size];
+        encode_elements::<F>(&secret, &mut bytes);
+        Ok((bytes, corrupted))
+    }
+}
+
+const BUF_SIZE: usize = 1024;
+
+impl<F: GaloisField> Partitioner for Shamir<F> {
+    fn split<R: Read, W: Write>(&self, input: R, outputs: &mut [OutputPartition<W>]) -> Result<()> {
+        check_indices(self.k, outputs.iter().map(|output| output.x))?;
+        let output_xs = indices::<F>(outputs.iter().map(|output| output.x))?;
+        let mut input = pad_to_elements::<F, _>(input);
+        let chunk_size = chunk_size(outputs.len(), F::ELEMENT_SIZE);
+
+        let field = self.field.clone();
+        let k = self.k;
+        run_pipeline(
+            &self.pool,
+            || read_chunk(&mut input, chunk_size),
+            move |chunk| split_chunk(&field, k, &chunk, &output_xs),
+            |shares| {
+                for (share, output) in shares.iter().zip(outputs.iter_mut()) {
+                    output.writer.write_all(share)?;
                 }
-            }
+                Ok(())
+            },
+        )
+    }
+
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: W,
+    ) -> Result<JoinReport> {
+        check_indices(self.k, inputs.iter().map(|input| input.x))?;
+        let combiner = Combiner::new(
+            &self.field,
+            self.k,
+            inputs.iter().map(|input| input.x).collect(),
+        )?;
+        let mut output = unpad_from_elements::<F, _>(output);
+        let chunk_size = chunk_size(inputs.len(), F::ELEMENT_SIZE);
+
+        let mut corrupted = BTreeSet::new();
+        run_pipeline(
+            &self.pool,
+            || {
+                let mut bufs = vec![vec![0u8; chunk_size]; inputs.len()];
+                match read_shares(inputs, &mut bufs)? {
+                    0 => Ok(None),
+                    read_size => Ok(Some((bufs, read_size))),
+                }
+            },
+            move |(bufs, read_size)| combiner.combine(&bufs, read_size),
+            |(secret, chunk_corrupted)| {
+                output.write_all(&secret)?;
+                corrupted.extend(chunk_corrupted);
+                Ok(())
+            },
+        )?;
+        output.flush()?;
+        Ok(JoinReport {
+            corrupted: corrupted.into_iter().collect(),
+        })
+    }
+
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: OutputPartition<W>,
+    ) -> Result<()> {
+        repair_elementwise(&self.field, self.k, inputs, output)
+    }
+
+    fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        check_indices(self.k, inputs.iter().map(|input| input.x))?;
+        check_outputs(inputs.len(), outputs.len())?;
+        let input_xs = indices::<F>(inputs.iter().map(|input| input.x))?;
+
+        let input_indices: Vec<u16> = inputs.iter().map(|input| input.x).collect();
+
+        let elements = BUF_SIZE / F::ELEMENT_SIZE;
+        let mut bufs: Vec<[u8; BUF_SIZE]> = vec![[0u8; BUF_SIZE]; inputs.len()];
+        let mut shares = vec![vec![F::zero(); elements]; inputs.len()];
+
+        loop {
+            let read_size = read_shares(inputs, &mut bufs)?;
             if read_size == 0 {
                 break;
             }
+            let len = decode_shares::<F, _>(&input_indices, &bufs, read_size, &mut shares)?;
+            add_random_polynomial(&self.field, self.k, &mut shares, &input_xs, len);
+            for ((buf, share), output) in bufs.iter_mut().zip(shares.iter()).zip(outputs.iter_mut())
+            {
+                encode_elements::<F>(&share[..len], &mut buf[..read_size]);
+                output.write_all(&buf[0..read_size])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+mod nonblocking {
+    use std::io::Write;
+
+    use super::*;
+    use crate::async_partitioner::{AsyncPartitioner, Blocks, JoinDecoder, SplitEncoder, Units};
+    use crate::padding_streaming::{Op, PaddedWriter};
+
+    use block_padding::Iso7816;
+
+    // Like pad_to_elements, but a piece at a time.
+    fn padded<F: GaloisField>() -> bool {
+        F::ELEMENT_SIZE > 1 && F::DENSE
+    }
+
+    struct Encoder<'a, F: GaloisField> {
+        shamir: &'a Shamir<F>,
+        xs: Vec<F::Element>,
+        blocks: Blocks<Iso7816>,
+    }
+
+    impl<'a, F: GaloisField> Encoder<'a, F> {
+        fn encode(&self, chunk: &[u8], outputs: &mut [Vec<u8>]) -> Result<()> {
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            let shares = split_chunk(&self.shamir.field, self.shamir.k, chunk, &self.xs)?;
+            for (share, output) in shares.iter().zip(outputs.iter_mut()) {
+                output.extend_from_slice(share);
+            }
+            Ok(())
+        }
+    }
+
+    impl<'a, F: GaloisField> SplitEncoder for Encoder<'a, F> {
+        fn update(&mut self, input: &[u8], outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.update(input);
+            self.encode(&chunk, outputs)
+        }
+
+        fn finish(&mut self, outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.finish();
+            self.encode(&chunk, outputs)
+        }
+    }
+
+    struct Decoder<F: GaloisField> {
+        combiner: Combiner<F>,
+        units: Units,
+        // Holds back the last element to unpad, like unpad_from_elements.
+        unpad: Option<PaddedWriter<Iso7816, Vec<u8>>>,
+        corrupted: BTreeSet<u16>,
+    }
+
+    impl<F: GaloisField> JoinDecoder for Decoder<F> {
+        fn update(&mut self, inputs: &[&[u8]], output: &mut Vec<u8>) -> Result<()> {
+            let bufs = self.units.update(inputs);
+            if bufs[0].is_empty() {
+                return Ok(());
+            }
+            let (secret, corrupted) = self.combiner.combine(&bufs, bufs[0].len())?;
+            self.corrupted.extend(corrupted);
+            match &mut self.unpad {
+                Some(writer) => {
+                    writer.write_all(&secret)?;
+                    output.append(writer.get_mut());
+                }
+                None => output.extend_from_slice(&secret),
+            }
+            Ok(())
+        }
 
-            write_buf.fill(0u8);
-            for (read_buf, scale) in read_bufs.iter().zip(combine_coefficients.iter()) {
-                field.add_scaled_multiword(
-                    &mut write_buf[0..read_size],
-                    &read_buf[0..read_size],
-                    *scale,
-                );
+        fn finish(&mut self, output: &mut Vec<u8>) -> Result<JoinReport> {
+            let input_xs = &self.combiner.input_xs;
+            if self.units.truncated() {
+                return Err(SplitSecretError::TruncatedShare { x: input_xs[0] });
+            }
+            if let Some(writer) = &mut self.unpad {
+                writer.flush()?;
+                output.append(writer.get_mut());
             }
-            output.write(&write_buf[0..read_size]).unwrap();
+            Ok(JoinReport {
+                corrupted: self.corrupted.iter().copied().collect(),
+            })
+        }
+    }
+
+    impl<F: GaloisField> AsyncPartitioner for Shamir<F> {
+        fn split_encoder(&self, xs: &[u16]) -> Result<Box<dyn SplitEncoder + Send + '_>> {
+            check_indices(self.k, xs.iter().copied())?;
+            Ok(Box::new(Encoder {
+                shamir: self,
+                xs: indices::<F>(xs.iter().copied())?,
+                blocks: Blocks::new(F::ELEMENT_SIZE, padded::<F>()),
+            }))
+        }
+
+        fn join_decoder(&self, xs: &[u16]) -> Result<Box<dyn JoinDecoder + Send + '_>> {
+            check_indices(self.k, xs.iter().copied())?;
+            Ok(Box::new(Decoder {
+                combiner: Combiner::new(&self.field, self.k, xs.to_vec())?,
+                units: Units::new(F::ELEMENT_SIZE, xs.len()),
+                unpad: if padded::<F>() {
+                    Some(PaddedWriter::new(F::ELEMENT_SIZE, Vec::new(), Op::Unpad))
+                } else {
+                    None
+                },
+                corrupted: BTreeSet::new(),
+            }))
         }
     }
 }
@@ -123,13 +414,14 @@ impl Partitioner for Shamir {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::partitioner::test_join;
+    use crate::field::{Gf2p8, Gf65536, Prime, PrimeField, Secp256k1Order};
+    use crate::partitioner::{test_join, InMemoryPartition};
 
     #[test]
     fn two_of_three() {
         let plaintext: Vec<u8> = "hello world".as_bytes().into();
-        let shamir = Shamir::new(2);
-        let mut partitions = shamir.split_in_memory(&plaintext, 3);
+        let shamir = Shamir::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
             assert_eq!(plaintext.len(), partition.value.len());
@@ -140,12 +432,243 @@ mod tests {
     #[test]
     fn five_of_ten() {
         let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
-        let shamir = Shamir::new(5);
-        let mut partitions = shamir.split_in_memory(&plaintext, 10);
+        let shamir = Shamir::new(5).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 10).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
             assert!(plaintext.len() == partition.value.len());
         }
         test_join(&shamir, &mut partitions[..], 5, &plaintext);
     }
+
+    #[test]
+    fn invalid_threshold() {
+        assert!(matches!(
+            Shamir::new(1),
+            Err(SplitSecretError::InvalidThreshold(1))
+        ));
+    }
+
+    #[test]
+    fn duplicate_index() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        partitions[1].x = partitions[0].x;
+        let (first, rest) = partitions.split_at_mut(1);
+        assert!(matches!(
+            shamir.join_in_memory(&mut [&mut first[0], &mut rest[0]]),
+            Err(SplitSecretError::DuplicateIndex(1))
+        ));
+    }
+
+    #[test]
+    fn truncated_share() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        partitions[1].value.pop();
+        let (first, rest) = partitions.split_at_mut(1);
+        assert!(matches!(
+            shamir.join_in_memory(&mut [&mut first[0], &mut rest[0]]),
+            Err(SplitSecretError::TruncatedShare { x: 2 })
+        ));
+    }
+
+    fn join_all(
+        shamir: &Shamir,
+        partitions: &[InMemoryPartition],
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        join_all_in(shamir, partitions)
+    }
+
+    fn join_all_in<F: GaloisField>(
+        shamir: &Shamir<F>,
+        partitions: &[InMemoryPartition],
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        let mut inputs: Vec<_> = partitions
+            .iter()
+            .map(|partition| InputPartition {
+                x: partition.x,
+                reader: &partition.value[..],
+            })
+            .collect();
+        let mut output = Vec::new();
+        let report = shamir.join(&mut inputs, &mut output)?;
+        Ok((output, report))
+    }
+
+    #[test]
+    fn refresh() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        let mut refreshed = vec![Vec::new(); 3];
+        shamir
+            .refresh(
+                &mut partitions
+                    .iter()
+                    .map(|partition| InputPartition {
+                        x: partition.x,
+                        reader: &partition.value[..],
+                    })
+                    .collect::<Vec<_>>(),
+                &mut refreshed,
+            )
+            .unwrap();
+        let mut refreshed: Vec<_> = partitions
+            .iter()
+            .zip(refreshed)
+            .map(|(partition, value)| {
+                assert_ne!(partition.value, value);
+                InMemoryPartition {
+                    x: partition.x,
+                    value,
+                }
+            })
+            .collect();
+        test_join(&shamir, &mut refreshed[..], 2, &plaintext);
+
+        // Every share needs an output.
+        assert!(matches!(
+            shamir.refresh(
+                &mut partitions
+                    .iter()
+                    .map(|partition| InputPartition {
+                        x: partition.x,
+                        reader: &partition.value[..],
+                    })
+                    .collect::<Vec<_>>(),
+                &mut vec![Vec::new(); 2],
+            ),
+            Err(SplitSecretError::OutputCount {
+                inputs: 3,
+                outputs: 2
+            })
+        ));
+
+        // An old share does not combine with a new one.
+        let (old, _) = partitions.split_at_mut(1);
+        let (_, new) = refreshed.split_at_mut(1);
+        assert_ne!(
+            shamir
+                .join_in_memory(&mut [&mut old[0], &mut new[0]])
+                .unwrap(),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn cheater_detected() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::new(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 4).unwrap();
+        assert_eq!(join_all(&shamir, &partitions).unwrap().0, plaintext);
+
+        // With one surplus share, an altered share is noticed but cannot be identified. This
+        // holds even when it is among the first k.
+        partitions[0].value[3] ^= 0x20;
+        assert!(matches!(
+            join_all(&shamir, &partitions),
+            Err(SplitSecretError::InconsistentShares)
+        ));
+    }
+
+    #[test]
+    fn cheater_corrected() {
+        let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
+        let shamir = Shamir::new(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 7).unwrap();
+        for byte in partitions[1].value.iter_mut() {
+            *byte = 0;
+        }
+        partitions[5].value[7] ^= 1;
+        let (output, report) = join_all(&shamir, &partitions).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [2, 6]);
+    }
+
+    #[test]
+    fn gf65536() {
+        // An odd length leaves a partial element to pad.
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::<Gf65536>::with_field(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 300).unwrap();
+        assert_eq!(partitions[299].x, 300);
+        let mut inputs: Vec<_> = partitions.iter_mut().skip(255).take(3).collect();
+        assert_eq!(shamir.join_in_memory(&mut inputs).unwrap(), plaintext);
+
+        partitions[260].value[4] ^= 1;
+        let (output, report) = join_all_in(&shamir, &partitions[255..262]).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [261]);
+    }
+
+    #[test]
+    fn threads() {
+        // Enough input for several chunks, spread over the threads.
+        let plaintext: Vec<u8> = (0..1_000_000u32)
+            .map(|i| (i * 31 + i / 997) as u8)
+            .collect();
+        let sequential = Shamir::new(3).unwrap().with_threads(1);
+        let parallel = Shamir::new(3).unwrap().with_threads(4);
+        let mut partitions = parallel.split_in_memory(&plaintext, 5).unwrap();
+        partitions[1].value[300_000] ^= 1;
+        for shamir in [&sequential, &parallel] {
+            let (output, report) = join_all_in(shamir, &partitions).unwrap();
+            assert_eq!(output, plaintext);
+            assert_eq!(report.corrupted, [2]);
+        }
+    }
+
+    #[test]
+    fn other_polynomial() {
+        // The AES polynomial gives different shares for the same secret and coefficients, but
+        // the scheme works the same.
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = Shamir::<Gf2p8<0x11b>>::with_field(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 5).unwrap();
+        test_join(&shamir, &mut partitions[..], 3, &plaintext);
+
+        partitions[0].value[2] ^= 1;
+        let (output, report) = join_all_in(&shamir, &partitions).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [1]);
+    }
+
+    #[test]
+    fn prime_field() {
+        // Two secp256k1 private keys.
+        let mut plaintext = vec![0xffu8; 64];
+        plaintext[0] = 0xfe;
+        plaintext[32] = 0x01;
+        let shamir = Shamir::<PrimeField<Secp256k1Order>>::with_field(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 5).unwrap();
+        for partition in partitions.iter() {
+            assert_eq!(partition.value.len(), 64);
+        }
+        test_join(&shamir, &mut partitions[..], 3, &plaintext);
+
+        partitions[3].value[40] ^= 1;
+        let (output, report) = join_all_in(&shamir, &partitions).unwrap();
+        assert_eq!(output, plaintext);
+        assert_eq!(report.corrupted, [4]);
+
+        // Secrets are whole numbers below the modulus.
+        for secret in [&Secp256k1Order::MODULUS[..], &plaintext[..40]] {
+            assert!(matches!(
+                shamir.split_in_memory(secret, 5),
+                Err(SplitSecretError::InvalidSecret(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn index_too_large_for_gf256() {
+        let shamir = Shamir::new(2).unwrap();
+        assert!(matches!(
+            shamir.split_in_memory(b"secret", 256),
+            Err(SplitSecretError::InvalidIndex(256))
+        ));
+    }
 }
@@ -1,23 +1,222 @@
-use std::io::{Cursor, Read, Take, Write};
+use std::io::{Read, Take, Write};
 use std::marker::PhantomData;
 
 use crate::block_mode_streaming::{DecryptWriteStream, EncryptReadStream};
+use crate::error::{Result, SplitSecretError};
+use crate::feldman::Feldman;
 use crate::ida::Ida;
-use crate::partitioner::{InputPartition, OutputPartition, Partitioner};
+use crate::partitioner::{InputPartition, JoinReport, OutputPartition, Partitioner};
 use crate::shamir::Shamir;
 
 use block_padding::RawPadding;
 use cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use rand::rngs::OsRng;
 
+// How the hybrid schemes share their key: plain Shamir, or Feldman's verifiable scheme so that
+// holders can check their key share against the dealer's commitments.
+pub(crate) enum KeySharing {
+    Shamir(Shamir),
+    Feldman(Feldman),
+}
+
+impl KeySharing {
+    pub(crate) fn new(k: u8, verifiable: bool) -> Result<Self> {
+        Ok(if verifiable {
+            KeySharing::Feldman(Feldman::new(k)?)
+        } else {
+            KeySharing::Shamir(Shamir::new(k)?)
+        })
+    }
+
+    // Key sharing of the same kind with a different threshold.
+    pub(crate) fn with_threshold(&self, k: u8) -> Result<Self> {
+        Self::new(k, matches!(self, KeySharing::Feldman(_)))
+    }
+
+    // Size of each share of a key of `key_size` bytes.
+    pub(crate) fn share_size(&self, key_size: usize) -> usize {
+        match self {
+            KeySharing::Shamir(_) => key_size,
+            KeySharing::Feldman(feldman) => feldman.share_size(),
+        }
+    }
+
+    pub(crate) fn split<W: Write>(
+        &self,
+        key: &[u8],
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        match self {
+            KeySharing::Shamir(shamir) => shamir.split(key, outputs),
+            KeySharing::Feldman(feldman) => feldman.split(key, outputs),
+        }
+    }
+
+    // Calls `f` with the inputs cut short after their shares of a key of `key_size` bytes, so
+    // that the readers are left just past them.
+    fn with_key_shares<R: Read, T>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        key_size: usize,
+        f: impl FnOnce(&mut [InputPartition<&mut Take<&mut R>>]) -> Result<T>,
+    ) -> Result<T> {
+        let share_size = self.share_size(key_size) as u64;
+        let mut limited_inputs: Vec<(u16, Take<_>)> = inputs
+            .iter_mut()
+            .map(|input| (input.x, (&mut input.reader).take(share_size)))
+            .collect();
+        let mut limited_inputs: Vec<_> = limited_inputs
+            .iter_mut()
+            .map(|(x, reader)| InputPartition { x: *x, reader })
+            .collect();
+        f(&mut limited_inputs)
+    }
+
+    // Joins the key from the start of each input, leaving the readers just past it.
+    pub(crate) fn join<R: Read>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        key_size: usize,
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        let mut key = Vec::new();
+        let report = self.with_key_shares(inputs, key_size, |key_shares| match self {
+            KeySharing::Shamir(shamir) => shamir.join(key_shares, &mut key),
+            KeySharing::Feldman(feldman) => feldman.join(key_shares, &mut key),
+        })?;
+        if key.len() != key_size {
+            return Err(SplitSecretError::TruncatedShare { x: inputs[0].x });
+        }
+        Ok((key, report))
+    }
+
+    // Refreshes the key share at the start of each input, leaving the readers just past it.
+    pub(crate) fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+        key_size: usize,
+    ) -> Result<()> {
+        self.with_key_shares(inputs, key_size, |key_shares| match self {
+            KeySharing::Shamir(shamir) => shamir.refresh(key_shares, outputs),
+            KeySharing::Feldman(feldman) => feldman.refresh(key_shares, outputs),
+        })
+    }
+
+    // Repairs the key share at the start of the output, leaving the readers just past the key
+    // shares they were read from.
+    pub(crate) fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: &mut OutputPartition<W>,
+        key_size: usize,
+    ) -> Result<()> {
+        let output = OutputPartition {
+            x: output.x,
+            writer: &mut output.writer,
+        };
+        self.with_key_shares(inputs, key_size, |key_shares| match self {
+            KeySharing::Shamir(shamir) => shamir.repair(key_shares, output),
+            KeySharing::Feldman(feldman) => feldman.repair(key_shares, output),
+        })
+    }
+}
+
+// What the hybrid schemes have in common: every share is a share of a key of `key_size` bytes,
+// IV or nonce prefix included, followed by an IDA share of the ciphertext. Only the split and join
+// depend on the cipher; the shares are refreshed, repaired and reshared without decrypting the
+// ciphertext.
+pub(crate) struct Hybrid<P: RawPadding> {
+    pub(crate) key_sharing: KeySharing,
+    pub(crate) ida: Ida<P>,
+    key_size: usize,
+}
+
+impl<P: RawPadding> Hybrid<P> {
+    pub(crate) fn new(k: u8, verifiable: bool, key_size: usize) -> Result<Self> {
+        Ok(Hybrid {
+            key_sharing: KeySharing::new(k, verifiable)?,
+            ida: Ida::new(k)?,
+            key_size,
+        })
+    }
+
+    // Each split draws a fresh key, whose shares go ahead of the ciphertext.
+    pub(crate) fn split<R: Read, W: Write>(
+        &self,
+        key: &[u8],
+        ciphertext: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        self.key_sharing.split(key, outputs)?;
+        self.ida.split(ciphertext, outputs)
+    }
+
+    // Joins the key from the start of each input, leaving the readers at the ciphertext.
+    pub(crate) fn join_key<R: Read>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+    ) -> Result<(Vec<u8>, JoinReport)> {
+        self.key_sharing.join(inputs, self.key_size)
+    }
+
+    // Only the key is secret shared; the ciphertext stays as it is.
+    pub(crate) fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        self.key_sharing.refresh(inputs, outputs, self.key_size)?;
+        for (input, output) in inputs.iter_mut().zip(outputs.iter_mut()) {
+            std::io::copy(&mut input.reader, output)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: OutputPartition<W>,
+    ) -> Result<()> {
+        self.key_sharing
+            .repair(inputs, &mut output, self.key_size)?;
+        self.ida.repair(inputs, output)
+    }
+
+    // The key is shared again, and the ciphertext is decoded and dispersed again without being
+    // decrypted.
+    pub(crate) fn reshare<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        k: u8,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        let (key, _) = self.join_key(inputs)?;
+        let ida = Ida::<P>::new(k)?;
+        self.key_sharing.with_threshold(k)?.split(&key, outputs)?;
+        ida.split(self.ida.join_reader(inputs)?, outputs)
+    }
+}
+
+/// Encrypts the input with a random key and IV, then disperses the ciphertext with [`Ida`] and
+/// shares the key and IV with [`Shamir`].
+///
+/// Every share starts with its Shamir share of the key and IV, followed by its IDA share of the
+/// ciphertext, so shares stay close to `1/k` the size of the input while remaining confidential.
+///
+/// With [`ShamirIda::new_verifiable`], the key and IV are shared with [`Feldman`] instead, so
+/// that every holder can check their share of them against the dealer's commitments.
+///
+/// Refreshing the shares re-randomizes only the key and IV; the dispersed ciphertext is copied
+/// unchanged. A lost share can be repaired from `k` others. The shares can also be reshared with a
+/// different threshold or number of shares, which re-encodes the ciphertext without decrypting
+/// it.
 pub struct ShamirIda<E, D, P>
 where
     E: KeyIvInit + BlockEncryptMut,
     D: KeyIvInit + BlockDecryptMut,
     P: RawPadding,
 {
-    shamir: Shamir,
-    ida: Ida<P>,
+    hybrid: Hybrid<P>,
     _e: PhantomData<E>,
     _d: PhantomData<D>,
     _p: PhantomData<P>,
@@ -29,15 +228,24 @@ where
     D: KeyIvInit + BlockDecryptMut,
     P: RawPadding,
 {
-    pub fn new(k: u8) -> Self {
-        assert!(k > 1);
-        return ShamirIda {
-            shamir: Shamir::new(k),
-            ida: Ida::new(k),
+    /// Creates a scheme where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, false)
+    }
+
+    /// Like [`ShamirIda::new`], but shares the key and IV with Feldman's verifiable secret
+    /// sharing.
+    pub fn new_verifiable(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, true)
+    }
+
+    fn with_key_sharing(k: u8, verifiable: bool) -> Result<Self> {
+        Ok(ShamirIda {
+            hybrid: Hybrid::new(k, verifiable, D::key_size() + D::iv_size())?,
             _e: PhantomData,
             _d: PhantomData,
             _p: PhantomData,
-        };
+        })
     }
 }
 
@@ -47,51 +255,210 @@ where
     D: KeyIvInit + BlockDecryptMut,
     P: RawPadding,
 {
-    fn split<R: Read, W: Write>(&self, mut input: R, outputs: &mut [OutputPartition<W>]) {
+    fn split<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
         let (key, iv) = <E as KeyIvInit>::generate_key_iv(OsRng);
         let cipher = E::new(&key, &iv);
         let mut input: EncryptReadStream<E, P, &mut R> = EncryptReadStream::new(cipher, &mut input);
 
-        // Write the key using Shamir's secret sharing
-        self.shamir.split(&mut Cursor::new(key), outputs);
-        self.shamir.split(&mut Cursor::new(iv), outputs);
-
-        // Write the input using IDA
-        self.ida.split(&mut input, outputs);
+        self.hybrid
+            .split(&[&key[..], &iv[..]].concat(), &mut input, outputs)
     }
 
-    fn join<R: Read, W: Write>(&self, inputs: &mut [InputPartition<R>], mut output: W) {
-        let mut key = Vec::new();
-        let mut limited_inputs: Vec<(u8, Take<_>)> = inputs
-            .iter_mut()
-            .map(|input| {
-                (
-                    input.x,
-                    (&mut input.reader).take((D::key_size() + D::iv_size()) as u64),
-                )
-            })
-            .collect();
-        self.shamir.join(
-            &mut limited_inputs
-                .iter_mut()
-                .map(|(x, reader)| InputPartition { x: *x, reader })
-                .collect::<Vec<_>>(),
-            &mut key,
-        );
-        debug_assert!(key.len() == D::key_size() + D::iv_size());
-
-        let cipher = D::new_from_slices(&key[..D::key_size()], &key[D::key_size()..]).unwrap();
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let (key, mut report) = self.hybrid.join_key(inputs)?;
+
+        let cipher = D::new_from_slices(&key[..D::key_size()], &key[D::key_size()..])
+            .expect("key and IV lengths were checked above");
         let mut output: DecryptWriteStream<D, P, &mut W> =
             DecryptWriteStream::new(cipher, &mut output);
-        self.ida.join(inputs, &mut output);
-        output.flush().unwrap();
+        report.merge(self.hybrid.ida.join(inputs, &mut output)?);
+        output.flush()?;
+        Ok(report)
+    }
+
+    fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        self.hybrid.refresh(inputs, outputs)
+    }
+
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: OutputPartition<W>,
+    ) -> Result<()> {
+        self.hybrid.repair(inputs, output)
+    }
+
+    fn reshare<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        k: u8,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        self.hybrid.reshare(inputs, k, outputs)
+    }
+}
+
+#[cfg(feature = "async")]
+mod nonblocking {
+    use super::*;
+    use crate::async_partitioner::{AsyncPartitioner, Blocks, JoinDecoder, SplitEncoder};
+    use crate::block_mode_streaming::to_blocks;
+
+    use cipher::{BlockSizeUser, Unsigned};
+
+    struct Encoder<'a, E: BlockEncryptMut, P: RawPadding> {
+        // Written to the start of every share before anything else.
+        key_shares: Vec<Vec<u8>>,
+        cipher: E,
+        blocks: Blocks<P>,
+        ida: Box<dyn SplitEncoder + Send + 'a>,
+    }
+
+    impl<'a, E: BlockEncryptMut, P: RawPadding> Encoder<'a, E, P> {
+        fn encode(&mut self, mut chunk: Vec<u8>, outputs: &mut [Vec<u8>]) -> Result<()> {
+            for (key_share, output) in self.key_shares.drain(..).zip(outputs.iter_mut()) {
+                output.extend_from_slice(&key_share);
+            }
+            self.cipher.encrypt_blocks_mut(to_blocks(&mut chunk));
+            self.ida.update(&chunk, outputs)
+        }
+    }
+
+    impl<'a, E: BlockEncryptMut, P: RawPadding> SplitEncoder for Encoder<'a, E, P> {
+        fn update(&mut self, input: &[u8], outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.update(input);
+            self.encode(chunk, outputs)
+        }
+
+        fn finish(&mut self, outputs: &mut [Vec<u8>]) -> Result<()> {
+            let chunk = self.blocks.finish();
+            self.encode(chunk, outputs)?;
+            self.ida.finish(outputs)
+        }
+    }
+
+    struct Decoder<'a, D: KeyIvInit + BlockDecryptMut, P: RawPadding> {
+        key_sharing: &'a KeySharing,
+        xs: Vec<u16>,
+        // The start of every input, until there is enough of them to join the key and IV.
+        key_shares: Vec<Vec<u8>>,
+        ida: Box<dyn JoinDecoder + Send + 'a>,
+        output: Option<DecryptWriteStream<D, P, Vec<u8>>>,
+        report: JoinReport,
+    }
+
+    impl<'a, D: KeyIvInit + BlockDecryptMut, P: RawPadding> JoinDecoder for Decoder<'a, D, P> {
+        fn update(&mut self, inputs: &[&[u8]], output: &mut Vec<u8>) -> Result<()> {
+            let mut inputs = inputs.to_vec();
+            if self.output.is_none() {
+                let key_size = D::key_size() + D::iv_size();
+                let share_size = self.key_sharing.share_size(key_size);
+                let take = (share_size - self.key_shares[0].len()).min(inputs[0].len());
+                for (key_share, input) in self.key_shares.iter_mut().zip(inputs.iter_mut()) {
+                    key_share.extend_from_slice(&input[..take]);
+                    *input = &input[take..];
+                }
+                if self.key_shares[0].len() < share_size {
+                    return Ok(());
+                }
+                let (key, report) = self.key_sharing.join(
+                    &mut self
+                        .xs
+                        .iter()
+                        .zip(self.key_shares.iter())
+                        .map(|(x, key_share)| InputPartition {
+                            x: *x,
+                            reader: &key_share[..],
+                        })
+                        .collect::<Vec<_>>(),
+                    key_size,
+                )?;
+                self.report = report;
+                let cipher = D::new_from_slices(&key[..D::key_size()], &key[D::key_size()..])
+                    .expect("key and IV lengths were checked by the join");
+                self.output = Some(DecryptWriteStream::new(cipher, Vec::new()));
+            }
+
+            let mut ciphertext = Vec::new();
+            self.ida.update(&inputs, &mut ciphertext)?;
+            let writer = self.output.as_mut().expect("key was joined above");
+            writer.write_all(&ciphertext)?;
+            output.append(writer.get_mut());
+            Ok(())
+        }
+
+        fn finish(&mut self, output: &mut Vec<u8>) -> Result<JoinReport> {
+            let writer = match &mut self.output {
+                Some(writer) => writer,
+                None => return Err(SplitSecretError::TruncatedShare { x: self.xs[0] }),
+            };
+            let mut ciphertext = Vec::new();
+            let report = self.ida.finish(&mut ciphertext)?;
+            writer.write_all(&ciphertext)?;
+            writer.flush()?;
+            output.append(writer.get_mut());
+            self.report.merge(report);
+            Ok(std::mem::take(&mut self.report))
+        }
+    }
+
+    impl<E, D, P> AsyncPartitioner for ShamirIda<E, D, P>
+    where
+        E: KeyIvInit + BlockEncryptMut + Send + Sync,
+        D: KeyIvInit + BlockDecryptMut + Send + Sync,
+        P: RawPadding + Send + Sync,
+    {
+        fn split_encoder(&self, xs: &[u16]) -> Result<Box<dyn SplitEncoder + Send + '_>> {
+            let (key, iv) = <E as KeyIvInit>::generate_key_iv(OsRng);
+            let mut key_shares = vec![Vec::new(); xs.len()];
+            self.hybrid.key_sharing.split(
+                &[&key[..], &iv[..]].concat(),
+                &mut xs
+                    .iter()
+                    .zip(key_shares.iter_mut())
+                    .map(|(x, key_share)| OutputPartition {
+                        x: *x,
+                        writer: key_share,
+                    })
+                    .collect::<Vec<_>>(),
+            )?;
+            Ok(Box::new(Encoder {
+                key_shares,
+                cipher: E::new(&key, &iv),
+                blocks: Blocks::<P>::new(<E as BlockSizeUser>::BlockSize::USIZE, true),
+                ida: self.hybrid.ida.split_encoder(xs)?,
+            }))
+        }
+
+        fn join_decoder(&self, xs: &[u16]) -> Result<Box<dyn JoinDecoder + Send + '_>> {
+            Ok(Box::new(Decoder::<D, P> {
+                key_sharing: &self.hybrid.key_sharing,
+                xs: xs.to_vec(),
+                key_shares: vec![Vec::new(); xs.len()],
+                ida: self.hybrid.ida.join_decoder(xs)?,
+                output: None,
+                report: JoinReport::default(),
+            }))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::partitioner::test_join;
+    use crate::partitioner::{test_join, InMemoryPartition};
 
     use aes::{Aes128, Aes256};
     use block_padding::{Iso7816, Pkcs7};
@@ -103,8 +470,8 @@ mod tests {
         P: RawPadding,
     {
         let plaintext: Vec<u8> = "hello world".as_bytes().into();
-        let shamir = ShamirIda::<E, D, P>::new(2);
-        let mut partitions = shamir.split_in_memory(&plaintext, 3);
+        let shamir = ShamirIda::<E, D, P>::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
         }
@@ -124,11 +491,140 @@ mod tests {
     #[test]
     fn five_of_ten() {
         let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
-        let shamir = ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new(5);
-        let mut partitions = shamir.split_in_memory(&plaintext, 10);
+        let shamir =
+            ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new(5).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 10).unwrap();
         for partition in partitions.iter() {
             assert_ne!(plaintext, partition.value);
         }
         test_join(&shamir, &mut partitions[..], 5, &plaintext);
     }
+
+    #[test]
+    fn refresh() {
+        let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
+        for shamir in [
+            ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new(2).unwrap(),
+            ShamirIda::new_verifiable(2).unwrap(),
+        ] {
+            let partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+            let mut refreshed = vec![Vec::new(); 3];
+            shamir
+                .refresh(
+                    &mut partitions
+                        .iter()
+                        .map(|partition| InputPartition {
+                            x: partition.x,
+                            reader: &partition.value[..],
+                        })
+                        .collect::<Vec<_>>(),
+                    &mut refreshed,
+                )
+                .unwrap();
+            let mut refreshed: Vec<_> = partitions
+                .iter()
+                .zip(refreshed)
+                .map(|(partition, value)| {
+                    // The ciphertext is unchanged.
+                    let key_share_size = shamir.hybrid.key_sharing.share_size(48);
+                    assert_ne!(partition.value[..key_share_size], value[..key_share_size]);
+                    assert_eq!(partition.value[key_share_size..], value[key_share_size..]);
+                    InMemoryPartition {
+                        x: partition.x,
+                        value,
+                    }
+                })
+                .collect();
+            test_join(&shamir, &mut refreshed[..], 2, &plaintext);
+        }
+    }
+
+    #[test]
+    fn repair() {
+        let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
+        for shamir in [
+            ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new(3).unwrap(),
+            ShamirIda::new_verifiable(3).unwrap(),
+        ] {
+            let partitions = shamir.split_in_memory(&plaintext, 5).unwrap();
+            let mut repaired = Vec::new();
+            shamir
+                .repair(
+                    &mut [&partitions[4], &partitions[0], &partitions[2]]
+                        .iter()
+                        .map(|partition| InputPartition {
+                            x: partition.x,
+                            reader: &partition.value[..],
+                        })
+                        .collect::<Vec<_>>(),
+                    OutputPartition {
+                        x: 2,
+                        writer: &mut repaired,
+                    },
+                )
+                .unwrap();
+            assert_eq!(partitions[1].value, repaired);
+        }
+    }
+
+    #[test]
+    fn reshare() {
+        let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
+        for shamir in [
+            ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Pkcs7>::new(3).unwrap(),
+            ShamirIda::new_verifiable(3).unwrap(),
+        ] {
+            let partitions = shamir.split_in_memory(&plaintext, 5).unwrap();
+            let mut reshared: Vec<_> = (1u16..=7)
+                .map(|x| InMemoryPartition {
+                    x,
+                    value: Vec::new(),
+                })
+                .collect();
+            shamir
+                .reshare(
+                    &mut partitions[2..]
+                        .iter()
+                        .map(|partition| InputPartition {
+                            x: partition.x,
+                            reader: &partition.value[..],
+                        })
+                        .collect::<Vec<_>>(),
+                    4,
+                    &mut reshared
+                        .iter_mut()
+                        .map(|partition| OutputPartition {
+                            x: partition.x,
+                            writer: &mut partition.value,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .unwrap();
+            let new_shamir: ShamirIda<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Pkcs7> =
+                match shamir.hybrid.key_sharing {
+                    KeySharing::Shamir(_) => ShamirIda::new(4).unwrap(),
+                    KeySharing::Feldman(_) => ShamirIda::new_verifiable(4).unwrap(),
+                };
+            test_join(&new_shamir, &mut reshared[..], 4, &plaintext);
+        }
+    }
+
+    #[test]
+    fn verifiable() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir =
+            ShamirIda::<cbc::Encryptor<Aes256>, cbc::Decryptor<Aes256>, Iso7816>::new_verifiable(3)
+                .unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 4).unwrap();
+        let feldman = Feldman::new(3).unwrap();
+        for partition in partitions.iter() {
+            feldman
+                .verify_share(InputPartition {
+                    x: partition.x,
+                    reader: &partition.value[..],
+                })
+                .unwrap();
+        }
+        test_join(&shamir, &mut partitions[..], 3, &plaintext);
+    }
 }
@@ -0,0 +1,256 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use crate::aead_streaming::{AeadDecryptWriteStream, AeadEncryptReadStream, NONCE_PREFIX_SIZE};
+use crate::error::{Result, SplitSecretError};
+use crate::partitioner::{InputPartition, JoinReport, OutputPartition, Partitioner};
+use crate::shamir_ida::Hybrid;
+
+use aead::consts::U12;
+use aead::generic_array::GenericArray;
+use aead::stream::{NewStream, StreamBE32, StreamPrimitive};
+use aead::{AeadCore, AeadInPlace, KeyInit};
+use block_padding::RawPadding;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// Like [`crate::ShamirIda`], but encrypts with an AEAD in the STREAM construction, so that a
+/// join fails instead of producing garbage when the dispersed ciphertext has been modified,
+/// truncated or reordered.
+///
+/// Every share starts with its Shamir share of the key and nonce prefix, followed by its IDA
+/// share of the ciphertext. As with [`crate::ShamirIda::new_verifiable`], the key and nonce
+/// prefix can be shared verifiably with [`ShamirIdaAead::new_verifiable`].
+pub struct ShamirIdaAead<A, P>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    P: RawPadding,
+{
+    hybrid: Hybrid<P>,
+    _a: PhantomData<A>,
+}
+
+impl<A, P> ShamirIdaAead<A, P>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    P: RawPadding,
+{
+    /// Creates a scheme where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, false)
+    }
+
+    /// Like [`ShamirIdaAead::new`], but shares the key and nonce prefix with Feldman's verifiable
+    /// secret sharing.
+    pub fn new_verifiable(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, true)
+    }
+
+    fn with_key_sharing(k: u8, verifiable: bool) -> Result<Self> {
+        Ok(ShamirIdaAead {
+            hybrid: Hybrid::new(k, verifiable, Self::key_size())?,
+            _a: PhantomData,
+        })
+    }
+
+    // The key and nonce prefix are shared together.
+    fn key_size() -> usize {
+        A::key_size() + NONCE_PREFIX_SIZE
+    }
+
+    /// Draws a key and nonce prefix for the chunks of a split; see
+    /// [`ShamirIdaAead::split_chunk`].
+    pub(crate) fn new_chunk_key() -> Vec<u8> {
+        let mut key = A::generate_key(OsRng).to_vec();
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        key.extend_from_slice(&nonce_prefix);
+        key
+    }
+
+    // The STREAM that the chunks of a split are sealed in, one segment each.
+    fn chunk_stream(key: &[u8]) -> StreamBE32<A> {
+        let aead = A::new_from_slice(&key[..A::key_size()])
+            .expect("keys are drawn and joined at this length");
+        StreamBE32::from_aead(aead, GenericArray::from_slice(&key[A::key_size()..]))
+    }
+
+    /// Splits chunk `index` of a longer input, sealed under `key` as segment `index` of one
+    /// STREAM over the whole input, the final segment if `last`.
+    ///
+    /// The shares of every chunk start with a sharing of the same key, so that any chunk can be
+    /// joined without the others, yet a join of chunks that were dropped, reordered or taken from
+    /// another split fails.
+    pub(crate) fn split_chunk<R: Read, W: Write>(
+        &self,
+        key: &[u8],
+        index: u64,
+        last: bool,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        let mut chunk = Vec::new();
+        input.read_to_end(&mut chunk)?;
+        Self::chunk_stream(key)
+            .encrypt_in_place(segment(index)?, last, &[], &mut chunk)
+            .map_err(|_| SplitSecretError::Authentication)?;
+        self.hybrid.split(key, &chunk[..], outputs)
+    }
+
+    /// Joins the key that the chunks of a split were sealed under from the shares of one of
+    /// them, to resume the split.
+    pub(crate) fn join_chunk_key<R: Read>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+    ) -> Result<Vec<u8>> {
+        Ok(self.hybrid.join_key(inputs)?.0)
+    }
+
+    /// Joins chunk `index` of a longer input split by [`ShamirIdaAead::split_chunk`]. Nothing is
+    /// written unless the chunk is authenticated as segment `index` of its STREAM, and as the
+    /// final one exactly if `last`.
+    ///
+    /// The chunk is opened with `key` if given, and otherwise with its own, which is stored in
+    /// `key` for the chunks after it. A chunk taken from another split then fails too.
+    pub(crate) fn join_chunk<R: Read, W: Write>(
+        &self,
+        key: &mut Option<Vec<u8>>,
+        index: u64,
+        last: bool,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let (chunk_key, mut report) = self.hybrid.join_key(inputs)?;
+        let key = key.get_or_insert(chunk_key);
+        let mut chunk = Vec::new();
+        report.merge(self.hybrid.ida.join(inputs, &mut chunk)?);
+        Self::chunk_stream(key)
+            .decrypt_in_place(segment(index)?, last, &[], &mut chunk)
+            .map_err(|_| SplitSecretError::Authentication)?;
+        output.write_all(&chunk)?;
+        output.flush()?;
+        Ok(report)
+    }
+}
+
+// The STREAM counter of chunk `index`. An input with more chunks than the counter can number
+// cannot be sealed, so a chunk claiming a larger index is not authentic.
+fn segment(index: u64) -> Result<u32> {
+    u32::try_from(index).map_err(|_| SplitSecretError::Authentication)
+}
+
+impl<A, P> Partitioner for ShamirIdaAead<A, P>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    P: RawPadding,
+{
+    fn split<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        let key = A::generate_key(OsRng);
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        let mut input = AeadEncryptReadStream::new(A::new(&key), &nonce_prefix, &mut input);
+
+        self.hybrid
+            .split(&[&key[..], &nonce_prefix[..]].concat(), &mut input, outputs)
+    }
+
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let (key, mut report) = self.hybrid.join_key(inputs)?;
+
+        let aead = A::new_from_slice(&key[..A::key_size()]).expect("key length was checked above");
+        let mut output = AeadDecryptWriteStream::new(aead, &key[A::key_size()..], &mut output);
+        report.merge(self.hybrid.ida.join(inputs, &mut output)?);
+        output.flush()?;
+        Ok(report)
+    }
+
+    fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        self.hybrid.refresh(inputs, outputs)
+    }
+
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: OutputPartition<W>,
+    ) -> Result<()> {
+        self.hybrid.repair(inputs, output)
+    }
+
+    fn reshare<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        k: u8,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        self.hybrid.reshare(inputs, k, outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partitioner::test_join;
+
+    use aes_gcm::Aes256Gcm;
+    use block_padding::{Iso7816, Pkcs7};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    fn base_two_of_three<A, P>()
+    where
+        A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+        P: RawPadding,
+    {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = ShamirIdaAead::<A, P>::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        for partition in partitions.iter() {
+            assert_ne!(plaintext, partition.value);
+        }
+        test_join(&shamir, &mut partitions[..], 2, &plaintext);
+    }
+
+    #[test]
+    fn two_of_three_aes_gcm_iso() {
+        base_two_of_three::<Aes256Gcm, Iso7816>();
+    }
+
+    #[test]
+    fn two_of_three_chacha20_poly1305_pkcs() {
+        base_two_of_three::<ChaCha20Poly1305, Pkcs7>();
+    }
+
+    #[test]
+    fn verifiable() {
+        let plaintext = vec![7u8; 5000];
+        let shamir = ShamirIdaAead::<ChaCha20Poly1305, Iso7816>::new_verifiable(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        test_join(&shamir, &mut partitions[..], 2, &plaintext);
+    }
+
+    #[test]
+    fn tampered_share() {
+        let plaintext = vec![42u8; 20000];
+        let shamir = ShamirIdaAead::<Aes256Gcm, Iso7816>::new(3).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 5).unwrap();
+        partitions[1].value[1000] ^= 0x80;
+        let (first, rest) = partitions.split_at_mut(1);
+        let (second, rest) = rest.split_at_mut(1);
+        assert!(matches!(
+            shamir.join_in_memory(&mut [&mut first[0], &mut second[0], &mut rest[0]]),
+            Err(SplitSecretError::Authentication)
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::{Read, Seek, Write};
+use std::marker::PhantomData;
+
+use crate::error::Result;
+use crate::partitioner::{InputPartition, JoinReport, OutputPartition, Partitioner};
+use crate::shamir_ida::Hybrid;
+use crate::stream_cipher_streaming::{StreamCipherReadStream, StreamCipherWriteStream};
+
+use block_padding::RawPadding;
+use cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use rand::rngs::OsRng;
+
+/// Like [`crate::ShamirIda`], but encrypts with a seekable stream cipher such as AES in counter
+/// mode, so that any range of the original can be joined without decoding everything before it;
+/// see [`Partitioner::join_range`].
+///
+/// Every share starts with its Shamir share of the key and IV, followed by its IDA share of the
+/// ciphertext, which is exactly as long as the input. As with
+/// [`crate::ShamirIda::new_verifiable`], the key and IV can be shared verifiably with
+/// [`ShamirIdaCtr::new_verifiable`]. Nothing detects a modified ciphertext beyond what IDA
+/// corrects; see [`crate::ShamirIdaAead`] for that.
+pub struct ShamirIdaCtr<C, P>
+where
+    C: KeyIvInit + StreamCipher + StreamCipherSeek,
+    P: RawPadding,
+{
+    hybrid: Hybrid<P>,
+    _c: PhantomData<C>,
+}
+
+impl<C, P> ShamirIdaCtr<C, P>
+where
+    C: KeyIvInit + StreamCipher + StreamCipherSeek,
+    P: RawPadding,
+{
+    /// Creates a scheme where any `k` shares are required to reconstruct the input.
+    pub fn new(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, false)
+    }
+
+    /// Like [`ShamirIdaCtr::new`], but shares the key and IV with Feldman's verifiable secret
+    /// sharing.
+    pub fn new_verifiable(k: u8) -> Result<Self> {
+        Self::with_key_sharing(k, true)
+    }
+
+    fn with_key_sharing(k: u8, verifiable: bool) -> Result<Self> {
+        Ok(ShamirIdaCtr {
+            hybrid: Hybrid::new(k, verifiable, C::key_size() + C::iv_size())?,
+            _c: PhantomData,
+        })
+    }
+
+    // The cipher for the key and IV joined from the inputs.
+    fn cipher(key: &[u8]) -> C {
+        C::new_from_slices(&key[..C::key_size()], &key[C::key_size()..])
+            .expect("key and IV lengths were checked by the join")
+    }
+}
+
+impl<C, P> Partitioner for ShamirIdaCtr<C, P>
+where
+    C: KeyIvInit + StreamCipher + StreamCipherSeek,
+    P: RawPadding,
+{
+    fn split<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        let (key, iv) = C::generate_key_iv(OsRng);
+        let mut input = StreamCipherReadStream::new(C::new(&key, &iv), &mut input);
+
+        self.hybrid
+            .split(&[&key[..], &iv[..]].concat(), &mut input, outputs)
+    }
+
+    fn join<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let (key, mut report) = self.hybrid.join_key(inputs)?;
+        let mut output = StreamCipherWriteStream::new(Self::cipher(&key), &mut output);
+        report.merge(self.hybrid.ida.join(inputs, &mut output)?);
+        output.flush()?;
+        Ok(report)
+    }
+
+    fn join_range<R: Read + Seek, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        offset: u64,
+        len: u64,
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let (key, mut report) = self.hybrid.join_key(inputs)?;
+        // Only the part of the ciphertext covering the range is read, and decrypted from its
+        // position in the keystream.
+        let mut cipher = Self::cipher(&key);
+        cipher.seek(offset);
+        let mut output = StreamCipherWriteStream::new(cipher, &mut output);
+        report.merge(
+            self.hybrid
+                .ida
+                .join_range(inputs, offset, len, &mut output)?,
+        );
+        Ok(report)
+    }
+
+    fn refresh<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        outputs: &mut [W],
+    ) -> Result<()> {
+        self.hybrid.refresh(inputs, outputs)
+    }
+
+    fn repair<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        output: OutputPartition<W>,
+    ) -> Result<()> {
+        self.hybrid.repair(inputs, output)
+    }
+
+    fn reshare<R: Read, W: Write>(
+        &self,
+        inputs: &mut [InputPartition<R>],
+        k: u8,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        self.hybrid.reshare(inputs, k, outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partitioner::test_join;
+
+    use std::io::Cursor;
+
+    use aes::{Aes128, Aes256};
+    use block_padding::{Iso7816, Pkcs7};
+    use ctr::Ctr128BE;
+
+    #[test]
+    fn two_of_three() {
+        let plaintext: Vec<u8> = "hello world".as_bytes().into();
+        let shamir = ShamirIdaCtr::<Ctr128BE<Aes128>, Pkcs7>::new(2).unwrap();
+        let mut partitions = shamir.split_in_memory(&plaintext, 3).unwrap();
+        for partition in partitions.iter() {
+            assert_ne!(plaintext, partition.value);
+        }
+        test_join(&shamir, &mut partitions[..], 2, &plaintext);
+    }
+
+    #[test]
+    fn join_range() {
+        let plaintext: Vec<u8> = (0..10_000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+        for shamir in [
+            ShamirIdaCtr::<Ctr128BE<Aes256>, Iso7816>::new(3).unwrap(),
+            ShamirIdaCtr::new_verifiable(3).unwrap(),
+        ] {
+            let partitions = shamir.split_in_memory(&plaintext, 4).unwrap();
+            for (offset, len) in [(0u64, 10_000u64), (17, 1), (4096, 3000), (9990, 100)] {
+                let mut inputs: Vec<_> = partitions[1..]
+                    .iter()
+                    .map(|partition| InputPartition {
+                        x: partition.x,
+                        reader: Cursor::new(&partition.value),
+                    })
+                    .collect();
+                let mut output = Vec::new();
+                shamir
+                    .join_range(&mut inputs, offset, len, &mut output)
+                    .unwrap();
+                let end = (offset + len).min(10_000) as usize;
+                assert_eq!(output, plaintext[offset as usize..end]);
+            }
+        }
+    }
+}
@@ -0,0 +1,1808 @@
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use crate::bundle::{input_lanes, seekable_lanes, LaneReader, LaneWriter, OutputLanes};
+use crate::chunks::{Chunk, ChunkReader, ChunkWriter, CHUNK_SIZE};
+use crate::error::{Result, SplitSecretError};
+use crate::feldman::{Commitments, Feldman};
+use crate::field::{GaloisField, Gf2p8, Gf65536, PrimeField, Secp256k1Order, P25519};
+use crate::header::{Cipher, Field, Padding, Scheme, ShareHeader, HEADER_SIZE};
+use crate::ida::Ida;
+use crate::partitioner::{check_outputs, InputPartition, JoinReport, OutputPartition, Partitioner};
+use crate::pipeline::read_chunk;
+use crate::shamir::Shamir;
+use crate::shamir_ida::ShamirIda;
+use crate::shamir_ida_aead::ShamirIdaAead;
+use crate::shamir_ida_ctr::ShamirIdaCtr;
+
+use aead::consts::U12;
+use aead::{AeadCore, AeadInPlace, KeyInit};
+use aes::{Aes128, Aes256};
+use aes_gcm::Aes256Gcm;
+use block_padding::{Iso7816, Pkcs7, RawPadding};
+use chacha20poly1305::ChaCha20Poly1305;
+use cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, StreamCipher, StreamCipherSeek};
+use ctr::Ctr128BE;
+
+/// A share file being written by [`split_weighted_shares`] for a participant who holds `weight`
+/// shares, with indices `x..x + weight`.
+pub struct WeightedOutput<W: Write> {
+    /// Index of the first share in the file.
+    pub x: u16,
+    /// Number of shares in the file, each of which counts toward the threshold on its own.
+    pub weight: u8,
+    pub writer: W,
+}
+
+/// Writes a share header to each output, then splits `input` with the partitioner that `header`
+/// describes. The `x` of `header` is replaced by the index of each output.
+///
+/// The input is split one [`CHUNK_SIZE`] chunk at a time, each on its own. The AEAD hybrid seals
+/// all chunks under one key as a single STREAM, so that a join fails if chunks were dropped,
+/// reordered or taken from another split; the other hybrid schemes encrypt every chunk under a
+/// key of its own. Every share file holds the shares of each chunk along with a digest (see
+/// [`ChunkWriter`]), so that a join can tell which share was damaged, and a split that was
+/// interrupted can be picked up again with [`resume_split_weighted_shares`].
+pub fn split_shares<R: Read, W: Write>(
+    header: &ShareHeader,
+    input: R,
+    outputs: &mut [OutputPartition<W>],
+) -> Result<()> {
+    let mut weighted_outputs: Vec<_> = outputs
+        .iter_mut()
+        .map(|output| WeightedOutput {
+            x: output.x,
+            weight: 1,
+            writer: &mut output.writer,
+        })
+        .collect();
+    split_weighted_shares(header, input, &mut weighted_outputs)
+}
+
+/// Like [`split_shares`], but each output bundles `weight` consecutive shares into one file, so
+/// that its holder counts as `weight` participants toward the threshold of a join.
+///
+/// The shares of a bundle are interleaved bytewise, so a bundle is `weight` times the size of a
+/// single share. The indices of all bundles must be distinct.
+pub fn split_weighted_shares<R: Read, W: Write>(
+    header: &ShareHeader,
+    input: R,
+    outputs: &mut [WeightedOutput<W>],
+) -> Result<()> {
+    with_partitioner(
+        header,
+        Split {
+            header,
+            input,
+            outputs,
+            index: 0,
+            key: None,
+        },
+    )
+}
+
+/// Picks up a [`split_weighted_shares`] into `outputs` that was interrupted, given the same
+/// `header`, input and outputs. The chunks that every output already holds whole and intact are
+/// kept, and the split continues from the first chunk that any output is missing, reading `input`
+/// from the start of that chunk.
+///
+/// The set identifier of `header` is replaced by the one in the outputs, if any got their header.
+/// Afterwards every output is positioned at the end of its share file, and whatever follows was
+/// left by the interrupted split and should be truncated.
+pub fn resume_split_weighted_shares<R: Read + Seek, W: Read + Write + Seek>(
+    header: &ShareHeader,
+    mut input: R,
+    outputs: &mut [WeightedOutput<W>],
+) -> Result<()> {
+    let mut header = header.clone();
+    let mut found_header = false;
+    let mut files = Vec::new();
+    let mut outputs_chunks = Vec::new();
+    for output in outputs.iter_mut() {
+        output.writer.seek(SeekFrom::Start(0))?;
+        // An output that did not get its whole header has nothing to keep.
+        let file = match ShareHeader::read(&mut output.writer) {
+            Ok(file) => file,
+            Err(_) => {
+                outputs_chunks.push((Vec::new(), false));
+                continue;
+            }
+        };
+        let expected = ShareHeader {
+            x: output.x,
+            weight: output.weight,
+            set_id: file.set_id,
+            ..header.clone()
+        };
+        if file != expected || (found_header && file.set_id != header.set_id) {
+            return Err(SplitSecretError::MismatchedHeader { x: output.x });
+        }
+        header.set_id = file.set_id;
+        found_header = true;
+        outputs_chunks.push(whole_chunks(&mut output.writer, &file));
+        files.push(file);
+    }
+
+    if !outputs_chunks.is_empty() && outputs_chunks.iter().all(|(_, complete)| *complete) {
+        for (output, (ends, _)) in outputs.iter_mut().zip(outputs_chunks.iter()) {
+            output.writer.seek(SeekFrom::Start(ends[ends.len() - 1]))?;
+        }
+        return Ok(());
+    }
+    let index = outputs_chunks
+        .iter()
+        .map(|(ends, _)| ends.len())
+        .min()
+        .unwrap_or(0);
+    let offset = (index * CHUNK_SIZE) as u64;
+    // Had the input ended at or before the offset, the chunk before it would have been the last.
+    if index > 0 && input.seek(SeekFrom::End(0))? <= offset {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "the input is shorter than what was already split",
+        )
+        .into());
+    }
+    input.seek(SeekFrom::Start(offset))?;
+    // The chunks still to be split go under the same key as the ones already written.
+    let key = match index {
+        0 => None,
+        _ => with_partitioner(
+            &header,
+            SplitKey {
+                files: &files,
+                first_chunks: || {
+                    let mut chunks = Vec::new();
+                    for (i, (output, file)) in outputs.iter_mut().zip(files.iter()).enumerate() {
+                        output.writer.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+                        chunks.push(ChunkReader::new(&mut output.writer, file, i).next_chunk()?);
+                    }
+                    Ok(chunks)
+                },
+            },
+        )?,
+    };
+    for (output, (ends, _)) in outputs.iter_mut().zip(outputs_chunks.iter()) {
+        let end = match index {
+            0 => 0,
+            _ => ends[index - 1],
+        };
+        output.writer.seek(SeekFrom::Start(end))?;
+    }
+    with_partitioner(
+        &header,
+        Split {
+            header: &header,
+            input,
+            outputs,
+            index: index as u64,
+            key,
+        },
+    )
+}
+
+// The end of every chunk at the start of a share file that is whole and intact, and whether the
+// last of them ends the share.
+fn whole_chunks<R: Read>(reader: R, header: &ShareHeader) -> (Vec<u64>, bool) {
+    let mut reader = ChunkReader::new(reader, header, 0);
+    let mut ends = Vec::new();
+    let mut end = HEADER_SIZE as u64;
+    while let Ok(chunk) = reader.next_chunk() {
+        end += chunk.frame_size();
+        ends.push(end);
+        if chunk.last {
+            return (ends, true);
+        }
+    }
+    (ends, false)
+}
+
+/// Reads the header of every input, checks that they all come from the same split, then joins
+/// them with the partitioner that the headers describe. A share file bundling several shares
+/// counts as each of them.
+///
+/// All inputs are handed to the partitioner, which may use more than `k` of them to correct
+/// shares with wrong contents; see [`JoinReport`]. A share file whose chunk fails its integrity
+/// check, or that ends before or after the others, is left out of the rest of the join and
+/// reported along with the corrected shares, as long as the others still hold `k` shares.
+/// Otherwise the join fails with [`SplitSecretError::CorruptShare`] naming its position in
+/// `inputs`.
+pub fn join_shares<R: Read, W: Write>(inputs: &mut [R], output: W) -> Result<JoinReport> {
+    let (headers, mut readers) = read_headers(inputs)?;
+    with_partitioner(
+        &headers[0],
+        Join {
+            files: &headers,
+            readers: &mut readers,
+            key: None,
+            output,
+        },
+    )
+}
+
+/// Picks up a [`join_shares`] into `output` that was interrupted. The whole chunks of the
+/// original already in `output` are kept, and the join continues from the next chunk, skipping
+/// over the earlier chunks of the inputs without reading them.
+///
+/// The report covers only the chunks joined now. Afterwards `output` is positioned at the end of
+/// the original, and whatever follows was left by the interrupted join and should be truncated.
+pub fn resume_join_shares<R: Read + Seek, W: Write + Seek>(
+    inputs: &mut [R],
+    mut output: W,
+) -> Result<JoinReport> {
+    let done = output.seek(SeekFrom::End(0))? / CHUNK_SIZE as u64;
+    let (headers, mut readers) = read_headers(inputs)?;
+    let key = first_chunk_key(&headers, &mut readers, done)?;
+    let index = skip_chunks(&headers, &mut readers, done)?;
+    output.seek(SeekFrom::Start(index * CHUNK_SIZE as u64))?;
+    with_partitioner(
+        &headers[0],
+        Join {
+            files: &headers,
+            readers: &mut readers,
+            key,
+            output,
+        },
+    )
+}
+
+/// Joins only the `len` bytes of the original starting at `offset`, writing them to `output`, for
+/// example to restore one file from the shares of a disk image.
+///
+/// The chunks before the one holding `offset` are skipped without being read, and only the
+/// chunks that cover the range are verified. Within the first and last of them, schemes that can
+/// seek decode only the part covering the range; see [`Partitioner::join_range`]. A range that
+/// runs past the end of the original is cut short. The report covers only the chunks joined.
+pub fn join_range_shares<R: Read + Seek, W: Write>(
+    inputs: &mut [R],
+    offset: u64,
+    len: u64,
+    output: W,
+) -> Result<JoinReport> {
+    let (headers, mut readers) = read_headers(inputs)?;
+    let skip = offset / CHUNK_SIZE as u64;
+    let key = first_chunk_key(&headers, &mut readers, skip)?;
+    let index = skip_chunks(&headers, &mut readers, skip)?;
+    with_partitioner(
+        &headers[0],
+        JoinRange {
+            files: &headers,
+            readers: &mut readers,
+            key,
+            position: index * CHUNK_SIZE as u64,
+            start: offset,
+            end: offset.saturating_add(len),
+            output,
+        },
+    )
+}
+
+/// Re-randomizes the shares in `inputs` without changing the original they join to, writing the
+/// new share for `inputs[i]` to `outputs[i]`; see [`Partitioner::refresh`].
+///
+/// The new shares get a new set identifier, so a join refuses to mix them with old ones. A share
+/// file bundling several shares is refreshed into a bundle of the same shares.
+pub fn refresh_shares<R: Read, W: Write>(inputs: &mut [R], outputs: &mut [W]) -> Result<()> {
+    check_outputs(inputs.len(), outputs.len())?;
+    let (headers, mut readers) = read_headers(inputs)?;
+    with_partitioner(
+        &headers[0],
+        Refresh {
+            header: &headers[0].new_split(),
+            files: &headers,
+            readers: &mut readers,
+            outputs,
+        },
+    )
+}
+
+/// Regenerates the share with index `x` from `k` of the shares in `inputs`, writing it to
+/// `output`; see [`Partitioner::repair`].
+///
+/// The regenerated share file is identical to the one written by the original split, header and
+/// digests included, as long as that file held only the share `x`.
+pub fn repair_share<R: Read, W: Write>(inputs: &mut [R], x: u16, output: W) -> Result<()> {
+    let (headers, mut readers) = read_headers(inputs)?;
+    with_partitioner(
+        &headers[0],
+        Repair {
+            header: &ShareHeader {
+                x,
+                weight: 1,
+                ..headers[0].clone()
+            },
+            files: &headers,
+            readers: &mut readers,
+            output,
+        },
+    )
+}
+
+/// Joins `inputs` and splits the original again into a new set of shares with threshold `k`,
+/// one for each of `outputs`; see [`Partitioner::reshare`].
+///
+/// The new shares get a new set identifier, so a join refuses to mix them with old ones.
+pub fn reshare_shares<R: Read, W: Write>(
+    inputs: &mut [R],
+    k: u8,
+    outputs: &mut [OutputPartition<W>],
+) -> Result<()> {
+    let (headers, mut readers) = read_headers(inputs)?;
+    with_partitioner(
+        &headers[0],
+        Reshare {
+            header: &ShareHeader {
+                k,
+                weight: 1,
+                ..headers[0].new_split()
+            },
+            files: &headers,
+            readers: &mut readers,
+            outputs,
+        },
+    )
+}
+
+// Reads the header of every input and checks that they all come from the same split, returning
+// the headers and a reader for the chunks that follow each.
+fn read_headers<R: Read>(inputs: &mut [R]) -> Result<(Vec<ShareHeader>, Vec<ChunkReader<&mut R>>)> {
+    let mut headers: Vec<ShareHeader> = Vec::new();
+    let mut readers = Vec::new();
+    for (i, reader) in inputs.iter_mut().enumerate() {
+        let share_header = ShareHeader::read(reader)?;
+        if let Some(header) = headers.first() {
+            if !header.same_split(&share_header) {
+                return Err(SplitSecretError::MismatchedHeader { x: share_header.x });
+            }
+        }
+        readers.push(ChunkReader::new(reader, &share_header, i));
+        headers.push(share_header);
+    }
+    if headers.is_empty() {
+        return Err(SplitSecretError::ShareCount { k: 0, n: 0 });
+    }
+    Ok((headers, readers))
+}
+
+// The key of the split, for a join that skips `count` chunks of the files first; see SplitKey.
+fn first_chunk_key<R: Read + Seek>(
+    files: &[ShareHeader],
+    readers: &mut [ChunkReader<R>],
+    count: u64,
+) -> Result<Option<Vec<u8>>> {
+    if count == 0 {
+        return Ok(None);
+    }
+    with_partitioner(
+        &files[0],
+        SplitKey {
+            files,
+            first_chunks: || readers.iter_mut().map(ChunkReader::peek_chunk).collect(),
+        },
+    )
+}
+
+// Skips up to `count` chunks of every file, stopping short of the last, and returns how many were
+// skipped.
+fn skip_chunks<R: Read + Seek>(
+    files: &[ShareHeader],
+    readers: &mut [ChunkReader<R>],
+    count: u64,
+) -> Result<u64> {
+    let mut skipped = Vec::new();
+    for reader in readers.iter_mut() {
+        let mut n = 0;
+        while n < count && reader.skip_chunk()? {
+            n += 1;
+        }
+        skipped.push(n);
+    }
+    // Shares of the same split all have the same number of chunks, so one that stops short of
+    // the others claims to end early.
+    let index = skipped.iter().copied().max().unwrap_or(0);
+    if let Some(input) = skipped.iter().position(|n| *n != index) {
+        return Err(SplitSecretError::CorruptShare {
+            input,
+            x: Some(files[input].x),
+        });
+    }
+    Ok(index)
+}
+
+// Reads the next chunk of every file, and returns them with their index and whether they are the
+// last. A file whose chunk is damaged, or that disagrees with most of the others on whether it is
+// the last, is reported as CorruptShare, as skip_chunks does for files that end early.
+//
+// Given `dropped`, such a file is instead left out as long as the files left hold at least k
+// shares: its chunk is None, and it is marked in `dropped` and not read from again.
+fn next_chunks<R: Read>(
+    files: &[ShareHeader],
+    readers: &mut [ChunkReader<R>],
+    dropped: Option<&mut [bool]>,
+) -> Result<(Vec<Option<Chunk>>, u64, bool)> {
+    let mut chunks = Vec::with_capacity(readers.len());
+    let mut damaged = Vec::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if matches!(dropped.as_deref(), Some(dropped) if dropped[i]) {
+            chunks.push(None);
+            continue;
+        }
+        match reader.next_chunk() {
+            Ok(chunk) => chunks.push(Some(chunk)),
+            Err(SplitSecretError::CorruptShare { .. }) => {
+                chunks.push(None);
+                damaged.push(i);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let ending = chunks.iter().flatten().filter(|chunk| chunk.last).count();
+    let last = ending > chunks.iter().flatten().count() - ending;
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        if matches!(chunk, Some(chunk) if chunk.last != last) {
+            *chunk = None;
+            damaged.push(i);
+        }
+    }
+    damaged.sort_unstable();
+
+    if let Some(&input) = damaged.first() {
+        let corrupt = || SplitSecretError::CorruptShare {
+            input,
+            x: Some(files[input].x),
+        };
+        let dropped = dropped.ok_or_else(corrupt)?;
+        for i in damaged {
+            dropped[i] = true;
+        }
+        let shares: usize = files
+            .iter()
+            .zip(dropped.iter())
+            .filter(|(_, dropped)| !**dropped)
+            .map(|(file, _)| usize::from(file.weight))
+            .sum();
+        if shares < usize::from(files[0].k) {
+            return Err(corrupt());
+        }
+    }
+    let index = chunks.iter().flatten().map(|chunk| chunk.index).next();
+    Ok((chunks, index.unwrap_or_default(), last))
+}
+
+// The shares of the files left out of a join by next_chunks, as damaged.
+fn dropped_shares(files: &[ShareHeader], dropped: &[bool]) -> JoinReport {
+    JoinReport {
+        corrupted: files
+            .iter()
+            .zip(dropped.iter())
+            .filter(|(_, dropped)| **dropped)
+            .flat_map(|(file, _)| file.x..file.x + u16::from(file.weight))
+            .collect(),
+    }
+}
+
+// One input per share for the shares in `chunks`, leaving out the files without one. A file
+// bundling several shares becomes several inputs, in order of their indices.
+fn chunk_inputs<'a>(
+    files: &[ShareHeader],
+    chunks: &'a [Option<Chunk>],
+) -> Result<Vec<InputPartition<LaneReader<&'a [u8]>>>> {
+    let mut inputs = Vec::new();
+    for (file, chunk) in files.iter().zip(chunks.iter()) {
+        if let Some(chunk) = chunk {
+            inputs.extend(input_lanes(file.x, file.weight, &chunk.shares[..])?);
+        }
+    }
+    Ok(inputs)
+}
+
+// Like chunk_inputs, with inputs that can seek.
+fn seekable_chunk_inputs(
+    files: &[ShareHeader],
+    chunks: &[Option<Chunk>],
+) -> Result<Vec<InputPartition<Cursor<Vec<u8>>>>> {
+    let mut inputs = Vec::new();
+    for (file, chunk) in files.iter().zip(chunks.iter()) {
+        if let Some(chunk) = chunk {
+            inputs.extend(seekable_lanes(file.x, file.weight, &chunk.shares)?);
+        }
+    }
+    Ok(inputs)
+}
+
+// Reads the chunks of all files in step, and calls f with the inputs for each chunk, its index and
+// whether it is the last. Damaged files are left out as next_chunks describes, if `dropped` is
+// given.
+fn for_each_chunk<R: Read>(
+    files: &[ShareHeader],
+    readers: &mut [ChunkReader<R>],
+    mut dropped: Option<&mut [bool]>,
+    mut f: impl FnMut(&mut [InputPartition<LaneReader<&[u8]>>], u64, bool) -> Result<()>,
+) -> Result<()> {
+    loop {
+        let (chunks, index, last) = next_chunks(files, readers, dropped.as_deref_mut())?;
+        f(&mut chunk_inputs(files, &chunks)?, index, last)?;
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+// Has f write the shares of one chunk to the files with the given indices and weights, each
+// bundle in memory, then writes each as the next chunk of its file.
+fn write_chunk<W: Write>(
+    files: &[(u16, u8)],
+    writers: &mut [ChunkWriter<W>],
+    last: bool,
+    f: impl FnOnce(&mut [OutputPartition<LaneWriter<&mut Vec<u8>>>]) -> Result<()>,
+) -> Result<()> {
+    let mut bundles = vec![Vec::new(); files.len()];
+    let mut lanes = OutputLanes::new();
+    for ((x, weight), bundle) in files.iter().zip(bundles.iter_mut()) {
+        lanes.add(*x, *weight, bundle)?;
+    }
+    f(&mut lanes.outputs)?;
+    lanes.finish()?;
+    for (writer, bundle) in writers.iter_mut().zip(bundles.iter()) {
+        writer.write_chunk(bundle, last)?;
+    }
+    Ok(())
+}
+
+/// Reads the header of a [`Scheme::ShamirIdaVss`] share and checks its key shares against the
+/// commitments they carry. The shares of a bundle all have to carry the same commitments.
+///
+/// Every chunk of the share has its key shared separately, so the returned commitments are those
+/// of all the chunks together. They should be compared with the ones the dealer published (for
+/// example by their [`Commitments::fingerprint`]); a share that is consistent with commitments
+/// nobody else received proves nothing.
+pub fn verify_share<R: Read>(mut input: R) -> Result<(ShareHeader, Commitments)> {
+    let header = ShareHeader::read(&mut input)?;
+    if header.scheme != Scheme::ShamirIdaVss {
+        return Err(SplitSecretError::InvalidHeader(format!(
+            "scheme {} is not verifiable",
+            header.scheme
+        )));
+    }
+    let feldman = Feldman::new(header.k)?;
+    let mut commitments = Vec::new();
+    for_each_chunk(
+        std::slice::from_ref(&header),
+        &mut [ChunkReader::new(input, &header, 0)],
+        None,
+        |inputs, _, _| {
+            // The shares of a bundle were dealt together, so they have to carry the same
+            // commitments, or a lane could be dealt from another polynomial.
+            let mut chunk_commitments: Option<Commitments> = None;
+            for input in inputs.iter_mut() {
+                let lane_commitments = feldman.verify_share(InputPartition {
+                    x: input.x,
+                    reader: &mut input.reader,
+                })?;
+                match &chunk_commitments {
+                    Some(first) if *first != lane_commitments => {
+                        return Err(SplitSecretError::CommitmentMismatch { x: input.x });
+                    }
+                    Some(_) => {}
+                    None => chunk_commitments = Some(lane_commitments),
+                }
+            }
+            commitments.extend(chunk_commitments);
+            Ok(())
+        },
+    )?;
+    Ok((header, Commitments::concat(commitments)))
+}
+
+// Partitioner methods are generic, so the partitioner chosen at runtime is handed to one of these
+// instead of being returned as a trait object.
+trait PartitionerFn {
+    type Output;
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<Self::Output>;
+}
+
+// How a partitioner splits and joins the chunks of a share file. Most split every chunk on its
+// own, but the AEAD hybrid seals all chunks of a split under one key as a single STREAM, so that a
+// join fails if chunks were dropped, reordered or taken from another split.
+trait ChunkPartitioner: Partitioner {
+    // Whether the chunks of a split share a key, which a join that starts past the first chunk
+    // has to get from it.
+    const SPLIT_KEY: bool = false;
+
+    // Draws the key for the chunks of a new split.
+    fn new_split_key(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    // Joins the key of a split from the shares of one of its chunks.
+    fn join_split_key<R: Read>(&self, _inputs: &mut [InputPartition<R>]) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn split_chunk<R: Read, W: Write>(
+        &self,
+        _key: &[u8],
+        _index: u64,
+        _last: bool,
+        input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        self.split(input, outputs)
+    }
+
+    // `key` is the key of the split if known, and is set from the chunk otherwise.
+    fn join_chunk<R: Read, W: Write>(
+        &self,
+        _key: &mut Option<Vec<u8>>,
+        _index: u64,
+        _last: bool,
+        inputs: &mut [InputPartition<R>],
+        output: W,
+    ) -> Result<JoinReport> {
+        self.join(inputs, output)
+    }
+
+    // Like join_chunk, writing only `range` of the chunk.
+    fn join_chunk_range<R: Read + Seek, W: Write>(
+        &self,
+        _key: &mut Option<Vec<u8>>,
+        _index: u64,
+        _last: bool,
+        inputs: &mut [InputPartition<R>],
+        range: Range<u64>,
+        output: W,
+    ) -> Result<JoinReport> {
+        self.join_range(inputs, range.start, range.end - range.start, output)
+    }
+}
+
+impl<G: GaloisField> ChunkPartitioner for Shamir<G> {}
+
+impl<P: RawPadding, G: GaloisField> ChunkPartitioner for Ida<P, G> {}
+
+impl<E, D, P> ChunkPartitioner for ShamirIda<E, D, P>
+where
+    E: KeyIvInit + BlockEncryptMut,
+    D: KeyIvInit + BlockDecryptMut,
+    P: RawPadding,
+{
+}
+
+impl<C, P> ChunkPartitioner for ShamirIdaCtr<C, P>
+where
+    C: KeyIvInit + StreamCipher + StreamCipherSeek,
+    P: RawPadding,
+{
+}
+
+impl<A, P> ChunkPartitioner for ShamirIdaAead<A, P>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    P: RawPadding,
+{
+    const SPLIT_KEY: bool = true;
+
+    fn new_split_key(&self) -> Vec<u8> {
+        Self::new_chunk_key()
+    }
+
+    fn join_split_key<R: Read>(&self, inputs: &mut [InputPartition<R>]) -> Result<Vec<u8>> {
+        self.join_chunk_key(inputs)
+    }
+
+    fn split_chunk<R: Read, W: Write>(
+        &self,
+        key: &[u8],
+        index: u64,
+        last: bool,
+        input: R,
+        outputs: &mut [OutputPartition<W>],
+    ) -> Result<()> {
+        ShamirIdaAead::split_chunk(self, key, index, last, input, outputs)
+    }
+
+    fn join_chunk<R: Read, W: Write>(
+        &self,
+        key: &mut Option<Vec<u8>>,
+        index: u64,
+        last: bool,
+        inputs: &mut [InputPartition<R>],
+        output: W,
+    ) -> Result<JoinReport> {
+        ShamirIdaAead::join_chunk(self, key, index, last, inputs, output)
+    }
+
+    // A chunk is authenticated as a whole, so all of it is joined.
+    fn join_chunk_range<R: Read + Seek, W: Write>(
+        &self,
+        key: &mut Option<Vec<u8>>,
+        index: u64,
+        last: bool,
+        inputs: &mut [InputPartition<R>],
+        range: Range<u64>,
+        mut output: W,
+    ) -> Result<JoinReport> {
+        let mut chunk = Vec::new();
+        let report = ShamirIdaAead::join_chunk(self, key, index, last, inputs, &mut chunk)?;
+        let from = range.start.min(chunk.len() as u64) as usize;
+        let to = range.end.min(chunk.len() as u64) as usize;
+        output.write_all(&chunk[from..to])?;
+        output.flush()?;
+        Ok(report)
+    }
+}
+
+// Joins the key of the split, for splits and joins that start past the first chunk. Only if the
+// partitioner has one are the first chunks of the files read.
+struct SplitKey<'a, F: FnOnce() -> Result<Vec<Chunk>>> {
+    files: &'a [ShareHeader],
+    first_chunks: F,
+}
+
+impl<'a, F: FnOnce() -> Result<Vec<Chunk>>> PartitionerFn for SplitKey<'a, F> {
+    type Output = Option<Vec<u8>>;
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<Option<Vec<u8>>> {
+        if !P::SPLIT_KEY {
+            return Ok(None);
+        }
+        let chunks: Vec<_> = (self.first_chunks)()?.into_iter().map(Some).collect();
+        let key = partitioner.join_split_key(&mut chunk_inputs(self.files, &chunks)?)?;
+        Ok(Some(key))
+    }
+}
+
+struct Split<'a, R: Read, W: Write> {
+    header: &'a ShareHeader,
+    input: R,
+    outputs: &'a mut [WeightedOutput<W>],
+    // The chunk to start from. Past 0, the outputs already hold their headers and the chunks
+    // before it.
+    index: u64,
+    // What those chunks have in common, as recovered by SplitKey; a new split draws its own.
+    key: Option<Vec<u8>>,
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for Split<'a, R, W> {
+    type Output = ();
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<()> {
+        let Split {
+            header,
+            mut input,
+            outputs,
+            mut index,
+            key,
+        } = self;
+        let key = key.unwrap_or_else(|| partitioner.new_split_key());
+        let files: Vec<_> = outputs
+            .iter()
+            .map(|output| (output.x, output.weight))
+            .collect();
+        let mut writers = Vec::new();
+        for output in outputs.iter_mut() {
+            let file = ShareHeader {
+                x: output.x,
+                weight: output.weight,
+                ..header.clone()
+            };
+            if index == 0 {
+                file.write(&mut output.writer)?;
+            }
+            writers.push(ChunkWriter::new(&mut output.writer, &file, index));
+        }
+        // Read a chunk ahead, to know which chunk is the last.
+        let mut next = read_chunk(&mut input, CHUNK_SIZE)?;
+        loop {
+            let chunk = next.take().unwrap_or_default();
+            next = read_chunk(&mut input, CHUNK_SIZE)?;
+            let last = next.is_none();
+            write_chunk(&files, &mut writers, last, |outputs| {
+                partitioner.split_chunk(&key, index, last, &chunk[..], outputs)
+            })?;
+            if last {
+                break;
+            }
+            index += 1;
+        }
+        drop(writers);
+        for output in outputs.iter_mut() {
+            output.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+struct Join<'a, R: Read, W: Write> {
+    files: &'a [ShareHeader],
+    readers: &'a mut [ChunkReader<R>],
+    // The key of the split, if the join starts past the first chunk; see SplitKey.
+    key: Option<Vec<u8>>,
+    output: W,
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for Join<'a, R, W> {
+    type Output = JoinReport;
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<JoinReport> {
+        let Join {
+            files,
+            readers,
+            mut key,
+            mut output,
+        } = self;
+        let mut report = JoinReport::default();
+        let mut dropped = vec![false; files.len()];
+        for_each_chunk(files, readers, Some(&mut dropped), |inputs, index, last| {
+            report.merge(partitioner.join_chunk(&mut key, index, last, inputs, &mut output)?);
+            Ok(())
+        })?;
+        report.merge(dropped_shares(files, &dropped));
+        output.flush()?;
+        Ok(report)
+    }
+}
+
+struct JoinRange<'a, R: Read, W: Write> {
+    files: &'a [ShareHeader],
+    readers: &'a mut [ChunkReader<R>],
+    key: Option<Vec<u8>>,
+    // Where in the original the next chunk of the readers starts.
+    position: u64,
+    // The range of the original to write.
+    start: u64,
+    end: u64,
+    output: W,
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for JoinRange<'a, R, W> {
+    type Output = JoinReport;
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<JoinReport> {
+        let JoinRange {
+            files,
+            readers,
+            mut key,
+            mut position,
+            start,
+            end,
+            mut output,
+        } = self;
+        let mut report = JoinReport::default();
+        let mut dropped = vec![false; files.len()];
+        while position < end {
+            let (chunks, index, last) = next_chunks(files, readers, Some(&mut dropped))?;
+            // Only the first and last chunk of the range are partly joined, and the partitioner
+            // reads just the part of their shares that covers it.
+            let from = start.max(position);
+            report.merge(partitioner.join_chunk_range(
+                &mut key,
+                index,
+                last,
+                &mut seekable_chunk_inputs(files, &chunks)?,
+                from - position..end - position,
+                &mut output,
+            )?);
+            position += CHUNK_SIZE as u64;
+            if last {
+                break;
+            }
+        }
+        report.merge(dropped_shares(files, &dropped));
+        output.flush()?;
+        Ok(report)
+    }
+}
+
+struct Refresh<'a, R: Read, W: Write> {
+    header: &'a ShareHeader,
+    // Headers of the input files, whose bundles the outputs reproduce.
+    files: &'a [ShareHeader],
+    readers: &'a mut [ChunkReader<R>],
+    outputs: &'a mut [W],
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for Refresh<'a, R, W> {
+    type Output = ();
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<()> {
+        let Refresh {
+            header,
+            files,
+            readers,
+            outputs,
+        } = self;
+        let bundles: Vec<_> = files.iter().map(|file| (file.x, file.weight)).collect();
+        let mut writers = Vec::new();
+        for (file, output) in files.iter().zip(outputs.iter_mut()) {
+            let file = ShareHeader {
+                x: file.x,
+                weight: file.weight,
+                ..header.clone()
+            };
+            file.write(output)?;
+            writers.push(ChunkWriter::new(output, &file, 0));
+        }
+        for_each_chunk(files, readers, None, |inputs, _, last| {
+            write_chunk(&bundles, &mut writers, last, |outputs| {
+                let mut lanes: Vec<_> = outputs
+                    .iter_mut()
+                    .map(|output| &mut output.writer)
+                    .collect();
+                partitioner.refresh(inputs, &mut lanes)
+            })
+        })?;
+        drop(writers);
+        for output in outputs.iter_mut() {
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+struct Repair<'a, R: Read, W: Write> {
+    header: &'a ShareHeader,
+    files: &'a [ShareHeader],
+    readers: &'a mut [ChunkReader<R>],
+    output: W,
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for Repair<'a, R, W> {
+    type Output = ();
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<()> {
+        let Repair {
+            header,
+            files,
+            readers,
+            mut output,
+        } = self;
+        header.write(&mut output)?;
+        let mut writers = [ChunkWriter::new(&mut output, header, 0)];
+        for_each_chunk(files, readers, None, |inputs, _, last| {
+            write_chunk(&[(header.x, 1)], &mut writers, last, |outputs| {
+                partitioner.repair(
+                    inputs,
+                    OutputPartition {
+                        x: header.x,
+                        writer: &mut outputs[0].writer,
+                    },
+                )
+            })
+        })?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+struct Reshare<'a, R: Read, W: Write> {
+    header: &'a ShareHeader,
+    files: &'a [ShareHeader],
+    readers: &'a mut [ChunkReader<R>],
+    outputs: &'a mut [OutputPartition<W>],
+}
+
+impl<'a, R: Read, W: Write> PartitionerFn for Reshare<'a, R, W> {
+    type Output = ();
+
+    fn call<P: ChunkPartitioner>(self, partitioner: P) -> Result<()> {
+        let Reshare {
+            header,
+            files,
+            readers,
+            outputs,
+        } = self;
+        let bundles: Vec<_> = outputs.iter().map(|output| (output.x, 1)).collect();
+        let mut writers = Vec::new();
+        for output in outputs.iter_mut() {
+            let file = ShareHeader {
+                x: output.x,
+                ..header.clone()
+            };
+            file.write(&mut output.writer)?;
+            writers.push(ChunkWriter::new(&mut output.writer, &file, 0));
+        }
+        for_each_chunk(files, readers, None, |inputs, _, last| {
+            write_chunk(&bundles, &mut writers, last, |outputs| {
+                partitioner.reshare(inputs, header.k, outputs)
+            })
+        })?;
+        drop(writers);
+        for output in outputs.iter_mut() {
+            output.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn with_partitioner<F: PartitionerFn>(header: &ShareHeader, f: F) -> Result<F::Output> {
+    match header.field {
+        Field::Gf2p8(0x11d) => with_field::<Gf2p8<0x11d>, F>(header, f),
+        Field::Gf2p8(0x11b) => with_field::<Gf2p8<0x11b>, F>(header, f),
+        Field::Gf2p8(_) => Err(unsupported(header)),
+        Field::Gf65536 => with_field::<Gf65536, F>(header, f),
+        Field::Secp256k1 => with_field::<PrimeField<Secp256k1Order>, F>(header, f),
+        Field::P25519 => with_field::<PrimeField<P25519>, F>(header, f),
+    }
+}
+
+fn with_field<G: GaloisField, F: PartitionerFn>(header: &ShareHeader, f: F) -> Result<F::Output> {
+    match header.padding {
+        Padding::None => match (header.scheme, header.cipher) {
+            (Scheme::Shamir, Cipher::None) => f.call(Shamir::<G>::with_field(header.k)?),
+            _ => Err(unsupported(header)),
+        },
+        // Padding could leave blocks that are not elements of the field.
+        _ if !G::DENSE => Err(unsupported(header)),
+        Padding::Iso7816 => with_padding::<Iso7816, G, F>(header, f),
+        // PKCS#7 cannot pad the blocks of k elements that IDA works in beyond 255 bytes.
+        Padding::Pkcs7 if usize::from(header.k) * G::ELEMENT_SIZE > 255 => Err(unsupported(header)),
+        Padding::Pkcs7 => with_padding::<Pkcs7, G, F>(header, f),
+    }
+}
+
+fn with_padding<P: RawPadding, G: GaloisField, F: PartitionerFn>(
+    header: &ShareHeader,
+    f: F,
+) -> Result<F::Output> {
+    let k = header.k;
+    match (header.scheme, header.cipher) {
+        (Scheme::Ida, Cipher::None) => f.call(Ida::<P, G>::with_field(k)?),
+        // The hybrid schemes share their key over GF(2^8) only.
+        _ if header.field != Field::GF256 => Err(unsupported(header)),
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes128Cbc) => f.call(shamir_ida::<
+            cbc::Encryptor<Aes128>,
+            cbc::Decryptor<Aes128>,
+            P,
+        >(header)?),
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes256Cbc) => f.call(shamir_ida::<
+            cbc::Encryptor<Aes256>,
+            cbc::Decryptor<Aes256>,
+            P,
+        >(header)?),
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes128Cfb) => f.call(shamir_ida::<
+            cfb_mode::Encryptor<Aes128>,
+            cfb_mode::Decryptor<Aes128>,
+            P,
+        >(header)?),
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes256Cfb) => f.call(shamir_ida::<
+            cfb_mode::Encryptor<Aes256>,
+            cfb_mode::Decryptor<Aes256>,
+            P,
+        >(header)?),
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes256Gcm) => {
+            f.call(shamir_ida_aead::<Aes256Gcm, P>(header)?)
+        }
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::ChaCha20Poly1305) => {
+            f.call(shamir_ida_aead::<ChaCha20Poly1305, P>(header)?)
+        }
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes128Ctr) => {
+            f.call(shamir_ida_ctr::<Ctr128BE<Aes128>, P>(header)?)
+        }
+        (Scheme::ShamirIda | Scheme::ShamirIdaVss, Cipher::Aes256Ctr) => {
+            f.call(shamir_ida_ctr::<Ctr128BE<Aes256>, P>(header)?)
+        }
+        _ => Err(unsupported(header)),
+    }
+}
+
+fn shamir_ida<E, D, P>(header: &ShareHeader) -> Result<ShamirIda<E, D, P>>
+where
+    E: KeyIvInit + BlockEncryptMut,
+    D: KeyIvInit + BlockDecryptMut,
+    P: RawPadding,
+{
+    match header.scheme {
+        Scheme::ShamirIdaVss => ShamirIda::new_verifiable(header.k),
+        _ => ShamirIda::new(header.k),
+    }
+}
+
+fn shamir_ida_aead<A, P>(header: &ShareHeader) -> Result<ShamirIdaAead<A, P>>
+where
+    A: AeadInPlace + KeyInit + AeadCore<NonceSize = U12>,
+    P: RawPadding,
+{
+    match header.scheme {
+        Scheme::ShamirIdaVss => ShamirIdaAead::new_verifiable(header.k),
+        _ => ShamirIdaAead::new(header.k),
+    }
+}
+
+fn shamir_ida_ctr<C, P>(header: &ShareHeader) -> Result<ShamirIdaCtr<C, P>>
+where
+    C: KeyIvInit + StreamCipher + StreamCipherSeek,
+    P: RawPadding,
+{
+    match header.scheme {
+        Scheme::ShamirIdaVss => ShamirIdaCtr::new_verifiable(header.k),
+        _ => ShamirIdaCtr::new(header.k),
+    }
+}
+
+fn unsupported(header: &ShareHeader) -> SplitSecretError {
+    SplitSecretError::InvalidHeader(format!(
+        "scheme {} does not support cipher {} with padding {} over {}",
+        header.scheme, header.cipher, header.padding, header.field
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    // Outputs for shares 1, 2, ... in `shares`.
+    fn outputs(shares: &mut [Vec<u8>]) -> Vec<OutputPartition<&mut Vec<u8>>> {
+        shares
+            .iter_mut()
+            .zip(1u16..)
+            .map(|(share, x)| OutputPartition { x, writer: share })
+            .collect()
+    }
+
+    fn round_trip(scheme: Scheme, cipher: Cipher, padding: Padding) {
+        // An empty original still makes a share file of one empty chunk.
+        for plaintext in [&b"this is a much longer text"[..], &[]] {
+            let header = ShareHeader::new(scheme, cipher, padding, 3, 0);
+            let mut shares = vec![Vec::new(); 5];
+            split_shares(&header, plaintext, &mut outputs(&mut shares)).unwrap();
+
+            let mut inputs: Vec<&[u8]> = shares[2..].iter().map(|share| &share[..]).collect();
+            let mut output = Vec::new();
+            join_shares(&mut inputs, &mut output).unwrap();
+            assert_eq!(plaintext, &output[..]);
+        }
+    }
+
+    #[test]
+    fn round_trip_all_schemes() {
+        round_trip(Scheme::Shamir, Cipher::None, Padding::None);
+        round_trip(Scheme::Ida, Cipher::None, Padding::Pkcs7);
+        round_trip(Scheme::ShamirIda, Cipher::Aes256Cbc, Padding::Iso7816);
+        round_trip(Scheme::ShamirIda, Cipher::Aes128Cfb, Padding::Pkcs7);
+        round_trip(Scheme::ShamirIdaVss, Cipher::Aes256Ctr, Padding::Iso7816);
+        round_trip(
+            Scheme::ShamirIda,
+            Cipher::ChaCha20Poly1305,
+            Padding::Iso7816,
+        );
+        round_trip(Scheme::ShamirIdaVss, Cipher::Aes256Gcm, Padding::Iso7816);
+    }
+
+    #[test]
+    fn weighted() {
+        let plaintext = vec![5u8; 10000];
+        for (scheme, cipher, padding) in [
+            (Scheme::Shamir, Cipher::None, Padding::None),
+            (Scheme::Ida, Cipher::None, Padding::Iso7816),
+            (Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816),
+        ] {
+            let header = ShareHeader::new(scheme, cipher, padding, 3, 0);
+            let mut shares = vec![Vec::new(); 3];
+            let mut outputs: Vec<_> = shares
+                .iter_mut()
+                .zip([(1, 2), (3, 1), (4, 1)])
+                .map(|(share, (x, weight))| WeightedOutput {
+                    x,
+                    weight,
+                    writer: share,
+                })
+                .collect();
+            split_weighted_shares(&header, &plaintext[..], &mut outputs).unwrap();
+
+            // The bundle counts as two of the three shares needed.
+            let mut inputs = vec![&shares[0][..]];
+            assert!(matches!(
+                join_shares(&mut inputs, Vec::new()),
+                Err(SplitSecretError::ShareCount { k: 3, n: 2 })
+            ));
+            let mut inputs = vec![&shares[0][..], &shares[2][..]];
+            let mut output = Vec::new();
+            join_shares(&mut inputs, &mut output).unwrap();
+            assert_eq!(plaintext, output);
+        }
+
+        // Refreshing a bundle keeps it a bundle.
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 3, 0);
+        let mut shares = vec![Vec::new(); 2];
+        let mut outputs: Vec<_> = shares
+            .iter_mut()
+            .zip([(1, 2), (3, 1)])
+            .map(|(share, (x, weight))| WeightedOutput {
+                x,
+                weight,
+                writer: share,
+            })
+            .collect();
+        split_weighted_shares(&header, &plaintext[..], &mut outputs).unwrap();
+        let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+        let mut refreshed = vec![Vec::new(); 2];
+        refresh_shares(&mut inputs, &mut refreshed).unwrap();
+        assert_eq!(ShareHeader::read(&mut &refreshed[0][..]).unwrap().weight, 2);
+        let mut inputs: Vec<&[u8]> = refreshed.iter().map(|share| &share[..]).collect();
+        let mut output = Vec::new();
+        join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+    }
+
+    #[test]
+    fn gf65536() {
+        let plaintext = vec![7u8; 1001];
+        for (scheme, padding) in [
+            (Scheme::Shamir, Padding::None),
+            (Scheme::Ida, Padding::Iso7816),
+        ] {
+            let header = ShareHeader {
+                field: Field::Gf65536,
+                ..ShareHeader::new(scheme, Cipher::None, padding, 2, 0)
+            };
+            let mut shares = vec![Vec::new(); 2];
+            let mut outputs: Vec<_> = shares
+                .iter_mut()
+                .zip([1000u16, 60000])
+                .map(|(share, x)| OutputPartition { x, writer: share })
+                .collect();
+            split_shares(&header, &plaintext[..], &mut outputs).unwrap();
+
+            let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+            let mut output = Vec::new();
+            join_shares(&mut inputs, &mut output).unwrap();
+            assert_eq!(plaintext, output);
+        }
+
+        // The hybrid schemes share their key over GF(2^8).
+        let header = ShareHeader {
+            field: Field::Gf65536,
+            ..ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816, 2, 0)
+        };
+        let mut outputs: Vec<_> = (1u16..=2)
+            .map(|x| OutputPartition {
+                x,
+                writer: Vec::new(),
+            })
+            .collect();
+        assert!(matches!(
+            split_shares(&header, &plaintext[..], &mut outputs),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn aes_polynomial() {
+        let plaintext: Vec<u8> = (0..1000).map(|i| (i * 13) as u8).collect();
+        let header = ShareHeader {
+            field: Field::Gf2p8(0x11b),
+            ..ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 2, 0)
+        };
+        let mut shares = vec![Vec::new(); 3];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+        let mut inputs: Vec<&[u8]> = shares[1..].iter().map(|share| &share[..]).collect();
+        let mut output = Vec::new();
+        join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+
+        // The single chunk holds the same share as a split over that field directly. Share 3 is
+        // the first that is not a column of the original, so it depends on the field.
+        let ida = Ida::<Iso7816, Gf2p8<0x11b>>::with_field(2).unwrap();
+        let partitions = ida.split_in_memory(&plaintext, 3).unwrap();
+        let body = &shares[2][HEADER_SIZE + 13..shares[2].len() - 32];
+        assert_eq!(body, &partitions[2].value[..]);
+        let other = Ida::<Iso7816>::new(2).unwrap();
+        assert_ne!(
+            body,
+            &other.split_in_memory(&plaintext, 3).unwrap()[2].value[..]
+        );
+    }
+
+    #[test]
+    fn prime_field() {
+        let mut secret = [0xffu8; 32];
+        secret[0] = 0x12;
+        let header = ShareHeader {
+            field: Field::Secp256k1,
+            ..ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 0)
+        };
+        let mut outputs: Vec<_> = (1u16..=3)
+            .map(|x| OutputPartition {
+                x,
+                writer: Vec::new(),
+            })
+            .collect();
+        split_shares(&header, &secret[..], &mut outputs).unwrap();
+
+        let mut inputs: Vec<&[u8]> = outputs.iter().map(|output| &output.writer[..]).collect();
+        let mut output = Vec::new();
+        join_shares(&mut inputs[1..], &mut output).unwrap();
+        assert_eq!(secret[..], output[..]);
+
+        // Only Shamir without padding works over a prime field.
+        let header = ShareHeader {
+            field: Field::P25519,
+            ..ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 2, 0)
+        };
+        assert!(matches!(
+            split_shares(&header, &secret[..], &mut outputs),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn verify_shares() {
+        let header = ShareHeader::new(
+            Scheme::ShamirIdaVss,
+            Cipher::Aes256Gcm,
+            Padding::Pkcs7,
+            2,
+            0,
+        );
+        let mut shares = vec![Vec::new(); 3];
+        split_shares(&header, &b"secret"[..], &mut outputs(&mut shares)).unwrap();
+
+        let (_, commitments) = verify_share(&shares[0][..]).unwrap();
+        for share in shares[1..].iter() {
+            assert_eq!(verify_share(&share[..]).unwrap().1, commitments);
+        }
+
+        // A bundle whose second lane was dealt by another split passes the check of each lane on
+        // its own, but not of the bundle.
+        let mut bundles = Vec::new();
+        for _ in 0..2 {
+            let mut shares = vec![Vec::new(); 2];
+            let mut outputs: Vec<_> = shares
+                .iter_mut()
+                .zip([(1, 2), (3, 1)])
+                .map(|(share, (x, weight))| WeightedOutput {
+                    x,
+                    weight,
+                    writer: share,
+                })
+                .collect();
+            split_weighted_shares(&header, &b"secret"[..], &mut outputs).unwrap();
+            bundles.push(shares.swap_remove(0));
+        }
+        assert!(verify_share(&bundles[0][..]).is_ok());
+        let (ours, theirs) = (chunks_of(&bundles[0]), chunks_of(&bundles[1]));
+        let mut mixed = ours[0].clone();
+        for (i, byte) in mixed.shares.iter_mut().enumerate().skip(1).step_by(2) {
+            *byte = theirs[0].shares[i];
+        }
+        assert!(matches!(
+            verify_share(&reframe(&bundles[0], &[&mixed])[..]),
+            Err(SplitSecretError::CommitmentMismatch { x: 2 })
+        ));
+
+        // Shares of other schemes carry no commitments.
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 0);
+        let mut share = Vec::new();
+        split_shares(
+            &header,
+            &b"secret"[..],
+            &mut [
+                OutputPartition {
+                    x: 1,
+                    writer: &mut share,
+                },
+                OutputPartition {
+                    x: 2,
+                    writer: &mut Vec::new(),
+                },
+            ],
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_share(&share[..]),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn refresh() {
+        let plaintext: Vec<u8> = "this is a much longer text".as_bytes().into();
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); 3];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+
+        let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+        let mut refreshed = vec![Vec::new(); 3];
+        refresh_shares(&mut inputs, &mut refreshed).unwrap();
+
+        let mut inputs: Vec<&[u8]> = refreshed[1..].iter().map(|share| &share[..]).collect();
+        let mut output = Vec::new();
+        join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+
+        let mut inputs = vec![&shares[0][..], &refreshed[1][..]];
+        assert!(matches!(
+            join_shares(&mut inputs, Vec::new()),
+            Err(SplitSecretError::MismatchedHeader { x: 2 })
+        ));
+
+        let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+        let mut refreshed = vec![Vec::new(); 2];
+        assert!(matches!(
+            refresh_shares(&mut inputs, &mut refreshed),
+            Err(SplitSecretError::OutputCount { .. })
+        ));
+        assert!(refreshed.iter().all(|share| share.is_empty()));
+
+        // IDA shares have nothing secret to refresh.
+        let header = ShareHeader::new(Scheme::Ida, Cipher::None, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); 2];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+        let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+        assert!(matches!(
+            refresh_shares(&mut inputs, &mut vec![Vec::new(); 2]),
+            Err(SplitSecretError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn repair() {
+        let plaintext = vec![17u8; 100000];
+        for (scheme, cipher, padding) in [
+            (Scheme::Shamir, Cipher::None, Padding::None),
+            (Scheme::Ida, Cipher::None, Padding::Iso7816),
+            (Scheme::ShamirIda, Cipher::Aes128Cbc, Padding::Pkcs7),
+            (Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816),
+            (
+                Scheme::ShamirIdaVss,
+                Cipher::ChaCha20Poly1305,
+                Padding::Iso7816,
+            ),
+        ] {
+            let header = ShareHeader::new(scheme, cipher, padding, 3, 0);
+            let mut shares = vec![Vec::new(); 5];
+            split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+
+            let mut inputs: Vec<&[u8]> = shares[1..4].iter().map(|share| &share[..]).collect();
+            let mut repaired = Vec::new();
+            repair_share(&mut inputs, 5, &mut repaired).unwrap();
+            assert!(shares[4] == repaired);
+        }
+    }
+
+    #[test]
+    fn reshare() {
+        let plaintext = vec![3u8; 10000];
+        let header = ShareHeader::new(
+            Scheme::ShamirIda,
+            Cipher::ChaCha20Poly1305,
+            Padding::Iso7816,
+            3,
+            0,
+        );
+        let mut shares = vec![Vec::new(); 5];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+
+        let mut inputs: Vec<&[u8]> = shares[..3].iter().map(|share| &share[..]).collect();
+        let mut reshared = vec![Vec::new(); 7];
+        reshare_shares(&mut inputs, 4, &mut outputs(&mut reshared)).unwrap();
+
+        let mut inputs: Vec<&[u8]> = reshared[..3].iter().map(|share| &share[..]).collect();
+        assert!(matches!(
+            join_shares(&mut inputs, Vec::new()),
+            Err(SplitSecretError::ShareCount { k: 4, n: 3 })
+        ));
+        let mut inputs: Vec<&[u8]> = reshared[3..].iter().map(|share| &share[..]).collect();
+        let mut output = Vec::new();
+        join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+    }
+
+    #[test]
+    fn resume_split() {
+        let plaintext: Vec<u8> = (0..5 * CHUNK_SIZE / 2).map(|i| (i * 7) as u8).collect();
+        for (scheme, cipher, padding) in [
+            (Scheme::Shamir, Cipher::None, Padding::None),
+            (Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816),
+        ] {
+            let header = ShareHeader::new(scheme, cipher, padding, 2, 0);
+            let mut outputs: Vec<_> = [(1, 1), (2, 2), (4, 1)]
+                .iter()
+                .map(|(x, weight)| WeightedOutput {
+                    x: *x,
+                    weight: *weight,
+                    writer: Cursor::new(Vec::new()),
+                })
+                .collect();
+            split_weighted_shares(&header, &plaintext[..], &mut outputs).unwrap();
+            let complete: Vec<_> = outputs
+                .iter()
+                .map(|output| output.writer.get_ref().clone())
+                .collect();
+
+            // Cut off partway through the second and third chunks, and after the last one.
+            let cuts = [
+                complete[0].len() / 2,
+                complete[1].len() * 3 / 4,
+                complete[2].len(),
+            ];
+            for (output, cut) in outputs.iter_mut().zip(cuts.iter()) {
+                output.writer.get_mut().truncate(*cut);
+            }
+            resume_split_weighted_shares(&header, Cursor::new(&plaintext), &mut outputs).unwrap();
+            for (output, share) in outputs.iter_mut().zip(complete.iter()) {
+                let end = output.writer.position() as usize;
+                output.writer.get_mut().truncate(end);
+                // The first chunk was kept.
+                let kept = HEADER_SIZE + share.len() / 3;
+                assert_eq!(output.writer.get_ref()[..kept], share[..kept]);
+            }
+
+            let mut inputs: Vec<&[u8]> = outputs[1..]
+                .iter()
+                .map(|output| &output.writer.get_ref()[..])
+                .collect();
+            let mut output = Vec::new();
+            join_shares(&mut inputs, &mut output).unwrap();
+            assert_eq!(plaintext, output);
+        }
+
+        // A split that never got past the headers keeps its set identifier.
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 0);
+        let mut outputs: Vec<_> = (1u16..=2)
+            .map(|x| WeightedOutput {
+                x,
+                weight: 1,
+                writer: Cursor::new(Vec::new()),
+            })
+            .collect();
+        ShareHeader {
+            x: 1,
+            ..header.clone()
+        }
+        .write(outputs[0].writer.get_mut())
+        .unwrap();
+        let new_header = header.new_split();
+        resume_split_weighted_shares(&new_header, Cursor::new(b"secret"), &mut outputs).unwrap();
+        for output in outputs.iter() {
+            let file = ShareHeader::read(&mut &output.writer.get_ref()[..]).unwrap();
+            assert_eq!(file.set_id, header.set_id);
+        }
+    }
+
+    #[test]
+    fn resume_join() {
+        let plaintext: Vec<u8> = (0..5 * CHUNK_SIZE / 2).map(|i| (i * 3) as u8).collect();
+        let mut shares = vec![Vec::new(); 3];
+        for (scheme, cipher) in [
+            (Scheme::ShamirIda, Cipher::Aes256Gcm),
+            (Scheme::Ida, Cipher::None),
+        ] {
+            let header = ShareHeader::new(scheme, cipher, Padding::Iso7816, 2, 0);
+            shares = vec![Vec::new(); 3];
+            split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+            for done in [0, CHUNK_SIZE + 10, 2 * CHUNK_SIZE, plaintext.len()] {
+                let mut output = Cursor::new(plaintext[..done].to_vec());
+                let mut inputs: Vec<_> = shares[1..].iter().map(Cursor::new).collect();
+                resume_join_shares(&mut inputs, &mut output).unwrap();
+                let end = output.position() as usize;
+                assert_eq!(output.get_ref()[..end], plaintext[..]);
+            }
+        }
+
+        // Skipped chunks are not read, but the ones joined are still checked.
+        let last = shares[1].len() - 1;
+        shares[1][last] ^= 1;
+        let mut output = Cursor::new(plaintext[..CHUNK_SIZE].to_vec());
+        let mut inputs: Vec<_> = shares[1..].iter().map(Cursor::new).collect();
+        assert!(matches!(
+            resume_join_shares(&mut inputs, &mut output),
+            Err(SplitSecretError::CorruptShare {
+                input: 0,
+                x: Some(2)
+            })
+        ));
+    }
+
+    // The chunks of a share file, and a share file with the header of `share` and `chunks` in the
+    // given order, framed and digested anew as anyone can.
+    fn chunks_of(share: &[u8]) -> Vec<Chunk> {
+        let header = ShareHeader::read(&mut &share[..]).unwrap();
+        let mut reader = ChunkReader::new(&share[HEADER_SIZE..], &header, 0);
+        let mut chunks = vec![reader.next_chunk().unwrap()];
+        while !chunks.last().unwrap().last {
+            chunks.push(reader.next_chunk().unwrap());
+        }
+        chunks
+    }
+
+    fn reframe(share: &[u8], chunks: &[&Chunk]) -> Vec<u8> {
+        let header = ShareHeader::read(&mut &share[..]).unwrap();
+        let mut file = share[..HEADER_SIZE].to_vec();
+        let mut writer = ChunkWriter::new(&mut file, &header, 0);
+        for (i, chunk) in chunks.iter().enumerate() {
+            writer
+                .write_chunk(&chunk.shares, i + 1 == chunks.len())
+                .unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn aead_chunks_stay_in_place() {
+        let plaintext: Vec<u8> = (0..5 * CHUNK_SIZE / 2).map(|i| (i * 5) as u8).collect();
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); 2];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+        let chunks: Vec<_> = shares.iter().map(|share| chunks_of(share)).collect();
+        // Another split of the same original, given the header of the first.
+        let mut others = vec![Vec::new(); 2];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut others)).unwrap();
+        let other_chunks: Vec<_> = others.iter().map(|share| chunks_of(share)).collect();
+
+        let tampered: Vec<Vec<Vec<u8>>> = vec![
+            // Dropping the last chunk.
+            (0..2)
+                .map(|i| reframe(&shares[i], &[&chunks[i][0], &chunks[i][1]]))
+                .collect(),
+            // Swapping the first two.
+            (0..2)
+                .map(|i| reframe(&shares[i], &[&chunks[i][1], &chunks[i][0], &chunks[i][2]]))
+                .collect(),
+            // Taking the second from the other split.
+            (0..2)
+                .map(|i| {
+                    reframe(
+                        &shares[i],
+                        &[&chunks[i][0], &other_chunks[i][1], &chunks[i][2]],
+                    )
+                })
+                .collect(),
+        ];
+        for files in tampered.iter() {
+            let mut inputs: Vec<&[u8]> = files.iter().map(|file| &file[..]).collect();
+            assert!(matches!(
+                join_shares(&mut inputs, Vec::new()),
+                Err(SplitSecretError::Authentication)
+            ));
+        }
+        // A range join checks its chunks against the key of the first one.
+        let mut inputs: Vec<_> = tampered[2].iter().map(Cursor::new).collect();
+        assert!(matches!(
+            join_range_shares(&mut inputs, CHUNK_SIZE as u64, 10, Vec::new()),
+            Err(SplitSecretError::Authentication)
+        ));
+    }
+
+    #[test]
+    fn damaged_share_left_out() {
+        let plaintext: Vec<u8> = (0..3 * CHUNK_SIZE / 2).map(|i| (i * 11) as u8).collect();
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Gcm, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); 4];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+
+        // One damaged byte in the second chunk of one of k + 2 shares.
+        let mut damaged = shares.clone();
+        let len = damaged[1].len();
+        damaged[1][len - 100] ^= 1;
+        let mut inputs: Vec<&[u8]> = damaged.iter().map(|share| &share[..]).collect();
+        let mut output = Vec::new();
+        let report = join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+        assert_eq!(report.corrupted, vec![2]);
+        let mut inputs: Vec<_> = damaged.iter().map(Cursor::new).collect();
+        let mut output = Vec::new();
+        let report = join_range_shares(&mut inputs, 10, u64::MAX, &mut output).unwrap();
+        assert_eq!(output, plaintext[10..]);
+        assert_eq!(report.corrupted, vec![2]);
+
+        // Without k shares left, the damage is reported.
+        let mut inputs = vec![&damaged[0][..], &damaged[1][..]];
+        assert!(matches!(
+            join_shares(&mut inputs, Vec::new()),
+            Err(SplitSecretError::CorruptShare {
+                input: 1,
+                x: Some(2)
+            })
+        ));
+
+        // A share cut short after its first chunk, and digested anew, disagrees with the others
+        // on which chunk is the last.
+        let chunks = chunks_of(&shares[2]);
+        let cut = reframe(&shares[2], &[&chunks[0]]);
+        let mut inputs = vec![&shares[0][..], &cut[..], &shares[3][..]];
+        let mut output = Vec::new();
+        let report = join_shares(&mut inputs, &mut output).unwrap();
+        assert_eq!(plaintext, output);
+        assert_eq!(report.corrupted, vec![3]);
+        let mut inputs = vec![&shares[0][..], &cut[..]];
+        assert!(matches!(
+            join_shares(&mut inputs, Vec::new()),
+            Err(SplitSecretError::CorruptShare {
+                input: 1,
+                x: Some(3)
+            })
+        ));
+    }
+
+    #[test]
+    fn join_range() {
+        let plaintext: Vec<u8> = (0..5 * CHUNK_SIZE / 2).map(|i| (i * 7) as u8).collect();
+        let end = plaintext.len() as u64;
+        // CTR seeks inside the chunks; CBC and GCM join them whole and drop the rest.
+        for cipher in [Cipher::Aes256Cbc, Cipher::Aes256Ctr, Cipher::Aes256Gcm] {
+            let header = ShareHeader::new(Scheme::ShamirIda, cipher, Padding::Iso7816, 2, 0);
+            let mut shares = vec![Vec::new(); 3];
+            split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+            for (offset, len) in [
+                (0, 10),
+                (CHUNK_SIZE as u64 - 5, 10),
+                (2 * CHUNK_SIZE as u64 + 7, u64::MAX),
+                (end - 1, 1),
+                (end, 10),
+                (10 * CHUNK_SIZE as u64, 10),
+            ] {
+                let mut inputs: Vec<_> = shares[1..].iter().map(Cursor::new).collect();
+                let mut output = Vec::new();
+                join_range_shares(&mut inputs, offset, len, &mut output).unwrap();
+                let start = offset.min(end) as usize;
+                let stop = offset.saturating_add(len).min(end) as usize;
+                assert_eq!(output, plaintext[start..stop]);
+            }
+        }
+
+        let header = ShareHeader::new(Scheme::ShamirIda, Cipher::Aes256Ctr, Padding::Iso7816, 2, 0);
+        let mut shares = vec![Vec::new(); 3];
+        split_shares(&header, &plaintext[..], &mut outputs(&mut shares)).unwrap();
+
+        // Chunks outside the range are not read, but the ones joined are still checked.
+        shares[1][HEADER_SIZE + 100] ^= 1;
+        let mut inputs: Vec<_> = shares[1..].iter().map(Cursor::new).collect();
+        let mut output = Vec::new();
+        join_range_shares(&mut inputs, CHUNK_SIZE as u64, 10, &mut output).unwrap();
+        assert_eq!(output, plaintext[CHUNK_SIZE..CHUNK_SIZE + 10]);
+        let mut inputs: Vec<_> = shares[1..].iter().map(Cursor::new).collect();
+        assert!(matches!(
+            join_range_shares(&mut inputs, 10, 10, Vec::new()),
+            Err(SplitSecretError::CorruptShare {
+                input: 0,
+                x: Some(2)
+            })
+        ));
+    }
+
+    #[test]
+    fn unsupported_combination() {
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::Aes256Cbc, Padding::None, 2, 0);
+        let mut shares = vec![Vec::new(); 2];
+        assert!(matches!(
+            split_shares(&header, &b"secret"[..], &mut outputs(&mut shares)),
+            Err(SplitSecretError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn shares_from_different_splits() {
+        let header = ShareHeader::new(Scheme::Shamir, Cipher::None, Padding::None, 2, 0);
+        let mut shares = Vec::new();
+        for x in 1u16..=2 {
+            // Each share comes from its own split, so the set identifiers differ.
+            let mut share = Vec::new();
+            split_shares(
+                &ShareHeader::new(header.scheme, header.cipher, header.padding, 2, 0),
+                &b"secret"[..],
+                &mut [
+                    OutputPartition {
+                        x,
+                        writer: &mut share,
+                    },
+                    OutputPartition {
+                        x: 3,
+                        writer: &mut Vec::new(),
+                    },
+                ],
+            )
+            .unwrap();
+            shares.push(share);
+        }
+        let mut inputs: Vec<&[u8]> = shares.iter().map(|share| &share[..]).collect();
+        assert!(matches!(
+            join_shares(&mut inputs, Vec::new()),
+            Err(SplitSecretError::MismatchedHeader { x: 2 })
+        ));
+    }
+}
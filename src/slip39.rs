@@ -0,0 +1,849 @@
+use std::convert::TryFrom;
+
+use crate::error::{Result, SplitSecretError};
+use crate::field::{GaloisField, Gf2p8};
+use crate::mnemonic::{push_bits, suggestions, take_bits};
+use crate::poly::lagrange_eval;
+
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+// SLIP-0039 computes over GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1, unlike share
+// files.
+type Rijndael = Gf2p8<0x11b>;
+
+/// Shortest master secret, in bytes, that SLIP-0039 shares.
+pub const MIN_SECRET_SIZE: usize = 16;
+/// Most groups, and most members in a group.
+pub const MAX_SHARE_COUNT: u8 = 16;
+
+// The official SLIP-0039 English word list: 1024 words, sorted, and each one identified by its
+// first four letters.
+const WORD_LIST: &str = include_str!("slip39_english.txt");
+
+const BITS_PER_WORD: usize = 10;
+const ID_BITS: usize = 15;
+// The identifier, the extendable flag and the iteration exponent take the first two words, and
+// the group and member parameters the next two.
+const PREFIX_WORDS: usize = 4;
+const CHECKSUM_WORDS: usize = 3;
+const MIN_WORDS: usize =
+    PREFIX_WORDS + CHECKSUM_WORDS + (MIN_SECRET_SIZE * 8).div_ceil(BITS_PER_WORD);
+
+// The secret is interpolated at x = 255, and a share holding a digest of it at x = 254.
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_SIZE: usize = 4;
+
+// The passphrase encryption is a Feistel network of 4 rounds of PBKDF2, which together take
+// 10000 << e iterations.
+const ROUND_COUNT: u8 = 4;
+const BASE_ITERATION_COUNT: u32 = 10000;
+const MAX_ITERATION_EXPONENT: u8 = 15;
+
+fn invalid(message: String) -> SplitSecretError {
+    SplitSecretError::InvalidMnemonic(message)
+}
+
+fn hmac_sha256(key: &[u8], message: &[&[u8]]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in message {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut key = Vec::new();
+    for block in 1u32.. {
+        if key.len() >= len {
+            break;
+        }
+        let mut u = hmac_sha256(password, &[salt, &block.to_be_bytes()]);
+        let mut t = u;
+        for _ in 1..iterations {
+            u = hmac_sha256(password, &[&u]);
+            t.iter_mut().zip(u.iter()).for_each(|(t, u)| *t ^= u);
+        }
+        key.extend_from_slice(&t);
+    }
+    key.truncate(len);
+    key
+}
+
+// Encrypts the master secret with the passphrase, or decrypts it with the rounds reversed.
+fn feistel(
+    secret: &[u8],
+    passphrase: &[u8],
+    share: &Slip39Share,
+    rounds: impl Iterator<Item = u8>,
+) -> Vec<u8> {
+    let half = secret.len() / 2;
+    let (mut l, mut r) = (secret[..half].to_vec(), secret[half..].to_vec());
+    let salt = match share.extendable {
+        true => Vec::new(),
+        false => [&b"shamir"[..], &share.id.to_be_bytes()].concat(),
+    };
+    let iterations = (BASE_ITERATION_COUNT << share.iteration_exponent) / u32::from(ROUND_COUNT);
+    for round in rounds {
+        let password = [&[round][..], passphrase].concat();
+        let f = pbkdf2_hmac_sha256(&password, &[&salt[..], &r].concat(), iterations, r.len());
+        let next = l.iter().zip(f).map(|(l, f)| l ^ f).collect();
+        l = std::mem::replace(&mut r, next);
+    }
+    [r, l].concat()
+}
+
+// The value at `x` of the polynomials through `shares`, bytewise.
+fn interpolate(shares: &[(u8, &[u8])], x: u8) -> Vec<u8> {
+    let field = Rijndael::new();
+    let xs: Vec<u8> = shares.iter().map(|(x, _)| *x).collect();
+    let coefficients = &lagrange_eval(&field, &xs, &[x])[0];
+    let mut value = vec![0u8; shares[0].1.len()];
+    for ((_, share), coefficient) in shares.iter().zip(coefficients.iter()) {
+        field.add_scaled(&mut value, share, *coefficient);
+    }
+    value
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    hmac_sha256(random, &[secret])[..DIGEST_SIZE].to_vec()
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+// Shares `secret` among `count` holders at x = 0, 1, ..., any `threshold` of whom recover it. The
+// first threshold - 2 shares are random, and the polynomials also pass through the secret and a
+// share made of a digest of the secret and random bytes, so that recovery can check the result.
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<Vec<u8>> {
+    if threshold == 1 {
+        return vec![secret.to_vec(); count.into()];
+    }
+    let mut shares: Vec<Vec<u8>> = (0..threshold - 2)
+        .map(|_| random_bytes(secret.len()))
+        .collect();
+    let random = random_bytes(secret.len() - DIGEST_SIZE);
+    let digest_share = [digest(&random, secret), random].concat();
+    let mut base: Vec<(u8, &[u8])> = shares
+        .iter()
+        .zip(0u8..)
+        .map(|(share, x)| (x, &share[..]))
+        .collect();
+    base.push((DIGEST_INDEX, &digest_share));
+    base.push((SECRET_INDEX, secret));
+    let rest: Vec<Vec<u8>> = (threshold - 2..count)
+        .map(|x| interpolate(&base, x))
+        .collect();
+    shares.extend(rest);
+    shares
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, &[u8])]) -> Result<Vec<u8>> {
+    if threshold == 1 {
+        return Ok(shares[0].1.to_vec());
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest_share = interpolate(shares, DIGEST_INDEX);
+    if digest_share[..DIGEST_SIZE] != digest(&digest_share[DIGEST_SIZE..], &secret)[..] {
+        return Err(invalid(
+            "the shares fail their digest check; one is mistyped or from another split".into(),
+        ));
+    }
+    Ok(secret)
+}
+
+// RS1024, the Reed-Solomon code over GF(1024) that checksums the words of a share.
+fn rs1024_polymod(customization: &[u8], values: &[u16]) -> u32 {
+    const GENERATOR: [u32; 10] = [
+        0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009, 0x1c0c2412, 0x38086c24, 0x3090fc48,
+        0x21b1f890, 0x3f3f120,
+    ];
+    let mut checksum = 1u32;
+    for value in customization
+        .iter()
+        .map(|&c| u32::from(c))
+        .chain(values.iter().map(|&v| u32::from(v)))
+    {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xfffff) << 10) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// The SLIP-0039 word list, which every implementation shares.
+pub struct WordList {
+    words: Vec<String>,
+}
+
+impl WordList {
+    /// The official English word list.
+    pub fn english() -> Self {
+        WordList {
+            words: WORD_LIST.lines().map(String::from).collect(),
+        }
+    }
+
+    fn index(&self, position: usize, word: &str) -> Result<u16> {
+        let word = word.to_lowercase();
+        match self.words.binary_search(&word) {
+            Ok(index) => Ok(index as u16),
+            Err(_) => {
+                let list: Vec<&str> = self.words.iter().map(String::as_str).collect();
+                Err(SplitSecretError::UnknownWord {
+                    position: position + 1,
+                    suggestions: suggestions(&list, &word),
+                    word,
+                })
+            }
+        }
+    }
+}
+
+/// One SLIP-0039 share: a member share of a group share of the encrypted master secret.
+///
+/// Indices and thresholds are as written in the share, so indices count from 0 and thresholds
+/// from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Slip39Share {
+    /// Random identifier of the split, which all of its shares carry.
+    pub id: u16,
+    /// Whether the identifier is left out of the passphrase encryption, so that the master secret
+    /// can be split again under new identifiers with the same passphrase.
+    pub extendable: bool,
+    /// The passphrase encryption takes 10000 << `iteration_exponent` iterations of PBKDF2.
+    pub iteration_exponent: u8,
+    pub group_index: u8,
+    pub group_threshold: u8,
+    pub group_count: u8,
+    pub member_index: u8,
+    pub member_threshold: u8,
+    pub value: Vec<u8>,
+}
+
+impl Slip39Share {
+    fn customization(&self) -> &'static [u8] {
+        match self.extendable {
+            true => b"shamir_extendable",
+            false => b"shamir",
+        }
+    }
+
+    // Checks that every field fits in the bits the share has for it, and that the thresholds are
+    // at least 1 and the group threshold at most the group count.
+    fn check(&self) -> Result<()> {
+        for (name, value) in [
+            ("group threshold", self.group_threshold),
+            ("group count", self.group_count),
+            ("member threshold", self.member_threshold),
+        ] {
+            if value == 0 || value > MAX_SHARE_COUNT {
+                return Err(invalid(format!(
+                    "the {} of {} is not between 1 and {}",
+                    name, value, MAX_SHARE_COUNT
+                )));
+            }
+        }
+        if self.id >> ID_BITS != 0
+            || self.iteration_exponent > MAX_ITERATION_EXPONENT
+            || self.group_index >= MAX_SHARE_COUNT
+            || self.member_index >= MAX_SHARE_COUNT
+        {
+            return Err(invalid(
+                "the identifier, iteration exponent or an index is out of range".into(),
+            ));
+        }
+        if self.group_threshold > self.group_count {
+            return Err(invalid(format!(
+                "the group threshold of {} is more than the {} groups",
+                self.group_threshold, self.group_count
+            )));
+        }
+        Ok(())
+    }
+
+    /// The indices in the word list of the words of the share, checksum included. Fails if a
+    /// field is out of range, such as a threshold of 0.
+    pub fn to_indices(&self) -> Result<Vec<u16>> {
+        self.check()?;
+        let mut bits = Vec::new();
+        push_bits(&mut bits, self.id.into(), ID_BITS);
+        push_bits(&mut bits, self.extendable as u64, 1);
+        push_bits(&mut bits, self.iteration_exponent.into(), 4);
+        push_bits(&mut bits, self.group_index.into(), 4);
+        push_bits(&mut bits, u64::from(self.group_threshold) - 1, 4);
+        push_bits(&mut bits, u64::from(self.group_count) - 1, 4);
+        push_bits(&mut bits, self.member_index.into(), 4);
+        push_bits(&mut bits, u64::from(self.member_threshold) - 1, 4);
+        // The value is padded with zero bits at its start to whole words.
+        let padding = (BITS_PER_WORD - self.value.len() * 8 % BITS_PER_WORD) % BITS_PER_WORD;
+        push_bits(&mut bits, 0, padding);
+        for &byte in &self.value {
+            push_bits(&mut bits, byte.into(), 8);
+        }
+        let mut indices: Vec<u16> = bits
+            .chunks(BITS_PER_WORD)
+            .map(|word| take_bits(word) as u16)
+            .collect();
+        let checksum = rs1024_polymod(
+            self.customization(),
+            &[&indices[..], &[0; CHECKSUM_WORDS]].concat(),
+        ) ^ 1;
+        for i in (0..CHECKSUM_WORDS).rev() {
+            indices.push(((checksum >> (BITS_PER_WORD * i)) & 0x3ff) as u16);
+        }
+        Ok(indices)
+    }
+
+    /// Decodes and checks the words at `indices` in the word list.
+    pub fn from_indices(indices: &[u16]) -> Result<Self> {
+        if indices.len() < MIN_WORDS {
+            return Err(invalid(format!(
+                "a SLIP-0039 share has at least {} words, not {}",
+                MIN_WORDS,
+                indices.len()
+            )));
+        }
+        let value_words = indices.len() - PREFIX_WORDS - CHECKSUM_WORDS;
+        let padding = BITS_PER_WORD * value_words % 16;
+        if padding > 8 {
+            return Err(invalid(format!(
+                "{} words are the wrong number for a SLIP-0039 share",
+                indices.len()
+            )));
+        }
+
+        let mut bits = Vec::new();
+        for &index in &indices[..indices.len() - CHECKSUM_WORDS] {
+            push_bits(&mut bits, index.into(), BITS_PER_WORD);
+        }
+        let field = |start: usize, len: usize| take_bits(&bits[start..start + len]) as u8;
+        let share = Slip39Share {
+            id: take_bits(&bits[..ID_BITS]) as u16,
+            extendable: bits[ID_BITS],
+            iteration_exponent: field(16, 4),
+            group_index: field(20, 4),
+            group_threshold: field(24, 4) + 1,
+            group_count: field(28, 4) + 1,
+            member_index: field(32, 4),
+            member_threshold: field(36, 4) + 1,
+            value: bits[PREFIX_WORDS * BITS_PER_WORD + padding..]
+                .chunks(8)
+                .map(|byte| take_bits(byte) as u8)
+                .collect(),
+        };
+        if rs1024_polymod(share.customization(), indices) != 1 {
+            return Err(invalid("the checksum does not match".into()));
+        }
+        if bits[PREFIX_WORDS * BITS_PER_WORD..][..padding]
+            .iter()
+            .any(|&bit| bit)
+        {
+            return Err(invalid("the padding bits are not zero".into()));
+        }
+        share.check()?;
+        Ok(share)
+    }
+
+    /// The words of the share, separated by single spaces; see [`Slip39Share::to_indices`].
+    pub fn to_words(&self, list: &WordList) -> Result<String> {
+        let words: Vec<&str> = self
+            .to_indices()?
+            .into_iter()
+            .map(|index| list.words[usize::from(index)].as_str())
+            .collect();
+        Ok(words.join(" "))
+    }
+
+    /// Reads a share from its words, ignoring case and how they are spaced or broken over lines.
+    pub fn from_words(text: &str, list: &WordList) -> Result<Self> {
+        let indices = text
+            .split_whitespace()
+            .enumerate()
+            .map(|(position, word)| list.index(position, word))
+            .collect::<Result<Vec<u16>>>()?;
+        Slip39Share::from_indices(&indices)
+    }
+}
+
+/// Splits a master secret into SLIP-0039 shares, the mnemonic share format of Trezor and other
+/// hardware wallets. The master secret is encrypted with a passphrase, split among groups of
+/// which any `group_threshold` recover it, and each group share split again among its members.
+///
+/// Unlike [`crate::Shamir`], shares are indexed from 0, the field is GF(2^8) with the AES
+/// polynomial, and every split with a threshold above 1 includes a digest share that lets
+/// [`join_slip39`] detect a wrong share.
+///
+/// Shares written with [`WordList::english`] are interchangeable with those of other SLIP-0039
+/// implementations.
+pub struct Slip39 {
+    group_threshold: u8,
+    groups: Vec<(u8, u8)>,
+    iteration_exponent: u8,
+    extendable: bool,
+}
+
+impl Slip39 {
+    /// Creates a split where any `group_threshold` of `groups`, each given as its member
+    /// threshold and member count, recover the master secret.
+    pub fn new(group_threshold: u8, groups: &[(u8, u8)]) -> Result<Self> {
+        let group_count = u8::try_from(groups.len()).unwrap_or(u8::MAX);
+        if group_threshold == 0 || group_threshold > group_count || group_count > MAX_SHARE_COUNT {
+            return Err(SplitSecretError::ShareCount {
+                k: group_threshold,
+                n: groups.len(),
+            });
+        }
+        for &(k, n) in groups {
+            // Several shares of a group with threshold 1 would all be the same.
+            if k == 0 || k > n || n > MAX_SHARE_COUNT || (k == 1 && n > 1) {
+                return Err(SplitSecretError::ShareCount { k, n: n.into() });
+            }
+        }
+        Ok(Slip39 {
+            group_threshold,
+            groups: groups.to_vec(),
+            iteration_exponent: 1,
+            extendable: true,
+        })
+    }
+
+    /// Makes the passphrase encryption take 10000 << `e` iterations of PBKDF2 instead of 20000.
+    pub fn with_iteration_exponent(mut self, e: u8) -> Result<Self> {
+        if e > MAX_ITERATION_EXPONENT {
+            return Err(SplitSecretError::InvalidParameter(format!(
+                "the iteration exponent is at most {}",
+                MAX_ITERATION_EXPONENT
+            )));
+        }
+        self.iteration_exponent = e;
+        Ok(self)
+    }
+
+    /// Whether the shares are extendable, as they are by default; see
+    /// [`Slip39Share::extendable`]. Wallets that predate the flag only read shares without it.
+    pub fn with_extendable(mut self, extendable: bool) -> Self {
+        self.extendable = extendable;
+        self
+    }
+
+    /// Splits `master_secret`, an even number of at least [`MIN_SECRET_SIZE`] bytes, encrypted
+    /// with `passphrase`, into the shares of every group in turn.
+    pub fn split(&self, master_secret: &[u8], passphrase: &[u8]) -> Result<Vec<Vec<Slip39Share>>> {
+        check_passphrase(passphrase)?;
+        if master_secret.len() < MIN_SECRET_SIZE || master_secret.len() % 2 == 1 {
+            return Err(SplitSecretError::InvalidSecret(format!(
+                "SLIP-0039 shares an even number of at least {} bytes",
+                MIN_SECRET_SIZE
+            )));
+        }
+        let template = Slip39Share {
+            id: OsRng.gen::<u16>() >> (16 - ID_BITS),
+            extendable: self.extendable,
+            iteration_exponent: self.iteration_exponent,
+            group_index: 0,
+            group_threshold: self.group_threshold,
+            group_count: self.groups.len() as u8,
+            member_index: 0,
+            member_threshold: 0,
+            value: Vec::new(),
+        };
+        let encrypted = feistel(master_secret, passphrase, &template, 0..ROUND_COUNT);
+        let group_shares = split_secret(self.group_threshold, self.groups.len() as u8, &encrypted);
+        Ok(self
+            .groups
+            .iter()
+            .zip(group_shares)
+            .zip(0u8..)
+            .map(|((&(k, n), group_share), group_index)| {
+                split_secret(k, n, &group_share)
+                    .into_iter()
+                    .zip(0u8..)
+                    .map(|(value, member_index)| Slip39Share {
+                        group_index,
+                        member_index,
+                        member_threshold: k,
+                        value,
+                        ..template.clone()
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+// SLIP-0039 passphrases are printable ASCII, so that they can be typed on any device.
+fn check_passphrase(passphrase: &[u8]) -> Result<()> {
+    if passphrase.iter().any(|&c| !(32..=126).contains(&c)) {
+        return Err(SplitSecretError::InvalidSecret(
+            "a SLIP-0039 passphrase can only hold printable ASCII characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Recovers the master secret from SLIP-0039 shares, decrypting it with `passphrase`.
+///
+/// Enough members of enough groups have to be given; shares beyond that are ignored. As SLIP-0039
+/// intends, a wrong passphrase is not detected, but gives a different master secret.
+pub fn join_slip39(shares: &[Slip39Share], passphrase: &[u8]) -> Result<Vec<u8>> {
+    check_passphrase(passphrase)?;
+    let first = shares
+        .first()
+        .ok_or(SplitSecretError::ShareCount { k: 0, n: 0 })?;
+    for (i, share) in shares.iter().enumerate() {
+        share.check()?;
+        let same_split = share.id == first.id
+            && share.extendable == first.extendable
+            && share.iteration_exponent == first.iteration_exponent
+            && share.group_threshold == first.group_threshold
+            && share.group_count == first.group_count
+            && share.value.len() == first.value.len();
+        if !same_split {
+            return Err(invalid(format!(
+                "share {} is not from the same split as the first",
+                i + 1
+            )));
+        }
+    }
+
+    let mut group_shares = Vec::new();
+    for group_index in 0..first.group_count {
+        let mut members: Vec<&Slip39Share> = Vec::new();
+        for share in shares
+            .iter()
+            .filter(|share| share.group_index == group_index)
+        {
+            match members
+                .iter()
+                .find(|member| member.member_index == share.member_index)
+            {
+                Some(member) if member != &share => {
+                    return Err(invalid(format!(
+                        "two different shares are member {} of group {}",
+                        share.member_index + 1,
+                        group_index + 1
+                    )))
+                }
+                Some(_) => {}
+                None => members.push(share),
+            }
+        }
+        let threshold = match members.first() {
+            Some(member) => member.member_threshold,
+            None => continue,
+        };
+        if members
+            .iter()
+            .any(|member| member.member_threshold != threshold)
+        {
+            return Err(invalid(format!(
+                "the shares of group {} disagree on its threshold",
+                group_index + 1
+            )));
+        }
+        if members.len() < threshold.into() {
+            continue;
+        }
+        let members: Vec<(u8, &[u8])> = members[..threshold.into()]
+            .iter()
+            .map(|member| (member.member_index, &member.value[..]))
+            .collect();
+        group_shares.push((group_index, recover_secret(threshold, &members)?));
+    }
+
+    if group_shares.len() < first.group_threshold.into() {
+        return Err(invalid(format!(
+            "{} of the {} groups are complete, but {} are needed",
+            group_shares.len(),
+            first.group_count,
+            first.group_threshold
+        )));
+    }
+    let group_shares: Vec<(u8, &[u8])> = group_shares[..first.group_threshold.into()]
+        .iter()
+        .map(|(x, value)| (*x, &value[..]))
+        .collect();
+    let encrypted = recover_secret(first.group_threshold, &group_shares)?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first,
+        (0..ROUND_COUNT).rev(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn word_list() -> WordList {
+        WordList::english()
+    }
+
+    fn secret() -> Vec<u8> {
+        (0..16u8).map(|i| i.wrapping_mul(29) ^ 0x5a).collect()
+    }
+
+    #[test]
+    fn hmac_and_pbkdf2() {
+        // RFC 4231, test case 2, and PBKDF2-HMAC-SHA256 with one and two iterations.
+        assert_eq!(
+            hex(&hmac_sha256(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&pbkdf2_hmac_sha256(b"password", b"salt", 1, 32)),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        assert_eq!(
+            hex(&pbkdf2_hmac_sha256(b"password", b"salt", 2, 20)),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8e"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for (group_threshold, groups) in [
+            (1, vec![(1, 1)]),
+            (1, vec![(3, 5)]),
+            (2, vec![(1, 1), (2, 3), (3, 5)]),
+            (3, vec![(2, 2), (2, 3), (4, 4)]),
+        ] {
+            for extendable in [true, false] {
+                let slip39 = Slip39::new(group_threshold, &groups)
+                    .unwrap()
+                    .with_iteration_exponent(0)
+                    .unwrap()
+                    .with_extendable(extendable);
+                let groups = slip39.split(&secret(), b"TREZOR").unwrap();
+                // The last members of the last groups.
+                let shares: Vec<Slip39Share> = groups
+                    .iter()
+                    .rev()
+                    .take(group_threshold.into())
+                    .flat_map(|members| {
+                        let k = members[0].member_threshold;
+                        members.iter().rev().take(k.into()).cloned()
+                    })
+                    .collect();
+                assert_eq!(join_slip39(&shares, b"TREZOR").unwrap(), secret());
+                assert_ne!(join_slip39(&shares, b"").unwrap(), secret());
+            }
+        }
+    }
+
+    #[test]
+    fn words() {
+        let list = word_list();
+        let groups = Slip39::new(1, &[(2, 3)])
+            .unwrap()
+            .with_iteration_exponent(0)
+            .unwrap()
+            .split(&[7u8; 32], b"")
+            .unwrap();
+        let share = &groups[0][1];
+        let text = share.to_words(&list).unwrap();
+        assert_eq!(text.split(' ').count(), 33);
+        let typed = format!("  {}\n", text.to_uppercase().replace(' ', "\n  "));
+        assert_eq!(&Slip39Share::from_words(&typed, &list).unwrap(), share);
+
+        // Every changed word fails the checksum.
+        let indices = share.to_indices().unwrap();
+        for position in 0..indices.len() {
+            let mut changed = indices.clone();
+            changed[position] ^= 0x155;
+            assert!(Slip39Share::from_indices(&changed).is_err());
+        }
+
+        let mut words: Vec<String> = text.split(' ').map(String::from).collect();
+        words[4].push('q');
+        assert!(matches!(
+            Slip39Share::from_words(&words.join(" "), &list),
+            Err(SplitSecretError::UnknownWord { position: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn not_enough() {
+        let groups = Slip39::new(2, &[(2, 3), (2, 3)])
+            .unwrap()
+            .with_iteration_exponent(0)
+            .unwrap()
+            .split(&secret(), b"")
+            .unwrap();
+        // One complete group and one member of the other.
+        let shares = [
+            groups[0][0].clone(),
+            groups[0][2].clone(),
+            groups[1][1].clone(),
+        ];
+        assert!(matches!(
+            join_slip39(&shares, b""),
+            Err(SplitSecretError::InvalidMnemonic(_))
+        ));
+
+        // A share changed to a different value, with a valid checksum, fails the digest check.
+        let mut shares = vec![groups[0][0].clone(), groups[0][1].clone()];
+        shares.extend(groups[1].iter().cloned());
+        shares[1].value[3] ^= 1;
+        let shares: Vec<Slip39Share> = shares
+            .iter()
+            .map(|share| Slip39Share::from_indices(&share.to_indices().unwrap()).unwrap())
+            .collect();
+        assert!(join_slip39(&shares, b"").is_err());
+    }
+
+    // Cases from the official vectors.json, all with the passphrase "TREZOR".
+    #[test]
+    fn official_vectors() {
+        let list = word_list();
+        let join = |mnemonics: &[&str]| {
+            let shares = mnemonics
+                .iter()
+                .map(|mnemonic| Slip39Share::from_words(mnemonic, &list))
+                .collect::<Result<Vec<_>>>()?;
+            join_slip39(&shares, b"TREZOR").map(|secret| hex(&secret))
+        };
+        let invalid = |mnemonics: &[&str], message: &str| match join(mnemonics) {
+            Err(SplitSecretError::InvalidMnemonic(error)) => {
+                assert!(error.contains(message), "{}", error)
+            }
+            result => panic!("{:?}", result),
+        };
+
+        for (mnemonics, secret) in [
+            // Valid mnemonic without sharing (128 bits).
+            (
+                vec!["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard"],
+                "bb54aac4b89dc868ba37d9cc21b2cece",
+            ),
+            // Basic sharing 2-of-3 (128 bits).
+            (
+                vec![
+                    "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+                    "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+                ],
+                "b43ceb7e57a0ea8766221624d01b0864",
+            ),
+            // Threshold number of groups and members in each group (128 bits).
+            (
+                vec![
+                    "eraser senior decision roster beard treat identify grumpy salt index fake aviation theater cubic bike cause research dragon emphasis counter",
+                    "eraser senior ceramic snake clay various huge numb argue hesitate auction category timber browser greatest hanger petition script leaf pickup",
+                    "eraser senior ceramic shaft dynamic become junior wrist silver peasant force math alto coal amazing segment yelp velvet image paces",
+                    "eraser senior ceramic round column hawk trust auction smug shame alive greatest sheriff living perfect corner chest sled fumes adequate",
+                    "eraser senior decision smug corner ruin rescue cubic angel tackle skin skunk program roster trash rumor slush angel flea amazing",
+                    "eraser senior decision scared cargo theory device idea deliver modify curly include pancake both news skin realize vitamins away join",
+                ],
+                "7c3397a292a5941682d7a4ae2d898d11",
+            ),
+            // Basic sharing 2-of-3 (256 bits).
+            (
+                vec![
+                    "humidity disease academic always aluminum jewelry energy woman receiver strategy amuse duckling lying evidence network walnut tactics forget hairy rebound impulse brother survive clothes stadium mailman rival ocean reward venture always armed unwrap",
+                    "humidity disease academic agency actress jacket gross physics cylinder solution fake mortgage benefit public busy prepare sharp friar change work slow purchase ruler again tricycle involve viral wireless mixture anatomy desert cargo upgrade",
+                ],
+                "c938b319067687e990e05e0da0ecce1278f75ff58d9853f19dcaeed5de104aae",
+            ),
+            // Valid extendable mnemonic without sharing (128 bits).
+            (
+                vec!["testify swimming academic academic column loyalty smear include exotic bedroom exotic wrist lobe cover grief golden smart junior estimate learn"],
+                "1679b4516e0ee5954351d288a838f45e",
+            ),
+        ] {
+            assert_eq!(join(&mnemonics).unwrap(), secret);
+        }
+
+        // Mnemonic with invalid checksum (128 bits).
+        invalid(
+            &["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney"],
+            "checksum",
+        );
+        // Mnemonic with invalid padding (128 bits).
+        invalid(
+            &["duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness"],
+            "padding",
+        );
+        // Basic sharing 2-of-3, one share short (128 bits).
+        assert!(join(&[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+        ])
+        .is_err());
+        // Mnemonics with different identifiers (128 bits).
+        assert!(join(&[
+            "adequate smoking academic acid debut wine petition glen cluster slow rhyme slow simple epidemic rumor junk tracks treat olympic tolerate",
+            "adequate stay academic agency agency formal party ting frequent learn upstairs remember smear leaf damage anatomy ladle market hush corner",
+        ])
+        .is_err());
+        // Mnemonics with different iteration exponents (128 bits).
+        assert!(join(&[
+            "peasant leaves academic acid desert exact olympic math alive axle trial tackle drug deny decent smear dominant desert bucket remind",
+            "peasant leader academic agency cultural blessing percent network envelope medal junk primary human pumps jacket fragment payroll ticket evoke voice",
+        ])
+        .is_err());
+        // Mnemonic with group threshold greater than group count (128 bits).
+        invalid(
+            &["music husband acrobat acid artist finance center either graduate swimming object bike medical clothes station aspect spider maiden bulb welcome"],
+            "group threshold",
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(Slip39::new(3, &[(1, 1), (1, 1)]).is_err());
+        assert!(Slip39::new(1, &[(1, 2)]).is_err());
+        assert!(Slip39::new(1, &[(3, 2)]).is_err());
+        assert!(Slip39::new(1, &[(2, 17)]).is_err());
+        let slip39 = Slip39::new(1, &[(1, 1)]).unwrap();
+        assert!(slip39.split(&[0u8; 15], b"").is_err());
+        assert!(slip39.split(&[0u8; 17], b"").is_err());
+        assert!(slip39.split(&[0u8; 16], "pässword".as_bytes()).is_err());
+        assert!(matches!(
+            Slip39::new(1, &[(1, 1)])
+                .unwrap()
+                .with_iteration_exponent(16),
+            Err(SplitSecretError::InvalidParameter(_))
+        ));
+
+        // A share built with a threshold of 0 is neither written nor joined.
+        let mut share = slip39
+            .with_iteration_exponent(0)
+            .unwrap()
+            .split(&[0u8; 16], b"")
+            .unwrap()[0][0]
+            .clone();
+        share.member_threshold = 0;
+        assert!(matches!(
+            share.to_indices(),
+            Err(SplitSecretError::InvalidMnemonic(_))
+        ));
+        assert!(join_slip39(&[share], b"").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{Read, Result, Write};
+
+use cipher::StreamCipher;
+
+const BUF_SIZE: usize = 1024;
+
+/// Applies a stream cipher's keystream to everything read from the wrapped reader, which
+/// encrypts plaintext and decrypts ciphertext alike.
+pub struct StreamCipherReadStream<C, R>
+where
+    C: StreamCipher,
+    R: Read,
+{
+    cipher: C,
+    reader: R,
+}
+
+impl<C, R> StreamCipherReadStream<C, R>
+where
+    C: StreamCipher,
+    R: Read,
+{
+    pub fn new(cipher: C, reader: R) -> Self {
+        Self { cipher, reader }
+    }
+}
+
+impl<C, R> Read for StreamCipherReadStream<C, R>
+where
+    C: StreamCipher,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read_size = self.reader.read(buf)?;
+        self.cipher.apply_keystream(&mut buf[..read_size]);
+        Ok(read_size)
+    }
+}
+
+/// Applies a stream cipher's keystream to everything written to it before passing it to the
+/// wrapped writer.
+///
+/// A cipher that has been seeked to a position in the keystream decrypts a slice of ciphertext
+/// starting there.
+pub struct StreamCipherWriteStream<C, W>
+where
+    C: StreamCipher,
+    W: Write,
+{
+    cipher: C,
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<C, W> StreamCipherWriteStream<C, W>
+where
+    C: StreamCipher,
+    W: Write,
+{
+    pub fn new(cipher: C, writer: W) -> Self {
+        Self {
+            cipher,
+            writer,
+            buf: Vec::with_capacity(BUF_SIZE),
+        }
+    }
+}
+
+impl<C, W> Write for StreamCipherWriteStream<C, W>
+where
+    C: StreamCipher,
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let write_size = buf.len().min(BUF_SIZE);
+        self.buf.clear();
+        self.buf.extend_from_slice(&buf[..write_size]);
+        self.cipher.apply_keystream(&mut self.buf);
+        self.writer.write_all(&self.buf)?;
+        Ok(write_size)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}